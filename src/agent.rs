@@ -0,0 +1,492 @@
+use std::net::{UdpSocket,SocketAddr};
+use std::sync::{Arc,Mutex};
+use std::thread;
+use std::time::Duration;
+use errors::*;
+use oid::OID;
+use value::Value;
+use provider::{Provider,Collector};
+use tree::Tree;
+use host::Host;
+use config::Config;
+use filter::Filter;
+use pdu;
+use pdu::{Message,Pdu,PduType,Version};
+use mib_sys;
+use mib_disks;
+use mib_net;
+use mib_bonding;
+use mib_bridge;
+use mib_ip;
+use mib_netstat;
+use mib_sockets;
+use mib_extend;
+
+/// Largest response we send, which is the most that fits into a UDP datagram.
+const MAX_RESPONSE_SIZE: usize = 65507;
+
+/**
+ * Something the agent receives requests from and sends responses to.
+ */
+pub trait Transport: Send {
+    /**
+     * Receive requests and send back whatever handle returns for them.
+     * Only returns if the transport fails.
+     */
+    fn serve(&mut self, handle: &mut dyn FnMut(&[u8]) -> Option<Vec<u8>>) -> Result<()>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    pub fn bind(addr: SocketAddr) -> Result<UdpTransport> {
+        let socket = UdpSocket::bind(addr)
+            .chain_err(|| format!("Could not bind to {}", addr))?;
+        Ok(UdpTransport { socket })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn serve(&mut self, handle: &mut dyn FnMut(&[u8]) -> Option<Vec<u8>>) -> Result<()> {
+        let mut buf = [0u8; 16 * 1024];
+        loop {
+            if let Ok((data_len, client_addr)) = self.socket.recv_from(&mut buf) {
+                if let Some(response) = handle(&buf[0..data_len]) {
+                    self.socket.send_to(&response[..], client_addr)
+                        .chain_err(|| "Could not send")?;
+                }
+            }
+        }
+    }
+}
+
+/**
+ * An SNMP agent that answers requests from its transports with values from its providers.
+ */
+pub struct Agent {
+    communities: Vec<String>,
+    host:        Host,
+    tree:        Tree,
+    transports:  Vec<Box<dyn Transport>>,
+}
+
+impl Default for Agent {
+    fn default() -> Agent {
+        Agent::new()
+    }
+}
+
+impl Agent {
+    pub fn new() -> Agent {
+        Agent {
+            communities: Vec::new(),
+            host:        Host::default(),
+            tree:        Tree::new(),
+            transports:  Vec::new(),
+        }
+    }
+
+    /**
+     * Only answer requests with the given community.
+     */
+    pub fn community(self, community: &str) -> Agent {
+        self.communities(&[String::from(community)])
+    }
+
+    /**
+     * Only answer requests with one of the given communities. Without any,
+     * requests with every community are answered.
+     */
+    pub fn communities(mut self, communities: &[String]) -> Agent {
+        self.communities = communities.to_vec();
+        self
+    }
+
+    /**
+     * Where host_collectors find the host's /proc, /sys, /dev and mountpoints.
+     * Must be called before host_collectors.
+     */
+    pub fn host_root(mut self, root: &str) -> Agent {
+        self.host = Host::new(root);
+        self
+    }
+
+    /**
+     * Like host_root, for hosts that are recorded or replayed.
+     */
+    pub fn host(mut self, host: Host) -> Agent {
+        self.host = host;
+        self
+    }
+
+    /**
+     * Serve the subtrees of the given provider, caching its values for max_age.
+     */
+    pub fn provider<P: Provider + 'static>(mut self, max_age: Duration, provider: P) -> Agent {
+        self.tree.register(max_age, provider);
+        self
+    }
+
+    /**
+     * Serve the host data collected by the mib_* modules, as far as config enables them.
+     */
+    pub fn host_collectors(mut self, config: &Config) -> Agent {
+        let collectors = &config.collectors;
+        let included_interfaces = mib_net::IncludedInterfaces::new(
+            Filter::new(&config.filters.interfaces, mib_net::interface_defaults()), collectors.interfaces.max_age);
+        if collectors.system.enabled {
+            let host = self.host.clone();
+            self = self.provider(collectors.system.max_age, Collector::new(&["1.3.6.1.2.1.1"], move |values| {
+                mib_sys::get_system(values, &host, "1.3.6.1.2.1.1")
+            }));
+        }
+        if collectors.disks.enabled {
+            let host = self.host.clone();
+            let filter = Filter::new(&config.filters.disks, mib_disks::disk_defaults());
+            self = self.provider(collectors.disks.max_age, Collector::new(&["1.3.6.1.4.1.2021.13.15.1.1"], move |values| {
+                mib_disks::get_disks(values, &host, &filter, "1.3.6.1.4.1.2021.13.15.1.1")
+            }));
+        }
+        if collectors.filesystems.enabled {
+            let host = self.host.clone();
+            let filter = Filter::new(&config.filters.filesystems, mib_disks::filesystem_defaults());
+            self = self.provider(collectors.filesystems.max_age, Collector::new(&["1.3.6.1.2.1.25.2.3.1", "1.3.6.1.4.1.2021.9.1"], move |values| {
+                mib_disks::get_filesystems(values, &host, &filter, "1.3.6.1.2.1.25.2.3.1", "1.3.6.1.4.1.2021.9.1")
+            }));
+        }
+        if collectors.interfaces.enabled {
+            let host = self.host.clone();
+            let filter = Filter::new(&config.filters.interfaces, mib_net::interface_defaults());
+            let mut history = mib_net::InterfaceHistory::new();
+            let subtrees = mib_net::interface_subtrees("1.3.6.1.2.1");
+            let subtrees = subtrees.iter().map(String::as_str).collect::<Vec<&str>>();
+            self = self.provider(collectors.interfaces.max_age, Collector::new(&subtrees, move |values| {
+                mib_net::get_interfaces(values, &host, &filter, &mut history, "1.3.6.1.2.1")
+            }).with_tables(&[&mib_net::LINK_SETTINGS_TABLE, &mib_net::DRIVER_STATS_TABLE]));
+        }
+        if collectors.bonding.enabled {
+            let host = self.host.clone();
+            let filter = Filter::new(&config.filters.interfaces, mib_net::interface_defaults());
+            let subtrees = [mib_bonding::BOND_TABLE.entry_oid, mib_bonding::BOND_SLAVE_TABLE.entry_oid];
+            self = self.provider(collectors.bonding.max_age, Collector::new(&subtrees, move |values| {
+                mib_bonding::get_bonds(values, &host, &filter)
+            }).with_tables(&[&mib_bonding::BOND_TABLE, &mib_bonding::BOND_SLAVE_TABLE]));
+        }
+        if collectors.bridge.enabled {
+            let host = self.host.clone();
+            let filter = Filter::new(&config.filters.interfaces, mib_net::interface_defaults());
+            let subtrees = ["1.3.6.1.2.1.17", mib_bridge::BRIDGE_FDB_TABLE.entry_oid];
+            self = self.provider(collectors.bridge.max_age, Collector::new(&subtrees, move |values| {
+                mib_bridge::get_bridge(values, &host, &filter, "1.3.6.1.2.1.17")
+            }).with_tables(&[&mib_bridge::BRIDGE_FDB_TABLE]));
+        }
+        if collectors.addresses.enabled {
+            let host = self.host.clone();
+            let interfaces = included_interfaces.clone();
+            let subtrees = mib_ip::address_subtrees("1.3.6.1.2.1");
+            let subtrees = subtrees.iter().map(String::as_str).collect::<Vec<&str>>();
+            self = self.provider(collectors.addresses.max_age, Collector::new(&subtrees, move |values| {
+                mib_ip::get_addresses(values, &host, &interfaces, "1.3.6.1.2.1")
+            }));
+        }
+        if collectors.routes.enabled {
+            let host = self.host.clone();
+            self = self.provider(collectors.routes.max_age, Collector::new(&["1.3.6.1.2.1.4.24"], move |values| {
+                mib_ip::get_routes(values, &host, "1.3.6.1.2.1.4.24")
+            }));
+        }
+        if collectors.neighbors.enabled {
+            let host = self.host.clone();
+            let interfaces = included_interfaces.clone();
+            let subtrees = mib_ip::neighbor_subtrees("1.3.6.1.2.1");
+            let subtrees = subtrees.iter().map(String::as_str).collect::<Vec<&str>>();
+            self = self.provider(collectors.neighbors.max_age, Collector::new(&subtrees, move |values| {
+                mib_ip::get_neighbors(values, &host, &interfaces, "1.3.6.1.2.1")
+            }));
+        }
+        if collectors.netstat.enabled {
+            let host = self.host.clone();
+            let interfaces = included_interfaces.clone();
+            let subtrees = mib_netstat::netstat_subtrees("1.3.6.1.2.1");
+            let subtrees = subtrees.iter().map(String::as_str).collect::<Vec<&str>>();
+            self = self.provider(collectors.netstat.max_age, Collector::new(&subtrees, move |values| {
+                mib_netstat::get_protocol_stats(values, &host, "1.3.6.1.2.1");
+                mib_netstat::get_interface_stats(values, &host, &interfaces, "1.3.6.1.2.1")
+            }).with_tables(&[&mib_netstat::NETSTAT_TABLE]));
+        }
+        // Who owns sockets is only looked up if it may be shown
+        let socket_owners = if collectors.socket_processes.enabled {
+            Some(mib_sockets::SocketOwners::new(collectors.socket_processes.max_age))
+        }
+        else {
+            None
+        };
+        if collectors.sockets.enabled {
+            let host = self.host.clone();
+            let owners = socket_owners.clone();
+            let subtrees = mib_sockets::socket_subtrees("1.3.6.1.2.1");
+            let subtrees = subtrees.iter().map(String::as_str).collect::<Vec<&str>>();
+            self = self.provider(collectors.sockets.max_age, Collector::new(&subtrees, move |values| {
+                mib_sockets::get_sockets(values, &host, owners.as_ref(), "1.3.6.1.2.1")
+            }));
+        }
+        if let Some(owners) = socket_owners {
+            let host = self.host.clone();
+            self = self.provider(collectors.socket_processes.max_age, Collector::new(&[mib_sockets::PROCESS_TABLE.entry_oid], move |values| {
+                mib_sockets::get_socket_processes(values, &host, &owners)
+            }).with_tables(&[&mib_sockets::PROCESS_TABLE]));
+        }
+        if collectors.extend.enabled {
+            let extend = config.extend.clone();
+            self = self.provider(collectors.extend.max_age, Collector::new(&["1.3.6.1.4.1.8072.1.3.2.3.1"], move |values| {
+                mib_extend::get_extend(values, &extend, "1.3.6.1.4.1.8072.1.3.2.3.1")
+            }));
+        }
+        self
+    }
+
+    /**
+     * Also listen for requests on the given transport.
+     */
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Agent {
+        self.transports.push(Box::new(transport));
+        self
+    }
+
+    /**
+     * Collect all values in the given subtree without serving them to anyone.
+     */
+    pub fn walk(&mut self, subtree: &OID) -> Vec<(OID, Value<'static>)> {
+        self.tree.walk(subtree)
+    }
+
+    /**
+     * Serve requests on all transports. Only returns if one of them fails.
+     */
+    pub fn run(self) -> Result<()> {
+        if self.transports.is_empty() {
+            bail!("No transports to listen on");
+        }
+
+        let communities = Arc::new(self.communities);
+        let tree = Arc::new(Mutex::new(self.tree));
+
+        let workers = self.transports.into_iter()
+            .map(|mut transport| {
+                let communities = communities.clone();
+                let tree = tree.clone();
+                thread::spawn(move || {
+                    transport.serve(&mut |request| {
+                        let mut tree = tree.lock().unwrap();
+                        handle_request(&mut tree, &communities, request)
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            match worker.join() {
+                Ok(result) => result?,
+                Err(_)     => bail!("Transport thread panicked")
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Parse the request, look up the values it asks for and build the response.
+ * Requests with unknown communities are dropped, like RFC 1157 says. An empty
+ * list of communities accepts all of them.
+ */
+pub fn handle_request(tree: &mut Tree, communities: &[String], request: &[u8]) -> Option<Vec<u8>> {
+    let request = Message::decode(request).ok()?;
+    if !communities.is_empty() && !communities.iter().any(|community| community.as_bytes() == &request.community[..]) {
+        return None;
+    }
+    let version = request.version;
+    let req = request.pdu;
+
+    let mut resp = Pdu {
+        pdu_type:     PduType::Response,
+        request_id:   req.request_id,
+        error_status: pdu::NO_ERROR,
+        error_index:  0,
+        varbinds:     Vec::new(),
+    };
+
+    match req.pdu_type {
+        PduType::GetRequest => {
+            for (oid, _) in &req.varbinds {
+                let value = match tree.get(oid) {
+                    // SNMPv1 cannot carry Counter64, so it is not there as far as v1 is concerned (RFC 2576 4.1.2.1)
+                    Value::Counter64(_) if version == Version::V1 => Value::NoSuchObject,
+                    value => value
+                };
+                resp.varbinds.push((oid.clone(), value));
+            }
+        }
+        PduType::GetNextRequest => {
+            for (oid, _) in &req.varbinds {
+                resp.varbinds.push(get_next(tree, oid, version));
+            }
+        }
+        PduType::GetBulkRequest if version != Version::V1 => {
+            let non_repeaters = ::std::cmp::min(req.non_repeaters(), req.varbinds.len());
+            for (oid, _) in &req.varbinds[..non_repeaters] {
+                resp.varbinds.push(get_next(tree, oid, version));
+            }
+            // Walk the remaining varbinds in lockstep, like the columns of a table.
+            let mut cursors = req.varbinds[non_repeaters..].iter()
+                .map(|(oid, _)| oid.clone())
+                .collect::<Vec<OID>>();
+            for _ in 0..req.max_repetitions() {
+                if cursors.is_empty() || resp.varbinds.len() + cursors.len() > pdu::MAX_VARBINDS {
+                    break;
+                }
+                let mut reached_end = true;
+                for cursor in cursors.iter_mut() {
+                    let (next_oid, value) = get_next(tree, cursor, version);
+                    if value != Value::EndOfMibView {
+                        reached_end = false;
+                    }
+                    *cursor = next_oid.clone();
+                    resp.varbinds.push((next_oid, value));
+                }
+                if reached_end {
+                    break;
+                }
+            }
+        }
+        PduType::SetRequest => {
+            // We're read-only.
+            resp.error_status = pdu::NOT_WRITABLE;
+            resp.error_index  = 1;
+            resp.varbinds     = req.varbinds.clone();
+        }
+        PduType::InformRequest => {
+            // Acknowledge
+            resp.varbinds = req.varbinds.clone();
+        }
+        _ => return None
+    }
+
+    if version == Version::V1 {
+        // SNMPv1 has no exceptions in varbinds, only noSuchName errors.
+        let missing = resp.varbinds.iter()
+            .position(|(_, value)| matches!(*value, Value::NoSuchObject | Value::NoSuchInstance | Value::EndOfMibView));
+        if let Some(idx) = missing {
+            resp.error_status = pdu::NO_SUCH_NAME;
+            resp.error_index  = idx as i32 + 1;
+        }
+        if resp.error_status != pdu::NO_ERROR {
+            resp.error_status = pdu::NO_SUCH_NAME;
+            resp.varbinds     = req.varbinds.clone();
+        }
+    }
+
+    let mut response = Message {
+        version,
+        community: request.community,
+        pdu:       resp,
+    };
+    let mut encoded = response.encode();
+
+    while encoded.len() > MAX_RESPONSE_SIZE {
+        if req.pdu_type == PduType::GetBulkRequest && !response.pdu.varbinds.is_empty() {
+            // GetBulk responses may just be cut short
+            response.pdu.varbinds.pop();
+        }
+        else {
+            response.pdu.error_status = pdu::TOO_BIG;
+            response.pdu.error_index  = 0;
+            response.pdu.varbinds.clear();
+        }
+        encoded = response.encode();
+    }
+
+    Some(encoded)
+}
+
+/**
+ * Find the varbind following oid. SNMPv1 cannot transport Counter64 values, so skip those.
+ */
+fn get_next(tree: &mut Tree, oid: &OID, version: Version) -> (OID, Value<'static>) {
+    let mut cursor = oid.clone();
+    loop {
+        match tree.get_next(&cursor) {
+            Some((next_oid, Value::Counter64(_))) if version == Version::V1 => cursor = next_oid,
+            Some(found) => return found,
+            None        => return (oid.clone(), Value::EndOfMibView)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sunt_mib;
+
+    fn get_request(version: Version, community: &str, oid: &str) -> Vec<u8> {
+        Message {
+            version,
+            community: community.as_bytes().to_vec(),
+            pdu:       Pdu {
+                pdu_type:     PduType::GetRequest,
+                request_id:   42,
+                error_status: pdu::NO_ERROR,
+                error_index:  0,
+                varbinds:     vec![(OID::from_parts(&[oid]), Value::Null)],
+            },
+        }.encode()
+    }
+
+    #[test]
+    fn communities() {
+        let mut tree = Tree::new();
+        tree.register(Duration::new(15, 0), Collector::new(&["1.3.6.1.4.1.99999"], |values| {
+            values.insert(OID::from_parts(&["1.3.6.1.4.1.99999.1.0"]), Value::Integer(42));
+        }));
+        let communities = vec![String::from("public"), String::from("monitoring")];
+
+        assert_eq!(handle_request(&mut tree, &communities, &get_request(Version::V2c, "private", "1.3.6.1.4.1.99999.1.0")), None);
+        for community in &communities {
+            let response = Message::decode(&handle_request(&mut tree, &communities, &get_request(Version::V2c, community, "1.3.6.1.4.1.99999.1.0")).unwrap()).unwrap();
+            assert_eq!(&response.community[..], community.as_bytes());
+            assert_eq!(response.pdu.varbinds[0].1, Value::Integer(42));
+        }
+        assert!(handle_request(&mut tree, &[], &get_request(Version::V2c, "private", "1.3.6.1.4.1.99999.1.0")).is_some());
+    }
+
+    #[test]
+    fn counter64_in_v1() {
+        let mut tree = Tree::new();
+        tree.register(Duration::new(15, 0), Collector::new(&["1.3.6.1.2.1.31.1.1.1"], |values| {
+            // ifHCInOctets
+            values.insert(OID::from_parts(&["1.3.6.1.2.1.31.1.1.1.6.2"]), Value::Counter64(1 << 40));
+        }));
+        let mut get = |version| Message::decode(&handle_request(&mut tree, &[], &get_request(version, "public", "1.3.6.1.2.1.31.1.1.1.6.2")).unwrap()).unwrap().pdu;
+
+        let response = get(Version::V2c);
+        assert_eq!(response.error_status, pdu::NO_ERROR);
+        assert_eq!(response.varbinds[0].1, Value::Counter64(1 << 40));
+
+        let response = get(Version::V1);
+        assert_eq!(response.error_status, pdu::NO_SUCH_NAME);
+        assert_eq!(response.error_index, 1);
+    }
+
+    #[test]
+    fn mib_tables() {
+        let mut config = Config::default();
+        config.collectors.socket_processes.enabled = true;
+        let agent = Agent::new().host_collectors(&config);
+        // The SUNT-MIB is generated from the static list, which must not miss any
+        for table in agent.tree.tables() {
+            assert!(sunt_mib::TABLES.iter().any(|known| known.name == table.name), "{} is not in TABLES", table.name);
+        }
+        assert_eq!(agent.tree.tables().len(), sunt_mib::TABLES.len());
+    }
+}
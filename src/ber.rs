@@ -0,0 +1,348 @@
+use std::error;
+use std::fmt;
+
+// Universal types
+pub const BOOLEAN:           u8 = 0x01;
+pub const INTEGER:           u8 = 0x02;
+pub const OCTET_STRING:      u8 = 0x04;
+pub const NULL:              u8 = 0x05;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const SEQUENCE:          u8 = 0x30;
+
+// SNMPv2-SMI application types
+pub const IP_ADDRESS:        u8 = 0x40;
+pub const COUNTER32:         u8 = 0x41;
+pub const GAUGE32:           u8 = 0x42;
+pub const TIMETICKS:         u8 = 0x43;
+pub const OPAQUE:            u8 = 0x44;
+pub const COUNTER64:         u8 = 0x46;
+
+// Exceptions in varbinds of responses (RFC 3416)
+pub const NO_SUCH_OBJECT:    u8 = 0x80;
+pub const NO_SUCH_INSTANCE:  u8 = 0x81;
+pub const END_OF_MIB_VIEW:   u8 = 0x82;
+
+/// No single element may be longer than this, which is way more than fits into a UDP datagram.
+pub const MAX_LENGTH: usize = 65535;
+
+/// RFC 2578 limits OIDs to 128 sub-identifiers.
+pub const MAX_OID_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data ended in the middle of an element.
+    Truncated,
+    /// Indefinite-length form, which SNMP does not allow.
+    IndefiniteLength,
+    /// The length field is too wide or exceeds MAX_LENGTH.
+    LengthTooLong,
+    UnexpectedTag { expected: u8, found: u8 },
+    UnknownTag(u8),
+    /// Integer has no content octets.
+    EmptyInteger,
+    /// Integer does not fit into the type it is declared as.
+    IntegerOverflow,
+    InvalidOid,
+    OidTooLong,
+    InvalidNull,
+    InvalidIpAddress,
+    /// There are bytes left after the element that should have been the last one.
+    TrailingData,
+    UnsupportedVersion(i32),
+    UnsupportedPdu(u8),
+    TooManyVarbinds,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::Truncated             => write!(f, "data ends in the middle of an element"),
+            DecodeError::IndefiniteLength      => write!(f, "indefinite length is not allowed"),
+            DecodeError::LengthTooLong         => write!(f, "length exceeds {} bytes", MAX_LENGTH),
+            DecodeError::UnexpectedTag { expected, found } =>
+                write!(f, "expected tag 0x{:02x}, found 0x{:02x}", expected, found),
+            DecodeError::UnknownTag(tag)       => write!(f, "unknown tag 0x{:02x}", tag),
+            DecodeError::EmptyInteger          => write!(f, "integer without content"),
+            DecodeError::IntegerOverflow       => write!(f, "integer out of range"),
+            DecodeError::InvalidOid            => write!(f, "malformed object identifier"),
+            DecodeError::OidTooLong            => write!(f, "object identifier has more than {} parts", MAX_OID_LEN),
+            DecodeError::InvalidNull           => write!(f, "null with content"),
+            DecodeError::InvalidIpAddress      => write!(f, "ip address is not 4 bytes long"),
+            DecodeError::TrailingData          => write!(f, "trailing data after element"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported SNMP version {}", v),
+            DecodeError::UnsupportedPdu(tag)   => write!(f, "unsupported PDU type 0x{:02x}", tag),
+            DecodeError::TooManyVarbinds       => write!(f, "too many varbinds"),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+pub type DecodeResult<T> = Result<T, DecodeError>;
+
+/**
+ * Reads elements from a buffer of BER encoded data.
+ */
+pub struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Reader<'a> {
+        Reader { buf }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /**
+     * Make sure everything has been read.
+     */
+    pub fn finish(&self) -> DecodeResult<()> {
+        match self.buf.is_empty() {
+            true  => Ok(()),
+            false => Err(DecodeError::TrailingData)
+        }
+    }
+
+    pub fn peek_tag(&self) -> DecodeResult<u8> {
+        self.buf.first().cloned().ok_or(DecodeError::Truncated)
+    }
+
+    /**
+     * Read one element, returning its tag and content.
+     */
+    pub fn read_tlv(&mut self) -> DecodeResult<(u8, &'a [u8])> {
+        if self.buf.len() < 2 {
+            return Err(DecodeError::Truncated);
+        }
+        let tag = self.buf[0];
+        let first = self.buf[1];
+        let (len, header_len) =
+            if first & 0x80 == 0 {
+                (first as usize, 2)
+            }
+            else {
+                let width = (first & 0x7F) as usize;
+                if width == 0 {
+                    return Err(DecodeError::IndefiniteLength);
+                }
+                if width > 3 {
+                    return Err(DecodeError::LengthTooLong);
+                }
+                if self.buf.len() < 2 + width {
+                    return Err(DecodeError::Truncated);
+                }
+                let len = self.buf[2..2 + width].iter()
+                    .fold(0usize, |len, &byte| (len << 8) | byte as usize);
+                (len, 2 + width)
+            };
+        if len > MAX_LENGTH {
+            return Err(DecodeError::LengthTooLong);
+        }
+        if self.buf.len() < header_len + len {
+            return Err(DecodeError::Truncated);
+        }
+        let content = &self.buf[header_len..header_len + len];
+        self.buf = &self.buf[header_len + len..];
+        Ok((tag, content))
+    }
+
+    /**
+     * Read one element and return its content, making sure it has the given tag.
+     */
+    pub fn read_expected(&mut self, expected: u8) -> DecodeResult<&'a [u8]> {
+        let (found, content) = self.read_tlv()?;
+        if found != expected {
+            return Err(DecodeError::UnexpectedTag { expected, found });
+        }
+        Ok(content)
+    }
+
+    pub fn read_sequence(&mut self, tag: u8) -> DecodeResult<Reader<'a>> {
+        Ok(Reader::new(self.read_expected(tag)?))
+    }
+
+    pub fn read_i32(&mut self, tag: u8) -> DecodeResult<i32> {
+        decode_i32(self.read_expected(tag)?)
+    }
+
+    pub fn read_octets(&mut self, tag: u8) -> DecodeResult<&'a [u8]> {
+        self.read_expected(tag)
+    }
+}
+
+pub fn decode_i32(content: &[u8]) -> DecodeResult<i32> {
+    if content.is_empty() {
+        return Err(DecodeError::EmptyInteger);
+    }
+    if content.len() > 4 {
+        return Err(DecodeError::IntegerOverflow);
+    }
+    // Start with all ones for negative numbers so the sign gets extended
+    let init: i32 = if content[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(content.iter().fold(init, |val, &byte| (val << 8) | byte as i32))
+}
+
+/**
+ * Decode an unsigned integer that must fit into `bits` bits, such as a Counter32.
+ */
+pub fn decode_unsigned(content: &[u8], bits: u32) -> DecodeResult<u64> {
+    if content.is_empty() {
+        return Err(DecodeError::EmptyInteger);
+    }
+    if content[0] & 0x80 != 0 {
+        // Negative numbers are not unsigned
+        return Err(DecodeError::IntegerOverflow);
+    }
+    // A leading zero byte is needed if the highest bit of the value is set
+    let digits = if content[0] == 0 { &content[1..] } else { content };
+    if digits.len() * 8 > bits as usize {
+        return Err(DecodeError::IntegerOverflow);
+    }
+    Ok(digits.iter().fold(0u64, |val, &byte| (val << 8) | byte as u64))
+}
+
+pub fn decode_oid(content: &[u8]) -> DecodeResult<Vec<u32>> {
+    let mut parts = Vec::new();
+    let mut current: u64 = 0;
+    let mut in_subid = false;
+
+    for &byte in content {
+        if !in_subid && byte == 0x80 {
+            // Sub-identifiers must be encoded in as few bytes as possible
+            return Err(DecodeError::InvalidOid);
+        }
+        current = (current << 7) | (byte & 0x7F) as u64;
+        if current > u32::MAX as u64 {
+            return Err(DecodeError::InvalidOid);
+        }
+        in_subid = byte & 0x80 != 0;
+        if !in_subid {
+            if parts.is_empty() {
+                // The first sub-identifier encodes the first two parts
+                let first = ::std::cmp::min(current / 40, 2);
+                parts.push(first as u32);
+                parts.push((current - first * 40) as u32);
+            }
+            else {
+                parts.push(current as u32);
+            }
+            if parts.len() > MAX_OID_LEN {
+                return Err(DecodeError::OidTooLong);
+            }
+            current = 0;
+        }
+    }
+    if in_subid {
+        return Err(DecodeError::InvalidOid);
+    }
+    Ok(parts)
+}
+
+/**
+ * Writes BER encoded elements into a buffer.
+ */
+#[derive(Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer { buf: Vec::new() }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn write_tlv(&mut self, tag: u8, content: &[u8]) {
+        self.buf.push(tag);
+        let len = content.len();
+        if len < 0x80 {
+            self.buf.push(len as u8);
+        }
+        else {
+            let bytes = (len as u32).to_be_bytes();
+            let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+            self.buf.push(0x80 | (4 - skip) as u8);
+            self.buf.extend_from_slice(&bytes[skip..]);
+        }
+        self.buf.extend_from_slice(content);
+    }
+
+    /**
+     * Write a constructed element, whose content is written by the given closure.
+     */
+    pub fn write_sequence<F: FnOnce(&mut Writer)>(&mut self, tag: u8, write_content: F) {
+        let mut inner = Writer::new();
+        write_content(&mut inner);
+        self.write_tlv(tag, &inner.buf);
+    }
+
+    pub fn write_integer(&mut self, tag: u8, value: i64) {
+        let bytes = value.to_be_bytes();
+        // Skip bytes that only repeat the sign, as long as the next one still carries it
+        let mut skip = 0;
+        while skip < 7 {
+            let (this, next) = (bytes[skip], bytes[skip + 1]);
+            if (this == 0x00 && next & 0x80 == 0) || (this == 0xFF && next & 0x80 != 0) {
+                skip += 1;
+            }
+            else {
+                break;
+            }
+        }
+        self.write_tlv(tag, &bytes[skip..]);
+    }
+
+    pub fn write_unsigned(&mut self, tag: u8, value: u64) {
+        let bytes = value.to_be_bytes();
+        let skip = ::std::cmp::min(bytes.iter().take_while(|&&byte| byte == 0).count(), 7);
+        let mut content = Vec::with_capacity(9);
+        if bytes[skip] & 0x80 != 0 {
+            // Keep the value from being read as negative
+            content.push(0);
+        }
+        content.extend_from_slice(&bytes[skip..]);
+        self.write_tlv(tag, &content);
+    }
+
+    pub fn write_null(&mut self, tag: u8) {
+        self.write_tlv(tag, &[]);
+    }
+
+    pub fn write_oid(&mut self, parts: &[u32]) {
+        let mut content = Vec::with_capacity(parts.len() + 4);
+        let mut encode = |subid: u64| {
+            let mut septets = vec![(subid & 0x7F) as u8];
+            let mut rest = subid >> 7;
+            while rest > 0 {
+                septets.push(0x80 | (rest & 0x7F) as u8);
+                rest >>= 7;
+            }
+            content.extend(septets.iter().rev());
+        };
+        match parts.len() {
+            0 => (),
+            1 => encode(parts[0] as u64 * 40),
+            _ => {
+                encode(parts[0] as u64 * 40 + parts[1] as u64);
+                for &part in &parts[2..] {
+                    encode(part as u64);
+                }
+            }
+        }
+        self.write_tlv(OBJECT_IDENTIFIER, &content);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader,BufWriter,Write};
+use std::path::{Path,PathBuf,Component};
+use std::process;
+use errors::*;
+use host::{Host,CAPTURE_DATA,normalize};
+use tar;
+use tar::Entry;
+
+/// Give up on symlink loops at some point, like the kernel does
+const MAX_SYMLINKS: usize = 40;
+
+/**
+ * Add path to entries, along with every symlink on the way to it and whatever
+ * these point to. Paths that do not exist on the host are skipped, as are
+ * files that cannot be read, like /sys/class/net/<iface>/speed of links that are down.
+ */
+fn add_path(host: &Host, path: &Path, entries: &mut BTreeMap<PathBuf, Entry>, depth: usize) {
+    if depth > MAX_SYMLINKS {
+        return;
+    }
+    let parts = path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None
+        })
+        .collect::<Vec<_>>();
+    let mut current = PathBuf::from("/");
+
+    for (idx, part) in parts.iter().enumerate() {
+        current.push(part);
+        let meta = match host.lstat_raw(&current) {
+            Ok(meta) => meta,
+            Err(_)   => return
+        };
+        if meta.file_type().is_symlink() {
+            let target = match host.read_link_raw(&current) {
+                Ok(target) => target,
+                Err(_)     => return
+            };
+            let mut resolved = normalize(&current.parent().unwrap_or(Path::new("/")).join(&target));
+            for rest in &parts[idx + 1..] {
+                resolved.push(rest);
+            }
+            entries.insert(current, Entry::Symlink(target));
+            return add_path(host, &resolved, entries, depth + 1);
+        }
+        if idx + 1 < parts.len() {
+            continue;
+        }
+        let entry =
+            if meta.is_dir() {
+                Entry::Dir
+            }
+            else if meta.is_file() {
+                match host.read_raw(&current) {
+                    Ok(content) => Entry::File(content),
+                    Err(_)      => return
+                }
+            }
+            else {
+                // Device nodes only need to exist
+                Entry::File(Vec::new())
+            };
+        entries.entry(current.clone()).or_insert(entry);
+    }
+}
+
+/**
+ * Follow all symlinks in path, the way add_path does.
+ */
+fn resolve(host: &Host, path: &Path) -> PathBuf {
+    let mut current = PathBuf::from("/");
+    let mut pending = path.components().rev().collect::<Vec<_>>();
+    let mut followed = 0;
+    while let Some(component) = pending.pop() {
+        if let Component::Normal(part) = component {
+            current.push(part);
+            if let Ok(target) = host.read_link_raw(&current) {
+                followed += 1;
+                if followed > MAX_SYMLINKS {
+                    break;
+                }
+                current = normalize(&current.parent().unwrap_or(Path::new("/")).join(target));
+            }
+        }
+    }
+    current
+}
+
+/**
+ * Write everything the collectors of host accessed so far into a tar archive.
+ * host must have been set up with Host::record.
+ */
+pub fn capture(host: &Host, out: &Path) -> Result<()> {
+    let recording = host.recorded().ok_or("Host is not recording")?;
+    let mut entries = BTreeMap::new();
+
+    for path in &recording.paths {
+        add_path(host, path, &mut entries, 0);
+    }
+    for dir in &recording.dirs {
+        add_path(host, dir, &mut entries, 0);
+        if let Ok(children) = host.read_dir_raw(resolve(host, dir)) {
+            for child in children {
+                add_path(host, &child, &mut entries, 0);
+            }
+        }
+    }
+
+    let data = PathBuf::from(CAPTURE_DATA);
+    let statvfs = recording.statvfs.iter()
+        .map(|(mountpoint, stat)| format!("{} {} {} {} {} {} {} {}\n",
+            mountpoint, stat.frsize, stat.blocks, stat.bfree, stat.bavail, stat.files, stat.ffree, stat.fsid))
+        .collect::<String>();
+    entries.insert(data.clone(), Entry::Dir);
+    entries.insert(data.join("statvfs"), Entry::File(statvfs.into_bytes()));
+    if let Some(info) = recording.uname {
+        let uname = format!("{}\n{}\n{}\n{}\n{}\n", info.sysname, info.nodename, info.release, info.version, info.machine);
+        entries.insert(data.join("uname"), Entry::File(uname.into_bytes()));
+    }
+    for (name, dump) in recording.dumps {
+        entries.insert(data.join(name), Entry::File(dump));
+    }
+    let bits = |bits: &Vec<u32>| bits.iter().map(|bit| bit.to_string()).collect::<Vec<String>>().join(",");
+    let mut ethtool = recording.link_settings.iter()
+        .map(|(ifname, settings)| format!("{} settings speed={} duplex={} port={} autoneg={} supported={} advertising={}\n",
+            ifname, settings.speed.map_or(String::from("-"), |speed| speed.to_string()), settings.duplex,
+            settings.port, if settings.autoneg { 1 } else { 0 }, bits(&settings.supported), bits(&settings.advertising)))
+        .collect::<String>();
+    for (ifname, stats) in &recording.driver_stats {
+        for &(ref name, value) in stats {
+            ethtool.push_str(&format!("{} stat {} {}\n", ifname, name, value));
+        }
+    }
+    if !ethtool.is_empty() {
+        entries.insert(data.join("ethtool"), Entry::File(ethtool.into_bytes()));
+    }
+
+    let file = File::create(out)
+        .chain_err(|| format!("Could not create {}", out.display()))?;
+    let mut builder = tar::Builder::new(BufWriter::new(file));
+    for (path, entry) in &entries {
+        let mut name = path.to_str().ok_or("Path is not UTF-8")?.trim_start_matches('/').to_owned();
+        if *entry == Entry::Dir {
+            name.push('/');
+        }
+        builder.append(&name, entry)?;
+    }
+    builder.finish()?
+        .flush()
+        .chain_err(|| format!("Could not write {}", out.display()))?;
+    Ok(())
+}
+
+/**
+ * A host to replay a capture, which may be an archive or a directory it was extracted to.
+ */
+pub fn replay(capture: &Path) -> Result<Host> {
+    if capture.is_dir() {
+        return Host::replay(capture);
+    }
+    let archive = File::open(capture)
+        .chain_err(|| format!("Could not open {}", capture.display()))?;
+
+    // A fresh dir, so nothing of an earlier replay that was not cleaned up gets mixed in
+    let dir = (0..)
+        .map(|attempt| env::temp_dir().join(format!("sunt-replay-{}-{}", process::id(), attempt)))
+        .find(|dir| !dir.exists())
+        .unwrap();
+    fs::create_dir(&dir)
+        .chain_err(|| format!("Could not create {}", dir.display()))?;
+    if let Err(err) = tar::extract(BufReader::new(archive), &dir) {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(err);
+    }
+    Host::replay_temporary(dir)
+}
@@ -1,18 +1,664 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read};
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path,PathBuf};
+use std::slice;
+use std::time::Duration;
 
 use yaml_rust::{Yaml,YamlLoader};
+use yaml_rust::parser::{Event,MarkedEventReceiver,Parser as YamlParser};
+use yaml_rust::scanner::{Marker,ScanError};
+use yaml_rust::yaml::Hash;
+use errors::*;
+use mib::Mibs;
+use pattern::Pattern;
+use filter::{Rule,Action};
+use mib_disks::{FILESYSTEM_ATTRIBUTES,DISK_ATTRIBUTES};
+use mib_net::INTERFACE_ATTRIBUTES;
 
+/// Where net-snmp looks for MIBs on Debian and friends.
+const DEFAULT_MIB_DIRS: &[&str] = &[
+    "/usr/share/snmp/mibs",
+    "/usr/share/snmp/mibs/iana",
+    "/usr/share/snmp/mibs/ietf",
+    "/usr/share/mibs/site",
+    "/usr/share/mibs/iana",
+    "/usr/share/mibs/ietf",
+    "/usr/share/mibs/netsnmp",
+];
 
-pub fn load_conf(confpath: PathBuf) -> Option<Yaml> {
-    let conffile = &mut String::new();
-    File::open(confpath).ok()?
-        .read_to_string(conffile).ok()?;
+const TOP_LEVEL_KEYS: &[&str] = &["listen", "communities", "cache_time", "host_root", "mibdirs", "collectors", "filters", "extend"];
+const COLLECTOR_KEYS: &[&str] = &["enabled", "max_age"];
+const FILTER_KEYS:    &[&str] = &["filesystems", "disks", "interfaces"];
+const RULE_KEYS:      &[&str] = &["include", "exclude"];
+const EXTEND_KEYS:    &[&str] = &["cmd", "args"];
 
-    let mut confs = YamlLoader::load_from_str(conffile.as_str()).ok()?;
-    if confs.len() < 1 {
-        return None;
+/**
+ * Whether and how often one of the host collectors runs.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollectorConfig {
+    pub enabled: bool,
+    /// How long collected values are served before collecting them again
+    pub max_age: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collectors {
+    pub system:           CollectorConfig,
+    pub disks:            CollectorConfig,
+    pub filesystems:      CollectorConfig,
+    pub interfaces:       CollectorConfig,
+    pub bonding:          CollectorConfig,
+    pub bridge:           CollectorConfig,
+    pub addresses:        CollectorConfig,
+    pub routes:           CollectorConfig,
+    pub neighbors:        CollectorConfig,
+    pub netstat:          CollectorConfig,
+    pub sockets:          CollectorConfig,
+    /// Names of the processes owning sockets, which not everyone should see
+    pub socket_processes: CollectorConfig,
+    pub extend:           CollectorConfig,
+}
+
+impl Collectors {
+    fn new(max_age: Duration) -> Collectors {
+        let collector = CollectorConfig { enabled: true, max_age };
+        Collectors {
+            system:           collector.clone(),
+            disks:            collector.clone(),
+            filesystems:      collector.clone(),
+            interfaces:       collector.clone(),
+            bonding:          collector.clone(),
+            bridge:           collector.clone(),
+            addresses:        collector.clone(),
+            routes:           collector.clone(),
+            neighbors:        collector.clone(),
+            netstat:          collector.clone(),
+            sockets:          collector.clone(),
+            socket_processes: CollectorConfig { enabled: false, ..collector.clone() },
+            extend:           collector,
+        }
+    }
+
+    fn by_name(&mut self, name: &str) -> Option<&mut CollectorConfig> {
+        match name {
+            "system"           => Some(&mut self.system),
+            "disks"            => Some(&mut self.disks),
+            "filesystems"      => Some(&mut self.filesystems),
+            "interfaces"       => Some(&mut self.interfaces),
+            "bonding"          => Some(&mut self.bonding),
+            "bridge"           => Some(&mut self.bridge),
+            "addresses"        => Some(&mut self.addresses),
+            "routes"           => Some(&mut self.routes),
+            "neighbors"        => Some(&mut self.neighbors),
+            "netstat"          => Some(&mut self.netstat),
+            "sockets"          => Some(&mut self.sockets),
+            "socket_processes" => Some(&mut self.socket_processes),
+            "extend"           => Some(&mut self.extend),
+            _                  => None
+        }
+    }
+
+    pub fn all_mut(&mut self) -> Vec<&mut CollectorConfig> {
+        vec![&mut self.system, &mut self.disks, &mut self.filesystems, &mut self.interfaces, &mut self.bonding, &mut self.bridge, &mut self.addresses, &mut self.routes, &mut self.neighbors, &mut self.netstat, &mut self.sockets, &mut self.socket_processes, &mut self.extend]
+    }
+}
+
+/**
+ * Rules deciding what the collectors export, on top of their defaults.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Filters {
+    /// hrStorageTable and dskTable
+    pub filesystems: Vec<Rule>,
+    /// diskIOTable
+    pub disks:       Vec<Rule>,
+    /// ifTable and ifXTable
+    pub interfaces:  Vec<Rule>,
+}
+
+/**
+ * A command whose output is served in nsExtendOutput1Table.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extend {
+    pub name: String,
+    pub cmd:  String,
+    pub args: Vec<String>,
+}
+
+/**
+ * Everything that can be set in the config file. Command line flags override it.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub listen:      Vec<SocketAddr>,
+    /// Requests with other communities are ignored. Empty accepts any community.
+    pub communities: Vec<String>,
+    pub host_root:   String,
+    /// None means to look in the default directories
+    pub mibdirs:     Option<Vec<PathBuf>>,
+    pub collectors:  Collectors,
+    pub filters:     Filters,
+    /// In the order of the config file
+    pub extend:      Vec<Extend>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            listen:      vec!["[::]:161".parse().unwrap()],
+            communities: Vec::new(),
+            host_root:   String::from("/"),
+            mibdirs:     None,
+            collectors:  Collectors::new(Duration::new(15, 0)),
+            filters:     Filters::default(),
+            extend:      Vec::new(),
+        }
+    }
+}
+
+/**
+ * Where the values of a config file are defined, by their path.
+ *
+ * The Yaml values YamlLoader makes do not keep the markers of the events they are
+ * made of, so this receives the events of a second parse. Values of mappings are
+ * found at their keys, items of lists where they start.
+ */
+#[derive(Default)]
+struct Locations {
+    lines:  HashMap<Vec<String>, usize>,
+    path:   Vec<String>,
+    /// The mappings and lists around the current event
+    frames: Vec<Frame>,
+}
+
+enum Frame {
+    /// The key of the current entry, None while waiting for the next key
+    Mapping(Option<String>),
+    /// The index of the current item
+    Sequence(usize),
+}
+
+/**
+ * The line mark points to. Marker keeps it to itself, except when displaying a ScanError.
+ */
+fn line_of(mark: Marker) -> usize {
+    ScanError::new(mark, "").to_string()
+        .split_whitespace()
+        .nth(2)
+        .and_then(|line| line.parse().ok())
+        .unwrap_or(0)
+}
+
+impl Locations {
+    /**
+     * The line the value at path is defined on, or that of its closest parent
+     * if it is not there, e.g. because it is missing. 0 if nothing is found.
+     */
+    fn line(&self, path: &[String]) -> usize {
+        (0..path.len() + 1).rev()
+            .filter_map(|len| self.lines.get(&path[..len]))
+            .next()
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /**
+     * Record where a node starts, returning the name it has in its parent.
+     */
+    fn start(&mut self, mark: Marker, scalar: Option<&str>) -> Option<String> {
+        let name = match self.frames.last() {
+            None                                 => return None,
+            // A key, which is where errors about its value point to
+            Some(&Frame::Mapping(None))          => {
+                if let Some(key) = scalar {
+                    let mut path = self.path.clone();
+                    path.push(key.to_owned());
+                    self.lines.entry(path).or_insert_with(|| line_of(mark));
+                }
+                String::new()
+            }
+            Some(&Frame::Mapping(Some(ref key))) => key.clone(),
+            Some(&Frame::Sequence(idx))          => format!("[{}]", idx),
+        };
+        let mut path = self.path.clone();
+        path.push(name.clone());
+        self.lines.entry(path).or_insert_with(|| line_of(mark));
+        Some(name)
+    }
+
+    /**
+     * Move on to the next key, value or item after a node ended.
+     */
+    fn end(&mut self, scalar: Option<&str>) {
+        match self.frames.last_mut() {
+            Some(&mut Frame::Mapping(ref mut key)) => {
+                *key = match *key {
+                    None    => Some(scalar.unwrap_or("").to_owned()),
+                    Some(_) => None
+                };
+            }
+            Some(&mut Frame::Sequence(ref mut idx)) => *idx += 1,
+            None                                    => ()
+        }
+    }
+
+    fn open(&mut self, mark: Marker, frame: Frame) {
+        if let Some(name) = self.start(mark, None) {
+            self.path.push(name);
+        }
+        self.frames.push(frame);
+    }
+
+    fn close(&mut self) {
+        self.frames.pop();
+        if !self.frames.is_empty() {
+            self.path.pop();
+        }
+        self.end(None);
+    }
+}
+
+impl MarkedEventReceiver for Locations {
+    fn on_event(&mut self, event: &Event, mark: Marker) {
+        match *event {
+            Event::Scalar(ref value, ..) => {
+                self.start(mark, Some(value));
+                self.end(Some(value));
+            }
+            Event::Alias(_)              => {
+                self.start(mark, None);
+                self.end(None);
+            }
+            Event::SequenceStart(_)      => self.open(mark, Frame::Sequence(0)),
+            Event::MappingStart(_)       => self.open(mark, Frame::Mapping(None)),
+            Event::SequenceEnd |
+            Event::MappingEnd            => self.close(),
+            _                            => ()
+        }
+    }
+}
+
+fn child(path: &[String], key: &str) -> Vec<String> {
+    let mut path = path.to_vec();
+    path.push(String::from(key));
+    path
+}
+
+/**
+ * Turns the YAML of a config file into a Config, or an error pointing to
+ * what's wrong with it.
+ */
+struct Parser<'a> {
+    file:      &'a str,
+    locations: Locations,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, path: &[String], message: &str) -> Error {
+        let key = path.iter().fold(String::new(), |key, part| match key.is_empty() || part.starts_with('[') {
+            true  => key + part,
+            false => key + "." + part
+        });
+        ErrorKind::Config(String::from(self.file), self.locations.line(path), key, String::from(message)).into()
+    }
+
+    /**
+     * Entries of a mapping with string keys. Unless known is empty, other keys are errors.
+     */
+    fn entries<'y>(&self, yaml: &'y Yaml, path: &[String], known: &[&str]) -> Result<Vec<(String, &'y Yaml)>> {
+        let hash: &Hash = yaml.as_hash().ok_or_else(|| self.error(path, "must be a mapping"))?;
+        let mut entries = Vec::new();
+        for (key, value) in hash {
+            let key = self.string(key, path)?;
+            if !known.is_empty() && !known.contains(&key.as_str()) {
+                return Err(self.error(&child(path, &key), &format!("unknown key, expected one of {}", known.join(", "))));
+            }
+            entries.push((key, value));
+        }
+        Ok(entries)
+    }
+
+    fn string(&self, yaml: &Yaml, path: &[String]) -> Result<String> {
+        match *yaml {
+            Yaml::String(ref string) => Ok(string.clone()),
+            // Unquoted numbers make perfectly fine communities and arguments
+            Yaml::Integer(int)       => Ok(int.to_string()),
+            Yaml::Real(ref real)     => Ok(real.clone()),
+            _ => Err(self.error(path, "must be a string"))
+        }
+    }
+
+    /**
+     * A list of strings, or a single one.
+     */
+    fn strings(&self, yaml: &Yaml, path: &[String]) -> Result<Vec<String>> {
+        match yaml.as_vec() {
+            Some(items) => items.iter().enumerate()
+                .map(|(idx, item)| self.string(item, &child(path, &format!("[{}]", idx))))
+                .collect(),
+            None => Ok(vec![self.string(yaml, path)?])
+        }
+    }
+
+    fn boolean(&self, yaml: &Yaml, path: &[String]) -> Result<bool> {
+        yaml.as_bool().ok_or_else(|| self.error(path, "must be true or false"))
+    }
+
+    fn seconds(&self, yaml: &Yaml, path: &[String]) -> Result<Duration> {
+        match yaml.as_i64() {
+            Some(seconds) if seconds >= 0 => Ok(Duration::new(seconds as u64, 0)),
+            _ => Err(self.error(path, "must be a number of seconds"))
+        }
+    }
+
+    /**
+     * A glob, or a regex if given as { regex: ... }.
+     */
+    fn pattern(&self, yaml: &Yaml, path: &[String]) -> Result<Pattern> {
+        let (path, pattern) = match yaml.as_hash() {
+            Some(_) => {
+                let entries = self.entries(yaml, path, &["regex"])?;
+                let path = child(path, "regex");
+                let regex = entries.first().ok_or_else(|| self.error(&path, "is missing"))?.1;
+                (path.clone(), Pattern::regex(&self.string(regex, &path)?))
+            }
+            None => (path.to_vec(), Pattern::glob(&self.string(yaml, path)?))
+        };
+        pattern.map_err(|err| self.error(&path, &err.to_string()))
+    }
+
+    fn rules(&self, yaml: &Yaml, path: &[String], attributes: &[&str]) -> Result<Vec<Rule>> {
+        let items = yaml.as_vec().ok_or_else(|| self.error(path, "must be a list of rules"))?;
+        let mut rules = Vec::new();
+        for (idx, item) in items.iter().enumerate() {
+            let path = child(path, &format!("[{}]", idx));
+            let entries = self.entries(item, &path, RULE_KEYS)?;
+            if entries.len() != 1 {
+                return Err(self.error(&path, "must either include or exclude"));
+            }
+            let (ref action, conditions) = entries[0];
+            let path = child(&path, action);
+            let mut rule = Rule {
+                action:     if action == "include" { Action::Include } else { Action::Exclude },
+                conditions: Vec::new(),
+            };
+            for (attribute, patterns) in self.entries(conditions, &path, attributes)? {
+                let path = child(&path, &attribute);
+                let patterns = match patterns.as_vec() {
+                    Some(items) => items.iter().enumerate()
+                        .map(|(idx, item)| self.pattern(item, &child(&path, &format!("[{}]", idx))))
+                        .collect::<Result<Vec<Pattern>>>()?,
+                    None => vec![self.pattern(patterns, &path)?]
+                };
+                rule.conditions.push((attribute, patterns));
+            }
+            rules.push(rule);
+        }
+        Ok(rules)
+    }
+
+    fn config(&self, yaml: &Yaml) -> Result<Config> {
+        let mut config = Config::default();
+        if yaml.is_null() || yaml.is_badvalue() {
+            // Empty file
+            return Ok(config);
+        }
+        let entries = self.entries(yaml, &[], TOP_LEVEL_KEYS)?;
+
+        // Collectors default to cache_time, wherever it is in the file
+        for &(ref key, value) in &entries {
+            if key == "cache_time" {
+                config.collectors = Collectors::new(self.seconds(value, slice::from_ref(key))?);
+            }
+        }
+
+        for (key, value) in entries {
+            let path = vec![key.clone()];
+            match key.as_str() {
+                "listen" => {
+                    config.listen = Vec::new();
+                    for (idx, addr) in self.strings(value, &path)?.into_iter().enumerate() {
+                        let addr = addr.parse::<SocketAddr>()
+                            .map_err(|_| self.error(&child(&path, &format!("[{}]", idx)), "must be an address like [::]:161 or 127.0.0.1:161"))?;
+                        config.listen.push(addr);
+                    }
+                    if config.listen.is_empty() {
+                        return Err(self.error(&path, "must list at least one address"));
+                    }
+                }
+                "communities" => {
+                    config.communities = self.strings(value, &path)?;
+                    if config.communities.is_empty() {
+                        return Err(self.error(&path, "must list at least one community"));
+                    }
+                }
+                "host_root" => config.host_root = self.string(value, &path)?,
+                "mibdirs"   => config.mibdirs = Some(self.strings(value, &path)?.into_iter().map(PathBuf::from).collect()),
+                "collectors" => {
+                    for (name, collector) in self.entries(value, &path, &[])? {
+                        let path = child(&path, &name);
+                        let known = config.collectors.by_name(&name).is_some();
+                        if !known {
+                            return Err(self.error(&path, "unknown collector, expected one of system, disks, filesystems, interfaces, bonding, bridge, addresses, routes, neighbors, netstat, sockets, socket_processes, extend"));
+                        }
+                        for (key, value) in self.entries(collector, &path, COLLECTOR_KEYS)? {
+                            let path = child(&path, &key);
+                            let enabled = if key == "enabled" { Some(self.boolean(value, &path)?) } else { None };
+                            let max_age = if key == "max_age" { Some(self.seconds(value, &path)?) } else { None };
+                            let conf = config.collectors.by_name(&name).unwrap();
+                            conf.enabled = enabled.unwrap_or(conf.enabled);
+                            conf.max_age = max_age.unwrap_or(conf.max_age);
+                        }
+                    }
+                }
+                "filters" => {
+                    for (section, rules) in self.entries(value, &path, FILTER_KEYS)? {
+                        let path = child(&path, &section);
+                        match section.as_str() {
+                            "filesystems" => config.filters.filesystems = self.rules(rules, &path, FILESYSTEM_ATTRIBUTES)?,
+                            "disks"       => config.filters.disks       = self.rules(rules, &path, DISK_ATTRIBUTES)?,
+                            _             => config.filters.interfaces  = self.rules(rules, &path, INTERFACE_ATTRIBUTES)?,
+                        }
+                    }
+                }
+                "extend" => {
+                    for (name, command) in self.entries(value, &path, &[])? {
+                        let path = child(&path, &name);
+                        let mut extend = Extend { name, cmd: String::new(), args: Vec::new() };
+                        for (key, value) in self.entries(command, &path, EXTEND_KEYS)? {
+                            match key.as_str() {
+                                "cmd" => extend.cmd  = self.string(value, &child(&path, &key))?,
+                                _     => extend.args = self.strings(value, &child(&path, &key))?,
+                            }
+                        }
+                        if extend.cmd.is_empty() {
+                            return Err(self.error(&path, "needs a cmd"));
+                        }
+                        config.extend.push(extend);
+                    }
+                }
+                _ => ()
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Config {
+    /**
+     * Parse the config in source. file is what errors call it.
+     */
+    pub fn parse(source: &str, file: &str) -> Result<Config> {
+        let mut docs = YamlLoader::load_from_str(source)
+            .map_err(|err| Error::from(format!("{}: {}", file, err)))?;
+        if docs.len() > 1 {
+            bail!("{}: must contain a single YAML document", file);
+        }
+        let mut locations = Locations::default();
+        YamlParser::new(source.chars()).load(&mut locations, false)
+            .map_err(|err| Error::from(format!("{}: {}", file, err)))?;
+        let parser = Parser { file, locations };
+        parser.config(&docs.pop().unwrap_or(Yaml::Null))
+    }
+
+    pub fn load(path: &Path) -> Result<Config> {
+        let mut source = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut source))
+            .chain_err(|| format!("Could not read {}", path.display()))?;
+        Config::parse(&source, &path.display().to_string())
+    }
+}
+
+/**
+ * Load the MIBs from the directories listed under `mibdirs` in the config,
+ * or from whichever of the default directories exist.
+ */
+pub fn load_mibs(config: &Config) -> Result<Mibs> {
+    let mut mibs = Mibs::new();
+    match config.mibdirs {
+        Some(ref dirs) => {
+            for dir in dirs {
+                mibs.load_dir(dir)?;
+            }
+        }
+        None => {
+            for dir in DEFAULT_MIB_DIRS {
+                if Path::new(dir).is_dir() {
+                    mibs.load_dir(Path::new(dir))?;
+                }
+            }
+        }
+    }
+    Ok(mibs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const EXAMPLE: &str = "
+listen:
+  - '[::]:161'
+  - 127.0.0.1:1161
+communities: [public, 12345]
+cache_time: 30
+
+collectors:
+  disks:
+    enabled: false
+  interfaces: { max_age: 5 }
+
+extend:
+  echo: { cmd: /bin/echo, args: ['testing'] }
+  'true':
+    cmd: /bin/true
+";
+
+    fn error_of(source: &str) -> (usize, String, String) {
+        match Config::parse(source, "sunt.yaml").unwrap_err().kind() {
+            &ErrorKind::Config(ref file, line, ref key, ref message) => {
+                assert_eq!(file, "sunt.yaml");
+                (line, key.clone(), message.clone())
+            }
+            other => panic!("unexpected error {}", other)
+        }
+    }
+
+    #[test]
+    fn parse_example() {
+        let config = Config::parse(EXAMPLE, "sunt.yaml").unwrap();
+        assert_eq!(config.listen, vec!["[::]:161".parse().unwrap(), "127.0.0.1:1161".parse().unwrap()]);
+        assert_eq!(config.communities, vec!["public", "12345"]);
+        assert_eq!(config.host_root, "/");
+        assert_eq!(config.collectors.system, CollectorConfig { enabled: true, max_age: Duration::new(30, 0) });
+        assert_eq!(config.collectors.disks, CollectorConfig { enabled: false, max_age: Duration::new(30, 0) });
+        assert_eq!(config.collectors.interfaces, CollectorConfig { enabled: true, max_age: Duration::new(5, 0) });
+        assert_eq!(config.extend, vec![
+            Extend { name: String::from("echo"), cmd: String::from("/bin/echo"), args: vec![String::from("testing")] },
+            Extend { name: String::from("true"), cmd: String::from("/bin/true"), args: vec![] },
+        ]);
+    }
+
+    #[test]
+    fn empty_config() {
+        assert_eq!(Config::parse("", "sunt.yaml").unwrap(), Config::default());
+        assert_eq!(Config::parse("# nothing to see here\n", "sunt.yaml").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn errors_point_to_keys() {
+        assert_eq!(error_of(&EXAMPLE.replace("cache_time: 30", "cache_time: soon")),
+            (6, String::from("cache_time"), String::from("must be a number of seconds")));
+        assert_eq!(error_of(&EXAMPLE.replace("enabled: false", "enabled: nope")),
+            (10, String::from("collectors.disks.enabled"), String::from("must be true or false")));
+        assert_eq!(error_of(&EXAMPLE.replace("max_age: 5", "max_age: -5")),
+            (11, String::from("collectors.interfaces.max_age"), String::from("must be a number of seconds")));
+        assert_eq!(error_of(&EXAMPLE.replace("127.0.0.1:1161", "localhost")).0, 4);
+        assert_eq!(error_of(&EXAMPLE.replace("127.0.0.1:1161", "localhost")).1, "listen[1]");
+        assert_eq!(error_of(&EXAMPLE.replace("cmd: /bin/true", "command: /bin/true")).0, 16);
+        assert_eq!(error_of(&EXAMPLE.replace("  disks:", "  diskz:")).1, "collectors.diskz");
+        assert_eq!(error_of(&EXAMPLE.replace("cache_time", "cachetime")).1, "cachetime");
+        assert_eq!(error_of("extend: [echo]").1, "extend");
+        // Not fooled by keys in comments, strings or other sections
+        assert_eq!(error_of("collectors:\n  system: { max_age: 5 }\n  disks:\n    # max_age: 10\n    enabled: \"max_age: 1\"\n").0, 5);
+        assert_eq!(error_of("collectors:\n  system: { max_age: 5 }\n  disks:\n    # max_age: 10\n    max_age: soon\n").0, 5);
+    }
+
+    #[test]
+    fn filters() {
+        let config = Config::parse("
+filters:
+  interfaces:
+    - include: { name: wg0 }
+    - exclude:
+        class: [virtual, tunnel]
+", "sunt.yaml").unwrap();
+        assert_eq!(config.filters.interfaces, vec![
+            Rule::globs(Action::Include, &[("name", "wg0")]),
+            Rule {
+                action:     Action::Exclude,
+                conditions: vec![(String::from("class"), vec![Pattern::glob("virtual").unwrap(), Pattern::glob("tunnel").unwrap()])],
+            },
+        ]);
+
+        assert_eq!(error_of("filters:\n  interfaces:\n    - include: { name: wg0 }\n    - exclude:\n        name: { regex: '(eth' }\n"),
+            (5, String::from("filters.interfaces[1].exclude.name.regex"), String::from("Invalid pattern \"(eth\": unclosed group")));
+        assert_eq!(error_of("filters:\n  disks:\n    - exclude: { fstype: ext4 }\n").0, 3);
+        assert_eq!(error_of("filters:\n  disks:\n    - exclude: { fstype: ext4 }\n").1, "filters.disks[0].exclude.fstype");
+        assert_eq!(error_of("filters:\n  disks:\n    - { include: {}, exclude: {} }\n").2, "must either include or exclude");
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(Config::parse("listen: [", "sunt.yaml").unwrap_err().to_string().starts_with("sunt.yaml: "));
+    }
+
+    #[test]
+    fn mibdirs() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("UCD-DISKIO-MIB.txt"), "\
+UCD-DISKIO-MIB DEFINITIONS ::= BEGIN
+ucdExperimental OBJECT IDENTIFIER ::= { iso 3 6 1 4 1 2021 13 }
+ucdDiskIOMIB    OBJECT IDENTIFIER ::= { ucdExperimental 15 }
+diskIOTable     OBJECT-TYPE ::= { ucdDiskIOMIB 1 }
+END
+").unwrap();
+
+        let config = Config::parse(&format!("mibdirs: [{}]", dir.path().display()), "sunt.yaml").unwrap();
+        let mibs = load_mibs(&config).unwrap();
+        dir.close().unwrap();
+        let oid = mibs.resolve_name("UCD-DISKIO-MIB::diskIOTable").unwrap();
+        assert_eq!(oid.to_string(), "1.3.6.1.4.1.2021.13.15.1");
+        assert_eq!(mibs.name_of(&oid), "UCD-DISKIO-MIB::diskIOTable");
+
+        // Listed directories have to exist, unlike the default ones
+        assert!(load_mibs(&config).is_err());
     }
-    Some(confs.pop().unwrap())
 }
@@ -0,0 +1,224 @@
+use std::str;
+use yaml_rust::{Yaml,YamlEmitter};
+use yaml_rust::yaml::Hash;
+use errors::*;
+use oid::OID;
+use value::Value;
+use mib::Mibs;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Like the output of snmpwalk
+    Text,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Format> {
+        match name {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            _      => bail!("Unknown output format {}, expected text, json or yaml", name)
+        }
+    }
+}
+
+/**
+ * Octet strings are shown as text if they are printable, and as hex otherwise.
+ */
+fn printable(bytes: &[u8]) -> Option<&str> {
+    str::from_utf8(bytes).ok()
+        .filter(|text| !text.chars().any(|chr| chr.is_control() && chr != '\n' && chr != '\t'))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/**
+ * Type and value of a varbind, the way snmpwalk names and formats them.
+ */
+fn describe(value: &Value) -> (&'static str, String) {
+    let octets = |bytes: &[u8]| match printable(bytes) {
+        Some(text) => ("STRING", String::from(text)),
+        None       => ("Hex-STRING", hex(bytes))
+    };
+    match *value {
+        Value::Boolean(bool_)            => ("BOOLEAN", bool_.to_string()),
+        Value::Null                      => ("NULL", String::new()),
+        Value::Integer(i64_)             => ("INTEGER", i64_.to_string()),
+        Value::OctetString(ref string)   => octets(string.as_bytes()),
+        Value::OctetStr(str_)            => octets(str_.as_bytes()),
+        Value::Octets(ref bytes)         => octets(bytes),
+        Value::ObjectIdentifier(ref oid) => ("OID", format!(".{}", oid)),
+        Value::IpAddress(ip)             => ("IpAddress", format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3])),
+        Value::Counter32(u64_)           => ("Counter32", (u64_ & 0xFFFFFFFF).to_string()),
+        Value::Unsigned32(u32_)          => ("Gauge32", u32_.to_string()),
+        Value::Timeticks(u32_)           => ("Timeticks", u32_.to_string()),
+        Value::Opaque(ref bytes)         => ("OPAQUE", hex(bytes)),
+        Value::Counter64(u64_)           => ("Counter64", u64_.to_string()),
+        Value::NoSuchObject              => ("NoSuchObject", String::new()),
+        Value::NoSuchInstance            => ("NoSuchInstance", String::new()),
+        Value::EndOfMibView              => ("EndOfMibView", String::new()),
+    }
+}
+
+/**
+ * Values that are numbers in SNMP should also be numbers in JSON and YAML.
+ */
+fn is_numeric(value: &Value) -> bool {
+    matches!(*value, Value::Integer(_) | Value::Counter32(_) | Value::Unsigned32(_) |
+                     Value::Timeticks(_) | Value::Counter64(_))
+}
+
+/**
+ * Format timeticks like snmpwalk does: (ticks) days, h:mm:ss.cc
+ */
+fn timeticks(ticks: u32) -> String {
+    let centis = ticks % 100;
+    let secs = ticks / 100;
+    let (days, hours, mins, secs) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    match days {
+        0 => format!("({}) {}:{:02}:{:02}.{:02}", ticks, hours, mins, secs, centis),
+        1 => format!("({}) 1 day, {}:{:02}:{:02}.{:02}", ticks, hours, mins, secs, centis),
+        _ => format!("({}) {} days, {}:{:02}:{:02}.{:02}", ticks, days, hours, mins, secs, centis),
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for chr in text.chars() {
+        match chr {
+            '"'  => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            chr if (chr as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr  => quoted.push(chr),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/**
+ * Render the varbinds in the given format. OIDs are translated to names where
+ * the MIBs know them.
+ */
+pub fn render(varbinds: &[(OID, Value)], mibs: &Mibs, format: Format) -> Result<String> {
+    match format {
+        Format::Text => {
+            let mut out = String::new();
+            for (oid, value) in varbinds {
+                let name = mibs.name_of(oid);
+                let name = if name == oid.to_string() { format!(".{}", oid) } else { name };
+                let (type_name, text) = describe(value);
+                let text = match *value {
+                    Value::Timeticks(ticks) => timeticks(ticks),
+                    _                       => text
+                };
+                out.push_str(&format!("{} = {}: {}\n", name, type_name, text));
+            }
+            Ok(out)
+        }
+        Format::Json => {
+            let rows = varbinds.iter()
+                .map(|(oid, value)| {
+                    let (type_name, text) = describe(value);
+                    format!("  {{\"oid\": {}, \"name\": {}, \"type\": {}, \"value\": {}}}",
+                        json_string(&oid.to_string()),
+                        json_string(&mibs.name_of(oid)),
+                        json_string(type_name),
+                        if is_numeric(value) { text } else { json_string(&text) })
+                })
+                .collect::<Vec<String>>();
+            Ok(format!("[\n{}\n]\n", rows.join(",\n")))
+        }
+        Format::Yaml => {
+            let rows = varbinds.iter()
+                .map(|(oid, value)| {
+                    let (type_name, text) = describe(value);
+                    let text = match is_numeric(value) {
+                        // Counter64 values may not fit into an i64, so leave those as they are
+                        true  => text.parse::<i64>().map(Yaml::Integer).unwrap_or(Yaml::Real(text)),
+                        false => Yaml::String(text)
+                    };
+                    let mut row = Hash::new();
+                    row.insert(Yaml::String(String::from("oid")),   Yaml::String(oid.to_string()));
+                    row.insert(Yaml::String(String::from("name")),  Yaml::String(mibs.name_of(oid)));
+                    row.insert(Yaml::String(String::from("type")),  Yaml::String(String::from(type_name)));
+                    row.insert(Yaml::String(String::from("value")), text);
+                    Yaml::Hash(row)
+                })
+                .collect::<Vec<Yaml>>();
+            let mut out = String::new();
+            YamlEmitter::new(&mut out).dump(&Yaml::Array(rows))
+                .map_err(|err| format!("Could not render YAML: {:?}", err))?;
+            out.push('\n');
+            Ok(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    const MIB: &str = r#"
+SNMPv2-MIB DEFINITIONS ::= BEGIN
+system      OBJECT IDENTIFIER ::= { iso 3 6 1 2 1 1 }
+sysDescr    OBJECT-TYPE ::= { system 1 }
+sysUpTime   OBJECT-TYPE ::= { system 3 }
+END
+"#;
+
+    fn varbinds() -> Vec<(OID, Value<'static>)> {
+        vec![
+            (OID::from_parts(&["1.3.6.1.2.1.1.1.0"]), Value::OctetString(String::from("Linux \"box\""))),
+            (OID::from_parts(&["1.3.6.1.2.1.1.3.0"]), Value::Timeticks(9000123)),
+            (OID::from_parts(&["1.3.6.1.2.1.2.2.1.6.2"]), Value::Octets(vec![0x52, 0x54, 0x00, 0x0a, 0xbc, 0xde])),
+            (OID::from_parts(&["1.3.6.1.2.1.2.2.1.10.2"]), Value::Counter32(1234)),
+        ]
+    }
+
+    fn mibs() -> Mibs {
+        let mut mibs = Mibs::new();
+        mibs.load_str(MIB);
+        mibs
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(render(&varbinds(), &mibs(), Format::Text).unwrap(),
+            "SNMPv2-MIB::sysDescr.0 = STRING: Linux \"box\"\n\
+             SNMPv2-MIB::sysUpTime.0 = Timeticks: (9000123) 1 day, 1:00:01.23\n\
+             .1.3.6.1.2.1.2.2.1.6.2 = Hex-STRING: 52 54 00 0A BC DE\n\
+             .1.3.6.1.2.1.2.2.1.10.2 = Counter32: 1234\n");
+    }
+
+    #[test]
+    fn json() {
+        let json = render(&varbinds(), &mibs(), Format::Json).unwrap();
+        assert!(json.contains(r#"{"oid": "1.3.6.1.2.1.1.1.0", "name": "SNMPv2-MIB::sysDescr.0", "type": "STRING", "value": "Linux \"box\""}"#));
+        assert!(json.contains(r#""type": "Counter32", "value": 1234}"#));
+        assert!(json.starts_with("[\n") && json.ends_with("\n]\n"));
+    }
+
+    #[test]
+    fn yaml_round_trip() {
+        let yaml = render(&varbinds(), &mibs(), Format::Yaml).unwrap();
+        let docs = YamlLoader::load_from_str(&yaml).unwrap();
+        let rows = docs[0].as_vec().unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0]["value"].as_str(), Some("Linux \"box\""));
+        assert_eq!(rows[1]["name"].as_str(), Some("SNMPv2-MIB::sysUpTime.0"));
+        assert_eq!(rows[3]["value"].as_i64(), Some(1234));
+    }
+}
@@ -0,0 +1,290 @@
+use std::io;
+use std::mem;
+use libc;
+
+const SIOCETHTOOL: libc::c_ulong = 0x8946;
+
+// Commands and string sets from linux/ethtool.h
+const ETHTOOL_GDRVINFO:      u32 = 0x03;
+const ETHTOOL_GSTRINGS:      u32 = 0x1b;
+const ETHTOOL_GSTATS:        u32 = 0x1d;
+const ETHTOOL_GLINKSETTINGS: u32 = 0x4c;
+const ETH_SS_STATS:          u32 = 1;
+const ETH_GSTRING_LEN:       usize = 32;
+
+/// struct ethtool_drvinfo and where its n_stats is
+const DRVINFO_LEN:         usize = 196;
+const DRVINFO_N_STATS:     usize = 180;
+/// struct ethtool_link_settings without the link mode masks that follow it
+const LINK_SETTINGS_LEN:   usize = 48;
+const LINK_MODE_NWORDS:    usize = 15;
+
+const SPEED_UNKNOWN: u32 = 0xffffffff;
+
+pub const DUPLEX_HALF:    u8 = 0;
+pub const DUPLEX_FULL:    u8 = 1;
+pub const DUPLEX_UNKNOWN: u8 = 0xff;
+
+/**
+ * ETHTOOL_LINK_MODE_* by bit number, named the way the ethtool command does.
+ */
+const LINK_MODES: &[&str] = &[
+    "10baseT/Half", "10baseT/Full", "100baseT/Half", "100baseT/Full", "1000baseT/Half", "1000baseT/Full",
+    "Autoneg", "TP", "AUI", "MII", "FIBRE", "BNC", "10000baseT/Full", "Pause", "Asym_Pause",
+    "2500baseX/Full", "Backplane", "1000baseKX/Full", "10000baseKX4/Full", "10000baseKR/Full",
+    "10000baseR_FEC", "20000baseMLD2/Full", "20000baseKR2/Full", "40000baseKR4/Full", "40000baseCR4/Full",
+    "40000baseSR4/Full", "40000baseLR4/Full", "56000baseKR4/Full", "56000baseCR4/Full", "56000baseSR4/Full",
+    "56000baseLR4/Full", "25000baseCR/Full", "25000baseKR/Full", "25000baseSR/Full", "50000baseCR2/Full",
+    "50000baseKR2/Full", "100000baseKR4/Full", "100000baseSR4/Full", "100000baseCR4/Full",
+    "100000baseLR4_ER4/Full", "50000baseSR2/Full", "1000baseX/Full", "10000baseCR/Full", "10000baseSR/Full",
+    "10000baseLR/Full", "10000baseLRM/Full", "10000baseER/Full", "2500baseT/Full", "5000baseT/Full",
+    "FEC_NONE", "FEC_RS", "FEC_BASER",
+];
+
+/**
+ * Speed, duplex and negotiation of a NIC, from ETHTOOL_GLINKSETTINGS.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkSettings {
+    /// Mbit/s, None while unknown, e.g. because the link is down
+    pub speed:       Option<u32>,
+    /// DUPLEX_*
+    pub duplex:      u8,
+    /// PORT_* from linux/ethtool.h, see port_name
+    pub port:        u8,
+    pub autoneg:     bool,
+    /// ETHTOOL_LINK_MODE_* bit numbers, see link_mode_name
+    pub supported:   Vec<u32>,
+    pub advertising: Vec<u32>,
+}
+
+/**
+ * Name of a link mode bit, e.g. 5 -> 1000baseT/Full. Modes newer than this
+ * code are reported by number.
+ */
+pub fn link_mode_name(bit: u32) -> String {
+    LINK_MODES.get(bit as usize)
+        .map(|&name| String::from(name))
+        .unwrap_or_else(|| format!("{}", bit))
+}
+
+pub fn port_name(port: u8) -> &'static str {
+    match port {
+        0x00 => "tp",
+        0x01 => "aui",
+        0x02 => "bnc",
+        0x03 => "mii",
+        0x04 => "fibre",
+        0x05 => "da",
+        0xef => "none",
+        _    => "other",
+    }
+}
+
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_ne_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]])
+}
+
+fn u64_at(buf: &[u8], pos: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[pos..pos + 8]);
+    u64::from_ne_bytes(bytes)
+}
+
+/**
+ * Bit numbers set in a link mode mask of nwords u32s at pos.
+ */
+fn mask_bits(buf: &[u8], pos: usize, nwords: usize) -> Vec<u32> {
+    (0..nwords * 32)
+        .filter(|&bit| u32_at(buf, pos + bit / 32 * 4) & (1 << (bit % 32)) != 0)
+        .map(|bit| bit as u32)
+        .collect()
+}
+
+/**
+ * Parse struct ethtool_link_settings followed by its supported, advertising
+ * and lp_advertising masks.
+ */
+fn parse_link_settings(buf: &[u8]) -> LinkSettings {
+    let nwords = (buf[15] as i8).max(0) as usize;
+    let speed = u32_at(buf, 4);
+    LinkSettings {
+        speed:       if speed == SPEED_UNKNOWN { None } else { Some(speed) },
+        duplex:      buf[8],
+        port:        buf[9],
+        autoneg:     buf[11] != 0,
+        supported:   mask_bits(buf, LINK_SETTINGS_LEN, nwords),
+        advertising: mask_bits(buf, LINK_SETTINGS_LEN + nwords * 4, nwords),
+    }
+}
+
+/**
+ * Pair the names from ETHTOOL_GSTRINGS with the values from ETHTOOL_GSTATS.
+ */
+fn parse_stats(strings: &[u8], stats: &[u8]) -> Vec<(String, u64)> {
+    strings.chunks(ETH_GSTRING_LEN)
+        .zip(stats.chunks(8).filter(|chunk| chunk.len() == 8))
+        .map(|(name, value)| {
+            let len = name.iter().position(|&byte| byte == 0).unwrap_or(name.len());
+            (String::from_utf8_lossy(&name[..len]).into_owned(), u64_at(value, 0))
+        })
+        .collect()
+}
+
+struct Socket(libc::c_int);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0); }
+    }
+}
+
+#[repr(C)]
+struct IfReq {
+    name: [libc::c_char; libc::IFNAMSIZ],
+    data: *mut libc::c_void,
+    /// The rest of the union in struct ifreq
+    _pad: [u8; 16],
+}
+
+/**
+ * Run an ethtool command on the interface. cmd is the command's struct, which
+ * the kernel fills in.
+ */
+fn ethtool(sock: &Socket, ifname: &str, cmd: &mut [u8]) -> io::Result<()> {
+    if ifname.len() >= libc::IFNAMSIZ {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "interface name too long"));
+    }
+    let mut ifreq: IfReq = unsafe { mem::zeroed() };
+    for (dst, &src) in ifreq.name.iter_mut().zip(ifname.as_bytes()) {
+        *dst = src as libc::c_char;
+    }
+    ifreq.data = cmd.as_mut_ptr() as *mut libc::c_void;
+    if unsafe { libc::ioctl(sock.0, SIOCETHTOOL as _, &mut ifreq) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn socket() -> io::Result<Socket> {
+    let sock = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if sock < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Socket(sock))
+}
+
+/**
+ * A socket to run ethtool commands through, which can be used for any number
+ * of interfaces.
+ */
+pub struct Ethtool {
+    sock: Socket,
+}
+
+impl Ethtool {
+    pub fn new() -> io::Result<Ethtool> {
+        Ok(Ethtool { sock: socket()? })
+    }
+
+    /**
+     * Speed, duplex and link modes of the interface. Fails with EOPNOTSUPP for
+     * most virtual interfaces.
+     */
+    pub fn link_settings(&self, ifname: &str) -> io::Result<LinkSettings> {
+        link_settings(&self.sock, ifname)
+    }
+
+    /**
+     * The driver's own counters of the interface, e.g. rx_missed_errors or
+     * per-queue packets, in the order the driver lists them.
+     */
+    pub fn driver_stats(&self, ifname: &str) -> io::Result<Vec<(String, u64)>> {
+        driver_stats(&self.sock, ifname)
+    }
+}
+
+fn link_settings(sock: &Socket, ifname: &str) -> io::Result<LinkSettings> {
+    // The first request tells how many words the link mode masks have, as a negative number
+    let mut cmd = vec![0u8; LINK_SETTINGS_LEN + 3 * LINK_MODE_NWORDS * 4];
+    cmd[..4].copy_from_slice(&ETHTOOL_GLINKSETTINGS.to_ne_bytes());
+    ethtool(sock, ifname, &mut cmd)?;
+    let nwords = -(cmd[15] as i8);
+    if nwords <= 0 || nwords as usize > LINK_MODE_NWORDS {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected link mode mask size"));
+    }
+
+    let mut cmd = vec![0u8; LINK_SETTINGS_LEN + 3 * LINK_MODE_NWORDS * 4];
+    cmd[..4].copy_from_slice(&ETHTOOL_GLINKSETTINGS.to_ne_bytes());
+    cmd[15] = nwords as u8;
+    ethtool(sock, ifname, &mut cmd)?;
+    Ok(parse_link_settings(&cmd))
+}
+
+fn driver_stats(sock: &Socket, ifname: &str) -> io::Result<Vec<(String, u64)>> {
+    let mut drvinfo = vec![0u8; DRVINFO_LEN];
+    drvinfo[..4].copy_from_slice(&ETHTOOL_GDRVINFO.to_ne_bytes());
+    ethtool(sock, ifname, &mut drvinfo)?;
+    let n_stats = u32_at(&drvinfo, DRVINFO_N_STATS) as usize;
+    if n_stats == 0 {
+        return Ok(Vec::new());
+    }
+
+    // struct ethtool_gstrings: cmd, string_set, len and the strings
+    let mut strings = vec![0u8; 12 + n_stats * ETH_GSTRING_LEN];
+    strings[..4].copy_from_slice(&ETHTOOL_GSTRINGS.to_ne_bytes());
+    strings[4..8].copy_from_slice(&ETH_SS_STATS.to_ne_bytes());
+    strings[8..12].copy_from_slice(&(n_stats as u32).to_ne_bytes());
+    ethtool(sock, ifname, &mut strings)?;
+
+    // struct ethtool_stats: cmd, n_stats and the values
+    let mut stats = vec![0u8; 8 + n_stats * 8];
+    stats[..4].copy_from_slice(&ETHTOOL_GSTATS.to_ne_bytes());
+    stats[4..8].copy_from_slice(&(n_stats as u32).to_ne_bytes());
+    ethtool(sock, ifname, &mut stats)?;
+
+    // The kernel says how many it filled in, which may be fewer if the driver changed its mind
+    let strings_len = (u32_at(&strings, 8) as usize).min(n_stats);
+    let stats_len = (u32_at(&stats, 4) as usize).min(n_stats);
+    Ok(parse_stats(&strings[12..12 + strings_len * ETH_GSTRING_LEN], &stats[8..8 + stats_len * 8]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_settings_of_a_gigabit_nic() {
+        let mut buf = vec![0u8; LINK_SETTINGS_LEN + 3 * 4];
+        buf[4..8].copy_from_slice(&1000u32.to_ne_bytes());
+        buf[8] = DUPLEX_FULL;
+        buf[11] = 1;
+        buf[15] = 1;
+        // 10/100/1000baseT, Autoneg and TP supported, everything but 1000baseT/Half advertised
+        buf[48..52].copy_from_slice(&0xffu32.to_ne_bytes());
+        buf[52..56].copy_from_slice(&0xefu32.to_ne_bytes());
+        let settings = parse_link_settings(&buf);
+        assert_eq!(settings.speed, Some(1000));
+        assert!(settings.autoneg);
+        assert_eq!(port_name(settings.port), "tp");
+        assert_eq!(settings.supported, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(settings.advertising.iter().map(|&bit| link_mode_name(bit)).collect::<Vec<String>>(),
+                   vec!["10baseT/Half", "10baseT/Full", "100baseT/Half", "100baseT/Full", "1000baseT/Full", "Autoneg", "TP"]);
+
+        buf[4..8].copy_from_slice(&SPEED_UNKNOWN.to_ne_bytes());
+        assert_eq!(parse_link_settings(&buf).speed, None);
+        assert_eq!(link_mode_name(200), "200");
+    }
+
+    #[test]
+    fn stats_names_and_values() {
+        let mut strings = vec![0u8; 2 * ETH_GSTRING_LEN];
+        strings[..10].copy_from_slice(b"rx_packets");
+        strings[ETH_GSTRING_LEN..ETH_GSTRING_LEN + 16].copy_from_slice(b"rx_missed_errors");
+        let mut stats = Vec::new();
+        stats.extend_from_slice(&1234u64.to_ne_bytes());
+        stats.extend_from_slice(&12u64.to_ne_bytes());
+        assert_eq!(parse_stats(&strings, &stats),
+                   vec![(String::from("rx_packets"), 1234), (String::from("rx_missed_errors"), 12)]);
+    }
+}
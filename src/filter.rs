@@ -0,0 +1,101 @@
+use pattern::Pattern;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Include,
+    Exclude,
+}
+
+/**
+ * Include or exclude whatever matches all conditions. A condition matches if
+ * any of its patterns matches any value of its attribute.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub action:     Action,
+    pub conditions: Vec<(String, Vec<Pattern>)>,
+}
+
+impl Rule {
+    /**
+     * A rule with glob patterns, for the defaults of collectors.
+     */
+    pub fn globs(action: Action, conditions: &[(&str, &str)]) -> Rule {
+        Rule {
+            action,
+            conditions: conditions.iter()
+                .map(|&(attribute, glob)| (String::from(attribute), vec![Pattern::glob(glob).unwrap()]))
+                .collect(),
+        }
+    }
+
+    fn matches<F: Fn(&str) -> Vec<String>>(&self, attribute: &F) -> bool {
+        self.conditions.iter().all(|(name, patterns)| {
+            attribute(name).iter().any(|value| patterns.iter().any(|pattern| pattern.matches(value)))
+        })
+    }
+}
+
+/**
+ * Decides which interfaces, filesystems or block devices a collector exports.
+ *
+ * Rules are checked in order and the first one that matches decides. The
+ * collector's defaults come after the configured rules, so these can override
+ * them. What matches no rule at all is included.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    rules: Vec<Rule>,
+}
+
+impl Filter {
+    pub fn new(configured: &[Rule], defaults: Vec<Rule>) -> Filter {
+        Filter { rules: configured.iter().cloned().chain(defaults).collect() }
+    }
+
+    /**
+     * Whether to export something. attribute returns the values of the
+     * attribute with the given name, it's only called for attributes
+     * rules ask about.
+     */
+    pub fn includes<F: Fn(&str) -> Vec<String>>(&self, attribute: F) -> bool {
+        self.rules.iter()
+            .find(|rule| rule.matches(&attribute))
+            .is_none_or(|rule| rule.action == Action::Include)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_match_decides() {
+        let configured = vec![
+            Rule::globs(Action::Include, &[("name", "wg0")]),
+            Rule::globs(Action::Exclude, &[("name", "eth*"), ("class", "vlan")]),
+        ];
+        let filter = Filter::new(&configured, vec![Rule::globs(Action::Exclude, &[("class", "virtual")])]);
+        let interface = |name: &str, class: &str| {
+            let (name, class) = (String::from(name), String::from(class));
+            filter.includes(|attribute| match attribute {
+                "name"  => vec![name.clone()],
+                "class" => vec![class.clone()],
+                _       => vec![]
+            })
+        };
+        assert!(interface("wg0", "virtual"));
+        assert!(!interface("wg1", "virtual"));
+        assert!(!interface("eth0.20", "vlan"));
+        assert!(interface("eth0", "physical"));
+        assert!(interface("br0.20", "vlan"));
+    }
+
+    #[test]
+    fn any_value_matches() {
+        let filter = Filter::new(&[Rule::globs(Action::Exclude, &[("options", "bind")])], vec![]);
+        assert!(!filter.includes(|_| vec![String::from("rw"), String::from("bind")]));
+        assert!(filter.includes(|_| vec![String::from("rw"), String::from("relatime")]));
+        assert!(filter.includes(|_| vec![]));
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap,BTreeSet};
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path,PathBuf,Component};
+use std::sync::{Arc,Mutex};
+use libc;
+use uname;
+use errors::*;
+use netlink;
+use ethtool;
+use ethtool::LinkSettings;
+
+/// Where captures keep what cannot be stored as files of the host.
+pub const CAPTURE_DATA: &str = "/.sunt";
+
+// Names of the captured rtnetlink dumps in CAPTURE_DATA
+const RTNL_LINK: &str = "rtnl_link";
+const RTNL_ADDR: &str = "rtnl_addr";
+const RTNL_ROUTE: &str = "rtnl_route";
+const RTNL_NEIGH: &str = "rtnl_neigh";
+
+/**
+ * The parts of statvfs64 the collectors care about.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FsStat {
+    pub frsize: u64,
+    pub blocks: u64,
+    pub bfree:  u64,
+    pub bavail: u64,
+    pub files:  u64,
+    pub ffree:  u64,
+    pub fsid:   u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SysInfo {
+    pub sysname:  String,
+    pub nodename: String,
+    pub release:  String,
+    pub version:  String,
+    pub machine:  String,
+}
+
+/**
+ * Everything the collectors looked at on a host, see Host::record.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    /// Host paths that were read or checked for existence
+    pub paths:   BTreeSet<PathBuf>,
+    /// Host paths of directories that were listed
+    pub dirs:    BTreeSet<PathBuf>,
+    /// statvfs results by mountpoint
+    pub statvfs: BTreeMap<String, FsStat>,
+    pub uname:   Option<SysInfo>,
+    /// Raw rtnetlink dumps by name, e.g. rtnl_link for RTM_GETLINK
+    pub dumps:   BTreeMap<String, Vec<u8>>,
+    /// ethtool results by interface name
+    pub link_settings: BTreeMap<String, LinkSettings>,
+    pub driver_stats:  BTreeMap<String, Vec<(String, u64)>>,
+}
+
+/**
+ * Where the filesystem of the monitored host is to be found.
+ *
+ * Usually that's just /, but when running in a container, the host's /proc,
+ * /sys, /dev and mountpoints are bind mounted somewhere else, e.g. /host.
+ * Collectors must access all of them through host paths.
+ *
+ * A host can also be a capture of another one, in which case statvfs, uname,
+ * netlink and ethtool return what was recorded on the original host.
+ */
+#[derive(Clone, Debug)]
+pub struct Host {
+    root:      PathBuf,
+    replay:    Option<Arc<Recording>>,
+    recording: Option<Arc<Mutex<Recording>>>,
+    /// Never read, only kept alive as long as the host
+    _temp_dir: Option<Arc<TempDir>>,
+}
+
+/**
+ * A directory that is removed along with the last host using it.
+ */
+#[derive(Debug)]
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+impl Default for Host {
+    fn default() -> Host {
+        Host::new("/")
+    }
+}
+
+/**
+ * See Host::ethtool.
+ */
+pub struct HostEthtool<'a> {
+    host:    &'a Host,
+    /// None when replaying or for a host root
+    ethtool: Option<io::Result<ethtool::Ethtool>>,
+}
+
+impl<'a> HostEthtool<'a> {
+    fn live(&self) -> io::Result<&ethtool::Ethtool> {
+        match self.ethtool {
+            Some(Ok(ref ethtool)) => Ok(ethtool),
+            Some(Err(ref err))    => Err(io::Error::new(err.kind(), err.to_string())),
+            None                  => Err(io::Error::other("ethtool does not see the host root"))
+        }
+    }
+
+    /**
+     * ethtool link settings of a network interface.
+     */
+    pub fn link_settings(&self, ifname: &str) -> io::Result<LinkSettings> {
+        let settings = match self.host.replay {
+            Some(ref replay) => replay.link_settings.get(ifname).cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not captured"))?,
+            None => self.live()?.link_settings(ifname)?
+        };
+        self.host.note(|recording| { recording.link_settings.insert(String::from(ifname), settings.clone()); });
+        Ok(settings)
+    }
+
+    /**
+     * ethtool driver statistics of a network interface.
+     */
+    pub fn driver_stats(&self, ifname: &str) -> io::Result<Vec<(String, u64)>> {
+        let stats = match self.host.replay {
+            Some(ref replay) => replay.driver_stats.get(ifname).cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not captured"))?,
+            None => self.live()?.driver_stats(ifname)?
+        };
+        self.host.note(|recording| { recording.driver_stats.insert(String::from(ifname), stats.clone()); });
+        Ok(stats)
+    }
+}
+
+/**
+ * Resolve . and .. without looking at the filesystem, which would follow
+ * symlinks as seen from inside the container rather than from the host.
+ */
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => { normalized.pop(); }
+            Component::Normal(part) => normalized.push(part),
+            _ => ()
+        }
+    }
+    normalized
+}
+
+/**
+ * Parse captured link settings like "speed=1000 duplex=1 port=0 autoneg=1 supported=0,1,5 advertising=5".
+ * A speed of - means the speed was unknown.
+ */
+fn parse_link_settings(text: &str) -> Result<LinkSettings> {
+    let mut settings = LinkSettings::default();
+    for field in text.split_whitespace() {
+        let mut parts = field.splitn(2, '=');
+        let (key, value) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        let bits = || value.split(',')
+            .filter(|bit| !bit.is_empty())
+            .map(|bit| bit.parse::<u32>())
+            .collect::<::std::result::Result<Vec<u32>, _>>();
+        match key {
+            "speed" if value == "-" => settings.speed = None,
+            "speed"       => settings.speed = Some(value.parse().chain_err(|| format!("Invalid speed {}", value))?),
+            "duplex"      => settings.duplex = value.parse().chain_err(|| format!("Invalid duplex {}", value))?,
+            "port"        => settings.port = value.parse().chain_err(|| format!("Invalid port {}", value))?,
+            "autoneg"     => settings.autoneg = value == "1",
+            "supported"   => settings.supported = bits().chain_err(|| format!("Invalid link modes {}", value))?,
+            "advertising" => settings.advertising = bits().chain_err(|| format!("Invalid link modes {}", value))?,
+            _             => bail!("Unknown link setting {}", key)
+        }
+    }
+    Ok(settings)
+}
+
+impl Host {
+    pub fn new<P: AsRef<Path>>(root: P) -> Host {
+        Host { root: root.as_ref().to_path_buf(), replay: None, recording: None, _temp_dir: None }
+    }
+
+    /**
+     * Remember everything collectors access from now on, so it can be captured.
+     */
+    pub fn record(self) -> Host {
+        Host { recording: Some(Arc::new(Mutex::new(Recording::default()))), ..self }
+    }
+
+    /**
+     * A host captured earlier and extracted into dir.
+     */
+    pub fn replay<P: AsRef<Path>>(dir: P) -> Result<Host> {
+        let host = Host::new(dir);
+        let data = host.root_path(CAPTURE_DATA);
+        let mut replay = Recording::default();
+
+        let statvfs = fs::read_to_string(data.join("statvfs"))
+            .chain_err(|| format!("{} is not a capture", host.root.display()))?;
+        for line in statvfs.lines() {
+            // The mountpoint may contain spaces, the numbers do not
+            let mut parts = line.rsplitn(8, ' ');
+            let mut number = || parts.next().and_then(|part| part.parse::<u64>().ok());
+            let (fsid, ffree, files, bavail, bfree, blocks, frsize) =
+                (number(), number(), number(), number(), number(), number(), number());
+            let mountpoint = parts.next();
+            match (mountpoint, frsize, blocks, bfree, bavail, files, ffree, fsid) {
+                (Some(mountpoint), Some(frsize), Some(blocks), Some(bfree), Some(bavail), Some(files), Some(ffree), Some(fsid)) => {
+                    replay.statvfs.insert(String::from(mountpoint), FsStat { frsize, blocks, bfree, bavail, files, ffree, fsid });
+                }
+                _ => bail!("Invalid line in captured statvfs: {}", line)
+            }
+        }
+
+        if let Ok(uname) = fs::read_to_string(data.join("uname")) {
+            let lines = uname.lines().map(String::from).collect::<Vec<String>>();
+            if lines.len() != 5 {
+                bail!("Captured uname must have 5 lines");
+            }
+            replay.uname = Some(SysInfo {
+                sysname:  lines[0].to_owned(),
+                nodename: lines[1].to_owned(),
+                release:  lines[2].to_owned(),
+                version:  lines[3].to_owned(),
+                machine:  lines[4].to_owned(),
+            });
+        }
+
+        // Captures from before netlink was used have none, so collectors fall back to sysfs
+        for name in &[RTNL_LINK, RTNL_ADDR, RTNL_ROUTE, RTNL_NEIGH] {
+            if let Ok(dump) = fs::read(data.join(name)) {
+                replay.dumps.insert(String::from(*name), dump);
+            }
+        }
+
+        // Lines like "eno1 settings speed=1000 duplex=1 ..." or "eno1 stat rx_missed_errors 12"
+        if let Ok(ethtool) = fs::read_to_string(data.join("ethtool")) {
+            for line in ethtool.lines() {
+                let mut parts = line.splitn(3, ' ');
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(ifname), Some("settings"), Some(settings)) => {
+                        replay.link_settings.insert(String::from(ifname), parse_link_settings(settings)?);
+                    }
+                    (Some(ifname), Some("stat"), Some(stat)) => {
+                        // Some drivers have spaces in their counter names
+                        let mut stat = stat.rsplitn(2, ' ');
+                        match (stat.next().and_then(|value| value.parse::<u64>().ok()), stat.next()) {
+                            (Some(value), Some(name)) => replay.driver_stats.entry(String::from(ifname))
+                                .or_insert_with(Vec::new)
+                                .push((String::from(name), value)),
+                            _ => bail!("Invalid line in captured ethtool: {}", line)
+                        }
+                    }
+                    _ => bail!("Invalid line in captured ethtool: {}", line)
+                }
+            }
+        }
+
+        Ok(Host { replay: Some(Arc::new(replay)), ..host })
+    }
+
+    /**
+     * Like replay, for a capture extracted into a temporary dir, which is
+     * removed once the host and all its clones are dropped, or right away if
+     * it is no capture.
+     */
+    pub fn replay_temporary<P: AsRef<Path>>(dir: P) -> Result<Host> {
+        let temp_dir = Arc::new(TempDir(dir.as_ref().to_path_buf()));
+        let host = Host::replay(dir)?;
+        Ok(Host { _temp_dir: Some(temp_dir), ..host })
+    }
+
+    fn root_path<P: AsRef<Path>>(&self, host_path: P) -> PathBuf {
+        self.root.join(normalize(host_path.as_ref()).strip_prefix("/").unwrap())
+    }
+
+    fn note<F: FnOnce(&mut Recording)>(&self, update: F) {
+        if let Some(ref recording) = self.recording {
+            update(&mut recording.lock().unwrap());
+        }
+    }
+
+    /**
+     * Where the given path of the host is to be found, e.g. /proc/mounts -> /host/proc/mounts.
+     */
+    pub fn path<P: AsRef<Path>>(&self, host_path: P) -> PathBuf {
+        self.note_path(&host_path);
+        self.root_path(host_path)
+    }
+
+    /**
+     * Capture a host path read with read_raw and friends after all, for
+     * collectors that only want some of what they look at to be captured.
+     */
+    pub fn note_path<P: AsRef<Path>>(&self, host_path: P) {
+        self.note(|recording| { recording.paths.insert(normalize(host_path.as_ref())); });
+    }
+
+    /**
+     * Like path, for directories whose entries are going to be listed.
+     */
+    pub fn dir<P: AsRef<Path>>(&self, host_path: P) -> PathBuf {
+        self.note(|recording| { recording.dirs.insert(normalize(host_path.as_ref())); });
+        self.root_path(host_path)
+    }
+
+    /**
+     * Follow symlinks as the host would, e.g. /dev/mapper/vg-lv -> /dev/dm-3.
+     * Both the argument and the result are host paths.
+     */
+    pub fn resolve_symlinks<P: AsRef<Path>>(&self, host_path: P) -> PathBuf {
+        let mut current = normalize(host_path.as_ref());
+        // Give up on symlink loops at some point, like the kernel does
+        for _ in 0..40 {
+            match self.path(&current).read_link() {
+                Ok(target) => {
+                    // Absolute targets replace the parent, relative ones are appended to it
+                    current = normalize(&current.parent().unwrap_or(Path::new("/")).join(target));
+                }
+                Err(_) => break
+            }
+        }
+        current
+    }
+
+    /**
+     * statvfs64 of the filesystem mounted at the given mountpoint of the host.
+     */
+    pub fn statvfs(&self, mountpoint: &str) -> io::Result<FsStat> {
+        let fsstat = match self.replay {
+            Some(ref replay) => replay.statvfs.get(mountpoint)
+                .cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not captured"))?,
+            None => self.statvfs_live(mountpoint)?
+        };
+        self.note(|recording| { recording.statvfs.insert(String::from(mountpoint), fsstat.clone()); });
+        Ok(fsstat)
+    }
+
+    fn statvfs_live(&self, mountpoint: &str) -> io::Result<FsStat> {
+        let fsstat = unsafe {
+            let mut fsstat: libc::statvfs64 = mem::zeroed();
+            let path = CString::new(self.root_path(mountpoint).as_os_str().as_bytes())?;
+            if libc::statvfs64(path.as_ptr(), &mut fsstat) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            fsstat
+        };
+        Ok(FsStat {
+            frsize: fsstat.f_frsize as u64,
+            blocks: fsstat.f_blocks as u64,
+            bfree:  fsstat.f_bfree as u64,
+            bavail: fsstat.f_bavail as u64,
+            files:  fsstat.f_files as u64,
+            ffree:  fsstat.f_ffree as u64,
+            fsid:   fsstat.f_fsid as u64,
+        })
+    }
+
+    /**
+     * uname of the host. Containers share the kernel of their host, so no need
+     * to look at the root for this one.
+     */
+    pub fn uname(&self) -> Option<SysInfo> {
+        let info = match self.replay {
+            Some(ref replay) => replay.uname.clone()?,
+            None => {
+                let info = uname::uname().ok()?;
+                SysInfo {
+                    sysname:  info.sysname,
+                    nodename: info.nodename,
+                    release:  info.release,
+                    version:  info.version,
+                    machine:  info.machine,
+                }
+            }
+        };
+        self.note(|recording| recording.uname = Some(info.clone()));
+        Some(info)
+    }
+
+    /**
+     * Netlink can only be asked about the network namespace the agent runs
+     * in, so dumps fail for host roots other than / and collectors have to
+     * look at /sys instead, if they can.
+     */
+    fn rtnl_dump<F: FnOnce() -> io::Result<Vec<u8>>>(&self, name: &str, dump_live: F) -> io::Result<Vec<u8>> {
+        let dump = match self.replay {
+            Some(ref replay) => replay.dumps.get(name).cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not captured"))?,
+            None if self.root == Path::new("/") => dump_live()?,
+            None => return Err(io::Error::other("netlink does not see the host root"))
+        };
+        self.note(|recording| { recording.dumps.insert(String::from(name), dump.clone()); });
+        Ok(dump)
+    }
+
+    /**
+     * Raw RTM_GETLINK dump of all network interfaces, see netlink::parse_links.
+     */
+    pub fn link_dump(&self) -> io::Result<Vec<u8>> {
+        self.rtnl_dump(RTNL_LINK, netlink::dump_links)
+    }
+
+    /**
+     * Raw RTM_GETADDR dump of all IPv4 and IPv6 addresses, see netlink::parse_addresses.
+     */
+    pub fn addr_dump(&self) -> io::Result<Vec<u8>> {
+        self.rtnl_dump(RTNL_ADDR, netlink::dump_addresses)
+    }
+
+    /**
+     * Raw RTM_GETROUTE dump of the IPv4 and IPv6 routes of all tables, see netlink::parse_routes.
+     */
+    pub fn route_dump(&self) -> io::Result<Vec<u8>> {
+        self.rtnl_dump(RTNL_ROUTE, netlink::dump_routes)
+    }
+
+    /**
+     * Raw RTM_GETNEIGH dump of the ARP and NDP caches, see netlink::parse_neighbors.
+     */
+    pub fn neigh_dump(&self) -> io::Result<Vec<u8>> {
+        self.rtnl_dump(RTNL_NEIGH, netlink::dump_neighbors)
+    }
+
+    /**
+     * ethtool for network interfaces of the host, through one socket for as
+     * long as the result is kept around.
+     *
+     * Like netlink, ethtool only sees the network namespace the agent runs in.
+     */
+    pub fn ethtool<'a>(&'a self) -> HostEthtool<'a> {
+        let ethtool = match self.replay {
+            None if self.root == Path::new("/") => Some(ethtool::Ethtool::new()),
+            _ => None
+        };
+        HostEthtool { host: self, ethtool }
+    }
+
+    /**
+     * What the collectors accessed so far, if this host is recording.
+     */
+    pub fn recorded(&self) -> Option<Recording> {
+        self.recording.as_ref().map(|recording| recording.lock().unwrap().clone())
+    }
+
+    /**
+     * Read a file of the host without recording it, e.g. for capturing it.
+     */
+    pub fn read_raw<P: AsRef<Path>>(&self, host_path: P) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        File::open(self.root_path(host_path))?.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    /**
+     * Metadata of a path of the host without following symlinks or recording it.
+     */
+    pub fn lstat_raw<P: AsRef<Path>>(&self, host_path: P) -> io::Result<fs::Metadata> {
+        self.root_path(host_path).symlink_metadata()
+    }
+
+    /**
+     * Target of a symlink of the host, without recording it.
+     */
+    pub fn read_link_raw<P: AsRef<Path>>(&self, host_path: P) -> io::Result<PathBuf> {
+        self.root_path(host_path).read_link()
+    }
+
+    /**
+     * Entries of a directory of the host, without recording it.
+     */
+    pub fn read_dir_raw<P: AsRef<Path>>(&self, host_path: P) -> io::Result<Vec<PathBuf>> {
+        let host_path = normalize(host_path.as_ref());
+        let mut entries = fs::read_dir(self.root_path(&host_path))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| host_path.join(entry.file_name()))
+            .collect::<Vec<PathBuf>>();
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    #[test]
+    fn host_paths() {
+        let host = Host::new("/host");
+        assert_eq!(host.path("/proc/mounts"), PathBuf::from("/host/proc/mounts"));
+        assert_eq!(host.path("/"), PathBuf::from("/host"));
+        assert_eq!(host.path("/dev/../../etc"), PathBuf::from("/host/etc"));
+        assert_eq!(Host::default().path("/proc/mounts"), PathBuf::from("/proc/mounts"));
+    }
+
+    #[test]
+    fn symlinks_stay_on_the_host() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("dev/mapper")).unwrap();
+        fs::create_dir_all(root.join("dev/vg")).unwrap();
+        fs::write(root.join("dev/dm-3"), "").unwrap();
+        symlink("../dm-3", root.join("dev/mapper/vg-lv")).unwrap();
+        symlink("/dev/mapper/vg-lv", root.join("dev/vg/lv")).unwrap();
+
+        let host = Host::new(root).record();
+        assert_eq!(host.resolve_symlinks("/dev/vg/lv"), PathBuf::from("/dev/dm-3"));
+        assert_eq!(host.resolve_symlinks("/dev/mapper/vg-lv"), PathBuf::from("/dev/dm-3"));
+        assert_eq!(host.resolve_symlinks("/dev/dm-3"), PathBuf::from("/dev/dm-3"));
+        assert_eq!(
+            host.recorded().unwrap().paths.into_iter().collect::<Vec<PathBuf>>(),
+            vec![PathBuf::from("/dev/dm-3"), PathBuf::from("/dev/mapper/vg-lv"), PathBuf::from("/dev/vg/lv")]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright 2016 Michael Ziegler <diese-addy@funzt-halt.net>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sunt as a library: The collectors, the value tree and the agent that serves it.
+//!
+//! Applications can embed the agent to publish their own metrics alongside host data:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use sunt::agent::{Agent,UdpTransport};
+//! use sunt::config::Config;
+//! use sunt::provider::Collector;
+//! use sunt::oid::OID;
+//! use sunt::value::Value;
+//!
+//! Agent::new()
+//!     .host_collectors(&Config::default())
+//!     .provider(Duration::new(5, 0), Collector::new(&["1.3.6.1.4.1.99999.1"], |values| {
+//!         values.insert(OID::from_parts(&["1.3.6.1.4.1.99999.1.1.0"]), Value::Integer(42));
+//!     }))
+//!     .transport(UdpTransport::bind("[::]:1161".parse().unwrap()).unwrap())
+//!     .run()
+//!     .unwrap();
+//! ```
+
+#[macro_use]
+extern crate error_chain;
+extern crate uname;
+extern crate libc;
+extern crate yaml_rust;
+extern crate regex;
+extern crate glob;
+#[cfg(test)]
+extern crate tempfile;
+
+pub mod errors {
+    error_chain! {
+        foreign_links {
+            Decode(::ber::DecodeError);
+        }
+
+        errors {
+            Config(file: String, line: usize, key: String, message: String) {
+                description("invalid configuration")
+                display("{}:{}: {}: {}", file, line, key, message)
+            }
+        }
+    }
+}
+
+pub mod config;
+pub mod pattern;
+pub mod filter;
+pub mod netlink;
+pub mod ethtool;
+pub mod host;
+pub mod tar;
+pub mod capture;
+
+pub mod oid;
+pub mod value;
+pub mod ber;
+pub mod pdu;
+pub mod mib;
+pub mod sunt_mib;
+pub mod table;
+pub mod provider;
+pub mod tree;
+pub mod agent;
+pub mod dump;
+
+pub mod mib_sys;
+pub mod mib_disks;
+pub mod mib_net;
+pub mod mib_bonding;
+pub mod mib_bridge;
+pub mod mib_ip;
+pub mod mib_netstat;
+pub mod mib_sockets;
+pub mod mib_extend;
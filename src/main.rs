@@ -6,177 +6,125 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-extern crate snmp;
-#[macro_use]
-extern crate error_chain;
+extern crate sunt;
 extern crate clap;
-extern crate uname;
-extern crate libc;
-extern crate yaml_rust;
-
-use std::collections::BTreeMap;
-use std::net::{UdpSocket,SocketAddr};
-use std::time::{Instant,Duration};
-use std::path::PathBuf;
-use clap::{Arg, App};
-use snmp::SnmpPdu;
-use snmp::pdu;
-
-mod errors {
-    error_chain! { }
-}
-
-use errors::*;
-
-mod config;
-
-mod oid;
-use oid::OID;
-
-mod value;
-use value::Value;
-
-mod mib_sys;
-mod mib_disks;
-mod mib_net;
-mod mib_extend;
-
-
-fn run(matches: clap::ArgMatches) -> Result<()> {
-    let port = matches.value_of("port").unwrap_or("161").parse::<u16>()
-        .chain_err(|| "Port argument must be a number between 1 and 65535")?;
-    let community = matches.value_of("community").unwrap_or("sunt");
-
-    let mut conf = None;
-    if let Some(confpath) = matches.value_of("extend") {
-        conf = Some(
-            config::load_conf(PathBuf::from(confpath))
-                .expect("failed to load config")
-        );
-    }
 
-    let addr: SocketAddr = format!("[::]:{}", port).parse()
-        .chain_err(|| "Could not parse address")?;
-
-    let socket = UdpSocket::bind(addr)
-        .chain_err(|| "Could not bind to socket")?;
-
-    socket.set_read_timeout(Some(Duration::new(1, 0)))
-        .chain_err(|| "could not set timeout")?;
-
-    let mut values: BTreeMap<OID, Value> = BTreeMap::new();
-    let mut last_refresh : Option<Instant> = None;
-
-    let mut buf = [0 as u8; 16 * 1024];
-    loop {
-        if last_refresh.is_none() ||
-           last_refresh.unwrap() + Duration::new(15, 0) < Instant::now() {
-            mib_sys::get_system(
-                &mut values,
-                "1.3.6.1.2.1.1"
-            );
-            mib_disks::get_disks(
-                &mut values,
-                "1.3.6.1.4.1.2021.13.15.1.1"
-            );
-            mib_disks::get_filesystems(
-                &mut values,
-                "1.3.6.1.2.1.25.2.3.1",
-                "1.3.6.1.4.1.2021.9.1"
-            );
-            mib_net::get_interfaces(
-                &mut values,
-                "1.3.6.1.2.1.2.2.1",
-                "1.3.6.1.2.1.31.1.1.1"
-            );
-            mib_extend::get_extend(
-                &mut values,
-                &conf,
-                "1.3.6.1.4.1.8072.1.3.2.3.1"
-            );
-            last_refresh = Some(Instant::now());
+use std::process;
+use std::time::Duration;
+use std::path::Path;
+use clap::{Arg, App, SubCommand};
+use sunt::errors::*;
+use sunt::config;
+use sunt::config::Config;
+use sunt::dump;
+use sunt::sunt_mib;
+use sunt::capture;
+use sunt::host::Host;
+use sunt::oid::OID;
+use sunt::mib::Mibs;
+use sunt::agent::{Agent,UdpTransport};
+
+
+/**
+ * Load the config file, if any, and apply the command line flags on top of it.
+ */
+fn load_config(matches: &clap::ArgMatches) -> Result<Config> {
+    let mut config = match matches.value_of("config").or(matches.value_of("extend")) {
+        Some(path) => Config::load(Path::new(path))?,
+        None       => Config::default()
+    };
+
+    if let Some(port) = matches.value_of("port") {
+        let port = port.parse::<u16>()
+            .chain_err(|| "Port argument must be a number between 1 and 65535")?;
+        for addr in config.listen.iter_mut() {
+            addr.set_port(port);
         }
-
-        if let Ok((data_len, client_addr)) = socket.recv_from(&mut buf) {
-            let pdu_bytes = &buf[0..data_len];
-
-            if let Ok(req) = SnmpPdu::from_bytes(pdu_bytes) {
-                let mut start_from_oid = OID::from_parts(&["1"]);
-
-                for (name, _) in req.varbinds {
-                    if name.to_string() != "Invalid OID: AsnInvalidLen" {
-                        start_from_oid = OID::from_object_identifier(name);
-                    }
-
-                    // snmptable likes to query OIDs that don't exist as start OID.
-                    // We catch that by checking if the OID actually exists, and if not,
-                    // brute-forcing our way up the tree until we find one that does.
-                    let mut start_oid_vec = start_from_oid.as_vec().to_owned();
-                    while !values.contains_key(&start_from_oid) {
-                        start_oid_vec.pop();
-                        start_from_oid = OID::from_vec(&start_oid_vec);
-                    }
-                    break;
-                }
-
-                respond(&values, start_from_oid, req.req_id, client_addr, &community, &socket)?;
-            }
+    }
+    if let Some(community) = matches.value_of("community") {
+        config.communities = vec![String::from(community)];
+    }
+    if let Some(max_age) = matches.value_of("cache-time") {
+        let max_age = max_age.parse::<u64>()
+            .chain_err(|| "Cache time argument must be a number of seconds")?;
+        for collector in config.collectors.all_mut() {
+            collector.max_age = Duration::new(max_age, 0);
         }
     }
+    if let Some(root) = matches.value_of("host-root") {
+        config.host_root = String::from(root);
+    }
+    Ok(config)
 }
 
+fn run(matches: clap::ArgMatches) -> Result<()> {
+    if let Some(check) = matches.subcommand_matches("check-config") {
+        let path = check.value_of("file")
+            .or(matches.value_of("config"))
+            .or(matches.value_of("extend"))
+            .ok_or("No config file given")?;
+        let config = Config::load(Path::new(path))?;
+        config::load_mibs(&config)?;
+        println!("{}: OK", path);
+        return Ok(());
+    }
 
-fn respond (
-    values:         &BTreeMap<OID, Value>,
-    start_from_oid: OID,
-    req_id:         i32,
-    client_addr:    SocketAddr,
-    community:      &str,
-    socket:         &UdpSocket
-) -> Result<()> {
-
-    let mut outbuf = pdu::Buf::default();
-    let mut found_start = false;
-    let mut vals: Vec<(&[u32], snmp::Value)> = Vec::new();
-
-    for (oid, val) in values {
-        if !found_start && oid.is_subtree_of(&start_from_oid) {
-            found_start = true;
-            if *oid == start_from_oid {
-                continue;
-            }
-        }
-
-        if found_start {
-            vals.push( (&oid.as_vec()[..], val.as_snmp_value()) );
-        }
-
-        if vals.len() >= 100 {
-            break
-        }
+    let mut config = load_config(&matches)?;
+
+    let capturing = matches.subcommand_matches("capture");
+    let host = match matches.value_of("replay") {
+        Some(capture) => capture::replay(Path::new(capture))?,
+        None          => Host::new(&config.host_root),
+    };
+    let host = if capturing.is_some() { host.record() } else { host };
+    if capturing.is_some() {
+        // Extend commands do not read anything worth capturing
+        config.extend.clear();
     }
 
-    if !vals.is_empty() {
-        pdu::build_response(
-            &community.as_bytes(),
-            req_id,
-            &vals[..],
-            &mut outbuf
-        );
+    let mut agent = Agent::new()
+        .communities(&config.communities)
+        .host(host.clone())
+        .host_collectors(&config);
+
+    if let Some(matches) = capturing {
+        let out = Path::new(matches.value_of("archive").unwrap_or("sunt-capture.tar"));
+        agent.walk(&OID::from_vec(&vec![]));
+        capture::capture(&host, out)?;
+        eprintln!("Captured to {}", out.display());
+        return Ok(());
     }
-    else {
-        pdu::build_response(
-            &community.as_bytes(),
-            req_id,
-            &[(&[0, 0], snmp::Value::EndOfMibView)],
-            &mut outbuf
-        );
+
+    if matches.subcommand_matches("mib").is_some() {
+        print!("{}", sunt_mib::module()?);
+        return Ok(());
     }
 
-    socket.send_to(&outbuf[..], client_addr)
-        .chain_err(|| "Could not send")?;
+    if let Some(matches) = matches.subcommand_matches("dump") {
+        // Only needed for names, so a broken MIB file cannot keep the agent from running,
+        // nor from dumping what it serves
+        let mut mibs = match config::load_mibs(&config) {
+            Ok(mibs) => mibs,
+            Err(e)   => {
+                let causes = e.iter().map(|cause| cause.to_string()).collect::<Vec<String>>();
+                eprintln!("warning: {}, printing numeric OIDs", causes.join(": "));
+                Mibs::new()
+            }
+        };
+        mibs.load_str(&sunt_mib::module()?);
+        let format = dump::Format::from_name(matches.value_of("format").unwrap_or("text"))?;
+        let subtree = match matches.value_of("subtree") {
+            Some(subtree) => mibs.resolve_name(subtree)?,
+            None          => OID::from_vec(&vec![])
+        };
+        print!("{}", dump::render(&agent.walk(&subtree), &mibs, format)?);
+        return Ok(());
+    }
 
-    Ok(())
+    for addr in &config.listen {
+        agent = agent.transport(UdpTransport::bind(*addr)?);
+    }
+    agent.run()
 }
 
 
@@ -189,20 +137,63 @@ fn main(){
             .short("p")
             .long("port")
             .takes_value(true)
-            .help("Port number to use [161]"))
+            .help("Port number to listen on, overrides the ports of all listeners [161]"))
         .arg(Arg::with_name("community")
             .short("c")
             .long("community")
             .takes_value(true)
-            .help("Community to use in responses [sunt]"))
+            .help("Community to accept, overrides the communities of the config [any]"))
+        .arg(Arg::with_name("cache-time")
+            .short("t")
+            .long("cache-time")
+            .takes_value(true)
+            .help("Seconds to cache collected values for, overrides the max_age of all collectors [15]"))
+        .arg(Arg::with_name("config")
+            .short("C")
+            .long("config")
+            .takes_value(true)
+            .help("Config file to use, see README.md"))
         .arg(Arg::with_name("extend")
             .short("e")
             .long("extend")
             .takes_value(true)
-            .help("Parse config.yaml that defines extends"))
+            .conflicts_with("config")
+            .hidden(true)
+            .help("Old name of --config"))
+        .arg(Arg::with_name("host-root")
+            .short("r")
+            .long("host-root")
+            .takes_value(true)
+            .help("Where the host's /proc, /sys, /dev and mountpoints are, e.g. /host in a container, overrides host_root [/]"))
+        .arg(Arg::with_name("replay")
+            .long("replay")
+            .takes_value(true)
+            .conflicts_with("host-root")
+            .help("Collect from a capture instead of this host"))
+        .subcommand(SubCommand::with_name("check-config")
+            .about("Check a config file for errors and exit")
+            .arg(Arg::with_name("file")
+                .help("Config file to check [the one given with --config]")))
+        .subcommand(SubCommand::with_name("mib")
+            .about("Print the SUNT-MIB module describing sunt's own tables"))
+        .subcommand(SubCommand::with_name("dump")
+            .about("Collect all values once and print them instead of serving them")
+            .arg(Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["text", "json", "yaml"])
+                .help("Output format [text]"))
+            .arg(Arg::with_name("subtree")
+                .help("Only print this subtree, e.g. IF-MIB::ifTable or 1.3.6.1.2.1.2")))
+        .subcommand(SubCommand::with_name("capture")
+            .about("Archive everything the collectors read from /proc, /sys and /dev, for use with --replay")
+            .arg(Arg::with_name("archive")
+                .help("Where to write the archive [sunt-capture.tar]")))
         .get_matches();
 
     if let Err(ref e) = run(matches) {
         eprintln!("error: {}", e);
+        process::exit(1);
     }
 }
@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use errors::*;
+use oid::OID;
+
+/**
+ * Macros whose invocations assign an OID to the name in front of them.
+ */
+const OID_MACROS: &[&str] = &[
+    "MODULE-IDENTITY", "OBJECT-IDENTITY", "OBJECT-TYPE", "NOTIFICATION-TYPE",
+    "OBJECT-GROUP", "NOTIFICATION-GROUP", "MODULE-COMPLIANCE", "AGENT-CAPABILITIES",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Number(u32),
+    Assign,
+    Punct(char),
+}
+
+/**
+ * Split an SMI module into tokens, dropping comments, strings and anything
+ * else that cannot possibly be relevant for figuring out OIDs.
+ */
+fn tokenize(text: &str) -> Vec<Token> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let chr = chars[pos];
+        if chr == '-' && chars.get(pos + 1) == Some(&'-') {
+            // Comments end at the end of the line or at the next --
+            pos += 2;
+            while pos < chars.len() && chars[pos] != '\n' {
+                if chars[pos] == '-' && chars.get(pos + 1) == Some(&'-') {
+                    pos += 1;
+                    break;
+                }
+                pos += 1;
+            }
+            pos += 1;
+        }
+        else if chr == '"' || chr == '\'' {
+            // Strings, and 'ABCD'H style hex strings
+            pos += 1;
+            while pos < chars.len() && chars[pos] != chr {
+                pos += 1;
+            }
+            pos += 1;
+        }
+        else if chr == ':' && chars.get(pos + 1) == Some(&':') && chars.get(pos + 2) == Some(&'=') {
+            tokens.push(Token::Assign);
+            pos += 3;
+        }
+        else if chr.is_ascii_alphanumeric() {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '-' || chars[pos] == '_') {
+                pos += 1;
+            }
+            let word = chars[start..pos].iter().collect::<String>();
+            match word.parse::<u32>() {
+                Ok(number) => tokens.push(Token::Number(number)),
+                Err(_)     => tokens.push(Token::Word(word)),
+            }
+        }
+        else {
+            if !chr.is_whitespace() {
+                tokens.push(Token::Punct(chr));
+            }
+            pos += 1;
+        }
+    }
+    tokens
+}
+
+/**
+ * One part of an OID value such as { iso org(3) dod(6) 1 }.
+ */
+#[derive(Debug)]
+struct Component {
+    name:   Option<String>,
+    number: Option<u32>,
+}
+
+#[derive(Debug)]
+struct Definition {
+    name:       String,
+    components: Vec<Component>,
+}
+
+#[derive(Debug, Default)]
+struct Module {
+    name:        String,
+    imports:     HashMap<String, String>,
+    definitions: Vec<Definition>,
+}
+
+fn is_word(token: Option<&Token>, expected: &str) -> bool {
+    match token {
+        Some(Token::Word(word)) => word == expected,
+        _ => false
+    }
+}
+
+fn parse_oid_value(tokens: &[Token], pos: &mut usize) -> Option<Vec<Component>> {
+    if tokens.get(*pos) != Some(&Token::Punct('{')) {
+        return None;
+    }
+    *pos += 1;
+    let mut components = Vec::new();
+    loop {
+        match tokens.get(*pos) {
+            Some(&Token::Punct('}')) => {
+                *pos += 1;
+                return Some(components);
+            }
+            Some(&Token::Number(number)) => {
+                components.push(Component { name: None, number: Some(number) });
+                *pos += 1;
+            }
+            Some(Token::Word(word)) => {
+                // name or name(number)
+                let mut number = None;
+                if tokens.get(*pos + 1) == Some(&Token::Punct('(')) && tokens.get(*pos + 3) == Some(&Token::Punct(')')) {
+                    if let Some(&Token::Number(num)) = tokens.get(*pos + 2) {
+                        number = Some(num);
+                        *pos += 3;
+                    }
+                }
+                components.push(Component { name: Some(word.to_owned()), number });
+                *pos += 1;
+            }
+            _ => return None
+        }
+    }
+}
+
+/**
+ * Extract the modules from the tokens of a MIB file.
+ */
+fn parse_modules(tokens: &[Token]) -> Vec<Module> {
+    let mut modules = Vec::new();
+    let mut module: Option<Module> = None;
+    let mut pos = 0;
+
+    while pos < tokens.len() {
+        let token = &tokens[pos];
+        let next = tokens.get(pos + 1);
+
+        if module.is_none() {
+            if let Token::Word(name) = token {
+                if is_word(next, "DEFINITIONS") {
+                    module = Some(Module { name: name.to_owned(), ..Module::default() });
+                    while pos < tokens.len() && !is_word(tokens.get(pos), "BEGIN") {
+                        pos += 1;
+                    }
+                }
+            }
+            pos += 1;
+            continue;
+        }
+
+        match token {
+            Token::Word(word) if word == "END" => {
+                modules.push(module.take().unwrap());
+                pos += 1;
+            }
+            Token::Word(word) if word == "IMPORTS" => {
+                let imports = &mut module.as_mut().unwrap().imports;
+                let mut pending = Vec::new();
+                pos += 1;
+                while pos < tokens.len() && tokens[pos] != Token::Punct(';') {
+                    if let Token::Word(ref word) = tokens[pos] {
+                        if word == "FROM" {
+                            if let Some(Token::Word(from)) = tokens.get(pos + 1) {
+                                for symbol in pending.drain(..) {
+                                    imports.insert(symbol, from.to_owned());
+                                }
+                                pos += 1;
+                            }
+                        }
+                        else {
+                            pending.push(word.to_owned());
+                        }
+                    }
+                    pos += 1;
+                }
+            }
+            &Token::Word(_) if is_word(next, "MACRO") => {
+                // Macro definitions (as in SNMPv2-SMI) have their own BEGIN/END
+                while pos < tokens.len() && !is_word(tokens.get(pos), "END") {
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            Token::Word(name) if name.starts_with(|chr: char| chr.is_lowercase()) => {
+                let is_oid_assignment =
+                    is_word(next, "OBJECT") && is_word(tokens.get(pos + 2), "IDENTIFIER") &&
+                    tokens.get(pos + 3) == Some(&Token::Assign);
+                let is_macro = match next {
+                    Some(Token::Word(word)) => OID_MACROS.contains(&&word[..]),
+                    _ => false
+                };
+                pos += 1;
+                if is_oid_assignment || is_macro {
+                    while pos < tokens.len() && tokens[pos] != Token::Assign {
+                        pos += 1;
+                    }
+                    pos += 1;
+                    if let Some(components) = parse_oid_value(tokens, &mut pos) {
+                        module.as_mut().unwrap().definitions.push(Definition {
+                            name:       name.to_owned(),
+                            components,
+                        });
+                    }
+                }
+            }
+            _ => pos += 1
+        }
+    }
+    modules
+}
+
+/**
+ * Symbols loaded from MIB modules, for translating between names and OIDs.
+ */
+#[derive(Default)]
+pub struct Mibs {
+    modules: Vec<Module>,
+    // (module, name) -> OID
+    oids:    HashMap<(String, String), Vec<u32>>,
+    // OID -> (module, name), the first one that was loaded wins
+    names:   HashMap<Vec<u32>, (String, String)>,
+}
+
+impl Mibs {
+    pub fn new() -> Mibs {
+        Mibs::default()
+    }
+
+    /**
+     * Load all MIB modules found in the given directory. Files that do not
+     * contain any modules are ignored.
+     */
+    pub fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut paths = fs::read_dir(dir)
+            .chain_err(|| format!("Could not read MIB directory {}", dir.display()))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            self.parse_file(&path)?;
+        }
+        self.resolve_all();
+        Ok(())
+    }
+
+    pub fn load_file(&mut self, path: &Path) -> Result<()> {
+        self.parse_file(path)?;
+        self.resolve_all();
+        Ok(())
+    }
+
+    pub fn load_str(&mut self, text: &str) {
+        self.modules.extend(parse_modules(&tokenize(text)));
+        self.resolve_all();
+    }
+
+    fn parse_file(&mut self, path: &Path) -> Result<()> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .chain_err(|| format!("Could not read MIB file {}", path.display()))?;
+        self.modules.extend(parse_modules(&tokenize(&String::from_utf8_lossy(&bytes))));
+        Ok(())
+    }
+
+    fn resolve_all(&mut self) {
+        let mut resolver = Resolver {
+            modules:   &self.modules,
+            qualified: HashMap::new(),
+            anywhere:  HashMap::new(),
+            oids:      HashMap::new(),
+        };
+        for (mod_idx, module) in self.modules.iter().enumerate() {
+            for (def_idx, definition) in module.definitions.iter().enumerate() {
+                resolver.qualified.entry((&module.name[..], &definition.name[..])).or_insert((mod_idx, def_idx));
+                resolver.anywhere.entry(&definition.name[..]).or_insert((mod_idx, def_idx));
+            }
+        }
+        for module in &self.modules {
+            for definition in &module.definitions {
+                resolver.resolve(&module.name, &definition.name, 0);
+            }
+        }
+
+        let oids = resolver.oids;
+        self.names.clear();
+        for module in &self.modules {
+            for definition in &module.definitions {
+                let key = (module.name.to_owned(), definition.name.to_owned());
+                if let Some(Some(oid)) = oids.get(&key) {
+                    self.names.entry(oid.clone()).or_insert(key);
+                }
+            }
+        }
+        self.oids = oids.into_iter()
+            .filter_map(|(key, oid)| oid.map(|oid| (key, oid)))
+            .collect();
+    }
+
+    /**
+     * Translate a name into an OID. Accepted forms are numeric OIDs (1.3.6.1 or .1.3.6.1),
+     * names (sysUpTime) and qualified names (SNMPv2-MIB::sysUpTime), the latter two
+     * optionally followed by numeric instance parts (sysUpTime.0).
+     */
+    pub fn resolve_name(&self, input: &str) -> Result<OID> {
+        let input = input.trim_start_matches('.');
+        let (module, rest) = match input.find("::") {
+            Some(pos) => (Some(&input[..pos]), &input[pos + 2..]),
+            None      => (None, input)
+        };
+        let mut parts = rest.split('.');
+        let symbol = parts.next().unwrap_or("");
+        let mut suffix = Vec::new();
+        for part in parts {
+            suffix.push(part.parse::<u32>()
+                .chain_err(|| format!("Invalid part {} in OID {}", part, input))?);
+        }
+
+        let mut oid = match (module, symbol.parse::<u32>()) {
+            (None, Ok(number)) => vec![number],
+            (Some(module), _)  => self.oids.get(&(module.to_owned(), symbol.to_owned()))
+                .cloned()
+                .ok_or_else(|| format!("Unknown object {}::{}", module, symbol))?,
+            (None, Err(_))     => self.oids.iter()
+                .filter(|&((_, name), _)| name == symbol)
+                .map(|(_, oid)| oid.clone())
+                .min()
+                .ok_or_else(|| format!("Unknown object {}", symbol))?,
+        };
+        oid.extend(suffix);
+        Ok(OID::from_vec(&oid))
+    }
+
+    /**
+     * Translate an OID into MODULE::name.instance, using the longest known prefix.
+     * OIDs that are not covered by any loaded module stay numeric.
+     */
+    pub fn name_of(&self, oid: &OID) -> String {
+        let parts = oid.as_vec();
+        for len in (1..parts.len() + 1).rev() {
+            if let Some((module, name)) = self.names.get(&parts[..len]) {
+                let mut name = format!("{}::{}", module, name);
+                for part in &parts[len..] {
+                    name.push_str(&format!(".{}", part));
+                }
+                return name;
+            }
+        }
+        oid.to_string()
+    }
+}
+
+struct Resolver<'m> {
+    modules:   &'m [Module],
+    // (module, name) -> (index into modules, index into definitions)
+    qualified: HashMap<(&'m str, &'m str), (usize, usize)>,
+    // name -> the first definition of that name in any module
+    anywhere:  HashMap<&'m str, (usize, usize)>,
+    oids:      HashMap<(String, String), Option<Vec<u32>>>,
+}
+
+impl<'m> Resolver<'m> {
+    /**
+     * Find the module that defines name, as seen from inside module.
+     */
+    fn find_definition(&self, module: &str, name: &str) -> Option<(&'m Module, &'m Definition)> {
+        let imported_from = self.modules.iter()
+            .find(|candidate| candidate.name == module)
+            .and_then(|candidate| candidate.imports.get(name));
+        self.qualified.get(&(module, name))
+            .or_else(|| imported_from.and_then(|from| self.qualified.get(&(&from[..], name))))
+            .or_else(|| self.anywhere.get(name))
+            .map(|&(mod_idx, def_idx)| {
+                let module = &self.modules[mod_idx];
+                (module, &module.definitions[def_idx])
+            })
+    }
+
+    fn resolve(&mut self, module: &str, name: &str, depth: usize) -> Option<Vec<u32>> {
+        match name {
+            "ccitt"           => return Some(vec![0]),
+            "iso"             => return Some(vec![1]),
+            "joint-iso-ccitt" => return Some(vec![2]),
+            _ => ()
+        }
+        let (module, definition) = self.find_definition(module, name)?;
+        let key = (module.name.to_owned(), name.to_owned());
+        if let Some(oid) = self.oids.get(&key) {
+            return oid.clone();
+        }
+        if depth > 64 {
+            // Definitions referencing each other in a loop
+            return None;
+        }
+        self.oids.insert(key.clone(), None);
+
+        let mut oid = Vec::new();
+        for (idx, component) in definition.components.iter().enumerate() {
+            match (idx, &component.name, component.number) {
+                (_, _, Some(number))    => oid.push(number),
+                (0, Some(parent), None) => oid = self.resolve(&module.name, parent, depth + 1)?,
+                _                       => return None
+            }
+        }
+        self.oids.insert(key, Some(oid.clone()));
+        Some(oid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMI: &str = r#"
+SNMPv2-SMI DEFINITIONS ::= BEGIN
+-- the path to the root
+org            OBJECT IDENTIFIER ::= { iso 3 }  --  "iso" = 1
+dod            OBJECT IDENTIFIER ::= { org 6 }
+internet       OBJECT IDENTIFIER ::= { dod 1 }
+mgmt           OBJECT IDENTIFIER ::= { internet 2 }
+mib-2          OBJECT IDENTIFIER ::= { mgmt 1 }
+private        OBJECT IDENTIFIER ::= { internet 4 }
+enterprises    OBJECT IDENTIFIER ::= { private 1 }
+
+OBJECT-TYPE MACRO ::=
+BEGIN
+    TYPE NOTATION ::= "SYNTAX" Syntax
+    VALUE NOTATION ::= value(VALUE ObjectName)
+END
+END
+"#;
+
+    const UCD: &str = r#"
+UCD-SNMP-MIB DEFINITIONS ::= BEGIN
+
+IMPORTS
+    OBJECT-TYPE, MODULE-IDENTITY, enterprises, Integer32
+        FROM SNMPv2-SMI
+    DisplayString FROM SNMPv2-TC;
+
+ucdavis MODULE-IDENTITY
+    LAST-UPDATED "200901190000Z"
+    DESCRIPTION "This file defines the private UCD SNMP MIB extensions. { not 1 }"
+    ::= { enterprises 2021 }
+
+dskTable OBJECT-TYPE
+    SYNTAX      SEQUENCE OF DskEntry
+    MAX-ACCESS  not-accessible
+    ::= { ucdavis 9 }
+
+dskEntry OBJECT-TYPE
+    SYNTAX      DskEntry
+    INDEX       { dskIndex }
+    ::= { dskTable 1 }
+
+DskEntry ::= SEQUENCE {
+    dskIndex    Integer32,
+    dskPath     DisplayString,
+    dskOid      OBJECT IDENTIFIER
+}
+
+dskIndex OBJECT-TYPE
+    SYNTAX      Integer32 (0..65535)
+    DEFVAL      { 0 }
+    ::= { dskEntry 1 }
+
+dskPath OBJECT-TYPE
+    SYNTAX      DisplayString
+    ::= { dskEntry 2 }
+
+END
+"#;
+
+    fn mibs() -> Mibs {
+        let mut mibs = Mibs::new();
+        // Order must not matter
+        mibs.load_str(UCD);
+        mibs.load_str(SMI);
+        mibs
+    }
+
+    #[test]
+    fn resolve_names() {
+        let mibs = mibs();
+        assert_eq!(mibs.resolve_name("UCD-SNMP-MIB::dskTable").unwrap().to_string(), "1.3.6.1.4.1.2021.9");
+        assert_eq!(mibs.resolve_name("dskPath.3").unwrap().to_string(), "1.3.6.1.4.1.2021.9.1.2.3");
+        assert_eq!(mibs.resolve_name("SNMPv2-SMI::mib-2").unwrap().to_string(), "1.3.6.1.2.1");
+        assert_eq!(mibs.resolve_name(".1.3.6.1.2.1.1.3.0").unwrap().to_string(), "1.3.6.1.2.1.1.3.0");
+        assert!(mibs.resolve_name("UCD-SNMP-MIB::dskOid").is_err());
+        assert!(mibs.resolve_name("nonexistent").is_err());
+        assert!(mibs.resolve_name("dskPath.x").is_err());
+    }
+
+    #[test]
+    fn translate_oids() {
+        let mibs = mibs();
+        assert_eq!(mibs.name_of(&OID::from_parts(&["1.3.6.1.4.1.2021.9.1.2.3"])), "UCD-SNMP-MIB::dskPath.3");
+        assert_eq!(mibs.name_of(&OID::from_parts(&["1.3.6.1.4.1.2021.9.1"])), "UCD-SNMP-MIB::dskEntry");
+        assert_eq!(mibs.name_of(&OID::from_parts(&["1.3.6.1.2.1.1.3.0"])), "SNMPv2-SMI::mib-2.1.3.0");
+        assert_eq!(mibs.name_of(&OID::from_parts(&["1.2.3"])), "1.2.3");
+    }
+
+    #[test]
+    fn inline_oid_names() {
+        let mut mibs = Mibs::new();
+        mibs.load_str("TEST-MIB DEFINITIONS ::= BEGIN\ntest OBJECT IDENTIFIER ::= { iso org(3) dod(6) 1 99 }\nEND");
+        assert_eq!(mibs.resolve_name("TEST-MIB::test").unwrap().to_string(), "1.3.6.1.99");
+    }
+}
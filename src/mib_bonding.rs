@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use value::{Value,str_from_file,u32_from_file};
+use oid::OID;
+use table::{Table,TableDef,Column};
+use table::Syntax::*;
+use host::Host;
+use filter::Filter;
+use mib_net::parse_phys_address;
+
+const BOND_COLUMNS: &[Column] = &[
+    Column::new(     1, "suntBondName",         OctetString).describe("Name of the bond, e.g. bond0."),
+    Column::new(     2, "suntBondMode",         OctetString)
+        .describe("Bonding mode as the kernel calls it, e.g. 802.3ad or active-backup."),
+    Column::new(     3, "suntBondMiiStatus",    Integer).describe("up(1) or down(2), as seen by MII link monitoring."),
+    Column::optional(4, "suntBondActiveSlave",  OctetString)
+        .describe("Slave currently carrying the traffic, in modes that only use one at a time."),
+    Column::new(     5, "suntBondSlaves",       Gauge32).describe("Number of slaves of the bond."),
+    Column::new(     6, "suntBondSlavesUp",     Gauge32).describe("Number of slaves whose MII status is up."),
+    Column::new(     7, "suntBondDegraded",     Integer).describe(
+        "true(1) if the bond is down, has no slaves, one of its slaves is down or, in 802.3ad mode, \
+         not part of the active aggregator. false(2) otherwise."),
+    Column::optional(8, "suntBondAggregatorId", Integer).describe("ID of the active 802.3ad aggregator."),
+    Column::optional(9, "suntBondPartnerMac",   OctetString)
+        .describe("System MAC address of the 802.3ad partner of the active aggregator, usually a switch."),
+];
+
+const BOND_SLAVE_COLUMNS: &[Column] = &[
+    Column::new(     1, "suntBondSlaveIfIndex",      Integer).describe("ifIndex of the slave."),
+    Column::new(     2, "suntBondSlaveName",         OctetString).describe("Name of the slave, e.g. eno1."),
+    Column::new(     3, "suntBondSlaveMiiStatus",    Integer).describe("up(1) or down(2), as seen by MII link monitoring."),
+    Column::new(     4, "suntBondSlaveLinkFailures", Counter32)
+        .describe("How often MII link monitoring saw the slave go down since it was enslaved."),
+    Column::optional(5, "suntBondSlaveAggregatorId", Integer).describe("ID of the 802.3ad aggregator the slave is part of."),
+    Column::optional(6, "suntBondSlavePartnerMac",   OctetString)
+        .describe("System MAC address of the 802.3ad partner the slave is connected to."),
+];
+
+pub const BOND_TABLE: TableDef = TableDef {
+    name:        "suntBondTable",
+    entry_oid:   "1.3.6.1.4.1.2147483647.1.1.1",
+    description: "Bonding interfaces, with their mode and state. Indexed by the ifIndex of the bond.",
+    index:       &["ifIndex"],
+    columns:     BOND_COLUMNS,
+};
+
+pub const BOND_SLAVE_TABLE: TableDef = TableDef {
+    name:        "suntBondSlaveTable",
+    entry_oid:   "1.3.6.1.4.1.2147483647.1.2.1",
+    description: "Slaves of the bonds in suntBondTable. Indexed by the ifIndex of the bond and the slave.",
+    index:       &["ifIndex", "suntBondSlaveIfIndex"],
+    columns:     BOND_SLAVE_COLUMNS,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct SlaveStatus {
+    name:          String,
+    mii_up:        bool,
+    link_failures: u64,
+    aggregator:    Option<i64>,
+    partner_mac:   Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct BondStatus {
+    mii_up:       bool,
+    active_slave: Option<String>,
+    aggregator:   Option<i64>,
+    partner_mac:  Option<Vec<u8>>,
+    slaves:       Vec<SlaveStatus>,
+}
+
+impl BondStatus {
+    fn slaves_up(&self) -> usize {
+        self.slaves.iter().filter(|slave| slave.mii_up).count()
+    }
+
+    fn degraded(&self) -> bool {
+        !self.mii_up || self.slaves.is_empty() || self.slaves_up() < self.slaves.len() ||
+            (self.aggregator.is_some() && self.slaves.iter().any(|slave| slave.aggregator != self.aggregator))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Bond,
+    ActiveAggregator,
+    Slave,
+    ActorPdu,
+    PartnerPdu,
+}
+
+/**
+ * Parse /proc/net/bonding/<bond>, which is a bunch of "Key: value" lines:
+ * First the bond, then for 802.3ad the active aggregator and then each slave.
+ */
+fn parse_bond_status(text: &str) -> BondStatus {
+    let mut status = BondStatus::default();
+    let mut section = Section::Bond;
+
+    for line in text.lines() {
+        let line = line.trim();
+        let (key, value) = match line.find(':') {
+            Some(pos) => (&line[..pos], line[pos + 1..].trim()),
+            None      => continue
+        };
+        match (section, key) {
+            (_, "Slave Interface") => {
+                status.slaves.push(SlaveStatus { name: String::from(value), ..SlaveStatus::default() });
+                section = Section::Slave;
+            }
+            (_, "Active Aggregator Info") => section = Section::ActiveAggregator,
+            (_, "details actor lacp pdu") => section = Section::ActorPdu,
+            (_, "details partner lacp pdu") => section = Section::PartnerPdu,
+
+            (Section::Bond, "MII Status") => status.mii_up = value == "up",
+            (Section::Bond, "Currently Active Slave") if value != "None" => status.active_slave = Some(String::from(value)),
+            (Section::ActiveAggregator, "Aggregator ID") => status.aggregator = value.parse().ok(),
+            (Section::ActiveAggregator, "Partner Mac Address") => status.partner_mac = Some(parse_phys_address(value)),
+
+            (_, _) => if let Some(slave) = status.slaves.last_mut() {
+                match (section, key) {
+                    (Section::Slave, "MII Status") => slave.mii_up = value == "up",
+                    (Section::Slave, "Link Failure Count") => slave.link_failures = value.parse().unwrap_or(0),
+                    (Section::Slave, "Aggregator ID") => slave.aggregator = value.parse().ok(),
+                    (Section::PartnerPdu, "system mac address") => slave.partner_mac = Some(parse_phys_address(value)),
+                    _ => ()
+                }
+            }
+        }
+    }
+    status
+}
+
+pub fn get_bonds(values: &mut BTreeMap<OID, Value>, host: &Host, filter: &Filter) {
+    let mut bond_table = Table::from_def(&BOND_TABLE);
+    let mut slave_table = Table::from_def(&BOND_SLAVE_TABLE);
+
+    let sys = PathBuf::from("/sys/class/net");
+    let mut bonds = fs::read_dir(host.dir(&sys))
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|ifname| host.path(sys.join(ifname).join("bonding")).exists())
+            .collect::<Vec<String>>())
+        .unwrap_or_default();
+    bonds.sort();
+
+    for bond in bonds {
+        let included = filter.includes(|attribute| match attribute {
+            "name"  => vec![bond.to_owned()],
+            "class" => vec![String::from("bonding")],
+            _       => vec![]
+        });
+        if !included {
+            continue;
+        }
+
+        let text = match fs::read_to_string(host.path(PathBuf::from("/proc/net/bonding").join(&bond))) {
+            Ok(text) => text,
+            Err(_)   => continue
+        };
+        let ifindex = match u32_from_file(&host.path(sys.join(&bond).join("ifindex"))) {
+            Some(ifindex) => ifindex,
+            None          => continue
+        };
+        let status = parse_bond_status(&text);
+        // e.g. "802.3ad 4"
+        let mode = str_from_file(&host.path(sys.join(&bond).join("bonding/mode")))
+            .and_then(|mode| mode.split_whitespace().next().map(String::from))
+            .unwrap_or_default();
+
+        bond_table.row(ifindex)
+            .set("suntBondName",      Value::OctetString(bond.to_owned()))
+            .set("suntBondMode",      Value::OctetString(mode))
+            .set("suntBondMiiStatus", Value::Integer(if status.mii_up { 1 } else { 2 }))
+            .set("suntBondSlaves",    Value::Unsigned32(status.slaves.len() as u32))
+            .set("suntBondSlavesUp",  Value::Unsigned32(status.slaves_up() as u32))
+            .set("suntBondDegraded",  Value::Integer(if status.degraded() { 1 } else { 2 }));
+        if let Some(ref active_slave) = status.active_slave {
+            bond_table.row(ifindex).set("suntBondActiveSlave", Value::OctetString(active_slave.to_owned()));
+        }
+        if let Some(aggregator) = status.aggregator {
+            bond_table.row(ifindex).set("suntBondAggregatorId", Value::Integer(aggregator));
+        }
+        if let Some(ref partner_mac) = status.partner_mac {
+            bond_table.row(ifindex).set("suntBondPartnerMac", Value::Octets(partner_mac.to_owned()));
+        }
+
+        for slave in &status.slaves {
+            let slave_ifindex = match u32_from_file(&host.path(sys.join(&slave.name).join("ifindex"))) {
+                Some(slave_ifindex) => slave_ifindex,
+                None                => continue
+            };
+            slave_table.row((ifindex, slave_ifindex))
+                .set("suntBondSlaveName",         Value::OctetString(slave.name.to_owned()))
+                .set("suntBondSlaveMiiStatus",    Value::Integer(if slave.mii_up { 1 } else { 2 }))
+                .set("suntBondSlaveLinkFailures", Value::Counter32(slave.link_failures));
+            if let Some(aggregator) = slave.aggregator {
+                slave_table.row((ifindex, slave_ifindex)).set("suntBondSlaveAggregatorId", Value::Integer(aggregator));
+            }
+            if let Some(ref partner_mac) = slave.partner_mac {
+                slave_table.row((ifindex, slave_ifindex)).set("suntBondSlavePartnerMac", Value::Octets(partner_mac.to_owned()));
+            }
+        }
+    }
+
+    bond_table.insert_into(values);
+    slave_table.insert_into(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_backup_with_a_slave_down() {
+        let status = parse_bond_status("\
+Ethernet Channel Bonding Driver: v5.10.0
+
+Bonding Mode: fault-tolerance (active-backup)
+Primary Slave: None
+Currently Active Slave: eth1
+MII Status: up
+MII Polling Interval (ms): 100
+
+Slave Interface: eth0
+MII Status: down
+Speed: Unknown
+Link Failure Count: 3
+Permanent HW addr: 52:54:00:aa:bb:01
+Slave queue ID: 0
+
+Slave Interface: eth1
+MII Status: up
+Speed: 10000 Mbps
+Link Failure Count: 0
+Permanent HW addr: 52:54:00:aa:bb:02
+Slave queue ID: 0
+");
+        assert_eq!(status.active_slave, Some(String::from("eth1")));
+        assert!(status.mii_up);
+        assert_eq!(status.aggregator, None);
+        assert_eq!(status.slaves.iter().map(|slave| (slave.name.as_str(), slave.mii_up, slave.link_failures)).collect::<Vec<_>>(),
+                   vec![("eth0", false, 3), ("eth1", true, 0)]);
+        assert_eq!(status.slaves_up(), 1);
+        assert!(status.degraded());
+    }
+
+    #[test]
+    fn lacp_slave_outside_the_active_aggregator() {
+        let mut status = BondStatus {
+            mii_up:     true,
+            aggregator: Some(1),
+            slaves:     vec![
+                SlaveStatus { name: String::from("eth0"), mii_up: true, aggregator: Some(1), ..SlaveStatus::default() },
+                SlaveStatus { name: String::from("eth1"), mii_up: true, aggregator: Some(1), ..SlaveStatus::default() },
+            ],
+            ..BondStatus::default()
+        };
+        assert!(!status.degraded());
+        // Cabled to another switch, which LACP puts in an aggregator of its own
+        status.slaves[1].aggregator = Some(2);
+        assert!(status.degraded());
+        status.slaves.clear();
+        assert!(status.degraded());
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use value::{Value,str_from_file,u32_from_file};
+use oid::OID;
+use table::{Table,TableDef,Column};
+use table::Syntax::*;
+use host::Host;
+use filter::Filter;
+use mib_net::parse_phys_address;
+
+const BASE_PORT_COLUMNS: &[Column] = &[
+    Column::new(1, "dot1dBasePort",        Integer),
+    Column::new(2, "dot1dBasePortIfIndex", Integer),
+];
+
+const STP_PORT_COLUMNS: &[Column] = &[
+    Column::new( 1, "dot1dStpPort",                 Integer),
+    Column::new( 2, "dot1dStpPortPriority",         Integer),
+    Column::new( 3, "dot1dStpPortState",            Integer),
+    Column::new( 4, "dot1dStpPortEnable",           Integer),
+    Column::new( 5, "dot1dStpPortPathCost",         Integer),
+    Column::new( 6, "dot1dStpPortDesignatedRoot",   OctetString),
+    Column::new( 7, "dot1dStpPortDesignatedCost",   Integer),
+    Column::new( 8, "dot1dStpPortDesignatedBridge", OctetString),
+    Column::new( 9, "dot1dStpPortDesignatedPort",   OctetString),
+    Column::new(11, "dot1dStpPortPathCost32",       Integer),
+];
+
+const TP_FDB_COLUMNS: &[Column] = &[
+    Column::new(1, "dot1dTpFdbAddress", OctetString),
+    Column::new(2, "dot1dTpFdbPort",    Integer),
+    Column::new(3, "dot1dTpFdbStatus",  Integer),
+];
+
+const BRIDGE_FDB_COLUMNS: &[Column] = &[
+    Column::new(     1, "suntBridgeFdbAddress",     OctetString).describe("MAC address the bridge knows about."),
+    Column::new(     2, "suntBridgeFdbPort",        Integer).describe("Port of the bridge the address was seen on, as in dot1dTpFdbPort."),
+    Column::optional(3, "suntBridgeFdbPortIfIndex", Integer).describe("ifIndex of that port."),
+    Column::new(     4, "suntBridgeFdbStatus",      Integer).describe("learned(3) or self(4), as in dot1dTpFdbStatus."),
+];
+
+pub const BRIDGE_FDB_TABLE: TableDef = TableDef {
+    name:        "suntBridgeFdbTable",
+    entry_oid:   "1.3.6.1.4.1.2147483647.1.7.1",
+    description: "Forwarding databases of all bridges, unlike the dot1dTpFdbTable which only has room for one. \
+                  Indexed by the ifIndex of the bridge and the MAC address.",
+    index:       &["ifIndex", "suntBridgeFdbAddress"],
+    columns:     BRIDGE_FDB_COLUMNS,
+};
+
+/// dot1dTpFdbStatus
+const FDB_LEARNED: i64 = 3;
+const FDB_SELF:    i64 = 4;
+
+/// Size of struct __fdb_entry, as read from /sys/class/net/<bridge>/brforward
+const FDB_ENTRY_SIZE: usize = 16;
+
+/**
+ * Translate the kernel's BR_STATE_* into dot1dStpPortState.
+ */
+fn stp_port_state(state: u32) -> i64 {
+    match state {
+        0 => 1, // disabled
+        1 => 3, // listening
+        2 => 4, // learning
+        3 => 5, // forwarding
+        4 => 2, // blocking
+        _ => 6, // broken
+    }
+}
+
+/**
+ * Parse a bridge ID like 8000.3c970ea1b201: Two bytes of priority and the bridge's MAC address.
+ */
+fn parse_bridge_id(bridge_id: &str) -> Option<Vec<u8>> {
+    let digits = bridge_id.replace('.', "");
+    if digits.len() != 16 {
+        return None;
+    }
+    (0..8).map(|pos| u8::from_str_radix(&digits[2 * pos..2 * pos + 2], 16).ok()).collect()
+}
+
+/**
+ * port_no and port_id are printed in hex, e.g. 0x1.
+ */
+fn hex_from_file(fpath: &PathBuf) -> Option<u32> {
+    let text = str_from_file(fpath)?;
+    u32::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FdbEntry {
+    address: [u8; 6],
+    port:    u32,
+    local:   bool,
+}
+
+/**
+ * Parse the forwarding database of a bridge, an array of struct __fdb_entry.
+ * With VLAN filtering the same address may be listed once per VLAN, only the
+ * first entry is kept as the dot1dTpFdbTable is indexed by address alone.
+ */
+fn parse_fdb(buf: &[u8]) -> BTreeMap<[u8; 6], FdbEntry> {
+    let mut entries = BTreeMap::new();
+    for chunk in buf.chunks(FDB_ENTRY_SIZE).filter(|chunk| chunk.len() == FDB_ENTRY_SIZE) {
+        let mut address = [0u8; 6];
+        address.copy_from_slice(&chunk[..6]);
+        entries.entry(address).or_insert(FdbEntry {
+            address,
+            port:    (chunk[12] as u32) << 8 | chunk[6] as u32,
+            local:   chunk[7] != 0,
+        });
+    }
+    entries
+}
+
+/**
+ * Export the first bridge (by name) that passes the interface filter. The
+ * BRIDGE-MIB only knows about a single bridge per agent, so the forwarding
+ * databases of all of them go to the suntBridgeFdbTable.
+ */
+pub fn get_bridge(values: &mut BTreeMap<OID, Value>, host: &Host, filter: &Filter, base_oid: &str) {
+    let sys = PathBuf::from("/sys/class/net");
+    let mut bridges = fs::read_dir(host.dir(&sys))
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|ifname| host.path(sys.join(ifname).join("bridge")).exists())
+            .filter(|ifname| filter.includes(|attribute| match attribute {
+                "name"  => vec![ifname.to_owned()],
+                "class" => vec![String::from("bridge")],
+                _       => vec![]
+            }))
+            .collect::<Vec<String>>())
+        .unwrap_or_default();
+    bridges.sort();
+
+    let mut bridge_fdb_table = Table::from_def(&BRIDGE_FDB_TABLE);
+    for (pos, bridge) in bridges.iter().enumerate() {
+        let first = pos == 0;
+        let brsys = sys.join(bridge);
+
+        let mut base_port_table = Table::new(&format!("{}.1.4.1", base_oid), BASE_PORT_COLUMNS);
+        let mut stp_port_table = Table::new(&format!("{}.2.15.1", base_oid), STP_PORT_COLUMNS);
+        let mut fdb_table = Table::new(&format!("{}.4.3.1", base_oid), TP_FDB_COLUMNS);
+
+        let mut ports = fs::read_dir(host.dir(brsys.join("brif")))
+            .map(|entries| entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect::<Vec<String>>())
+            .unwrap_or_default();
+        ports.sort();
+
+        let mut port_ifindexes = BTreeMap::new();
+        for port in ports {
+            let brport = sys.join(&port).join("brport");
+            let (port_no, ifindex) = match (hex_from_file(&host.path(brport.join("port_no"))),
+                                            u32_from_file(&host.path(sys.join(&port).join("ifindex")))) {
+                (Some(port_no), Some(ifindex)) => (port_no, ifindex),
+                _                              => continue
+            };
+            port_ifindexes.insert(port_no, ifindex);
+            if !first {
+                continue;
+            }
+
+            base_port_table.row(port_no)
+                .set("dot1dBasePort",        Value::Integer(port_no as i64))
+                .set("dot1dBasePortIfIndex", Value::Integer(ifindex as i64));
+
+            let state = u32_from_file(&host.path(brport.join("state"))).unwrap_or(0);
+            let path_cost = u32_from_file(&host.path(brport.join("path_cost"))).unwrap_or(0);
+            let designated_port = u32_from_file(&host.path(brport.join("designated_port"))).unwrap_or(0);
+            let designated_root = str_from_file(&host.path(brport.join("designated_root")))
+                .and_then(|bridge_id| parse_bridge_id(&bridge_id))
+                .unwrap_or_else(|| vec![0; 8]);
+            let designated_bridge = str_from_file(&host.path(brport.join("designated_bridge")))
+                .and_then(|bridge_id| parse_bridge_id(&bridge_id))
+                .unwrap_or_else(|| vec![0; 8]);
+
+            stp_port_table.row(port_no)
+                .set("dot1dStpPort",                 Value::Integer(port_no as i64))
+                .set("dot1dStpPortPriority",         Value::Integer(u32_from_file(&host.path(brport.join("priority"))).unwrap_or(0) as i64))
+                .set("dot1dStpPortState",            Value::Integer(stp_port_state(state)))
+                .set("dot1dStpPortEnable",           Value::Integer(if state == 0 { 2 } else { 1 }))
+                // Costs that do not fit are only reported in dot1dStpPortPathCost32
+                .set("dot1dStpPortPathCost",         Value::Integer(path_cost.min(65535) as i64))
+                .set("dot1dStpPortDesignatedRoot",   Value::Octets(designated_root))
+                .set("dot1dStpPortDesignatedCost",   Value::Integer(u32_from_file(&host.path(brport.join("designated_cost"))).unwrap_or(0) as i64))
+                .set("dot1dStpPortDesignatedBridge", Value::Octets(designated_bridge))
+                .set("dot1dStpPortDesignatedPort",   Value::Octets(vec![(designated_port >> 8) as u8, designated_port as u8]))
+                .set("dot1dStpPortPathCost32",       Value::Integer(path_cost as i64));
+        }
+
+        if first {
+            let address = str_from_file(&host.path(brsys.join("address"))).unwrap_or_default();
+            values.insert(OID::from_parts(&[base_oid, "1.1.0"]), Value::Octets(parse_phys_address(&address)));
+            values.insert(OID::from_parts(&[base_oid, "1.2.0"]), Value::Integer(port_ifindexes.len() as i64));
+            // transparent-only
+            values.insert(OID::from_parts(&[base_oid, "1.3.0"]), Value::Integer(2));
+            if let Some(ageing_time) = u32_from_file(&host.path(brsys.join("bridge/ageing_time"))) {
+                // In hundredths of a second
+                values.insert(OID::from_parts(&[base_oid, "4.2.0"]), Value::Integer((ageing_time / 100) as i64));
+            }
+        }
+
+        let fdb = fs::read(host.path(brsys.join("brforward"))).unwrap_or_default();
+        let bridge_ifindex = u32_from_file(&host.path(brsys.join("ifindex")));
+        for entry in parse_fdb(&fdb).values() {
+            let status = if entry.local { FDB_SELF } else { FDB_LEARNED };
+            if first {
+                fdb_table.row(entry.address)
+                    .set("dot1dTpFdbAddress", Value::Octets(entry.address.to_vec()))
+                    .set("dot1dTpFdbPort",    Value::Integer(entry.port as i64))
+                    .set("dot1dTpFdbStatus",  Value::Integer(status));
+            }
+            if let Some(bridge_ifindex) = bridge_ifindex {
+                let mut row = bridge_fdb_table.row((bridge_ifindex, entry.address));
+                row.set("suntBridgeFdbPort",   Value::Integer(entry.port as i64))
+                   .set("suntBridgeFdbStatus", Value::Integer(status));
+                if let Some(ifindex) = port_ifindexes.get(&entry.port) {
+                    row.set("suntBridgeFdbPortIfIndex", Value::Integer(*ifindex as i64));
+                }
+            }
+        }
+
+        base_port_table.insert_into(values);
+        stp_port_table.insert_into(values);
+        fdb_table.insert_into(values);
+    }
+    bridge_fdb_table.insert_into(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fdb_entries() {
+        let fdb = [
+            // 52:54:00:12:34:56 learned on port 0x102, then again for a second VLAN
+            0x52, 0x54, 0x00, 0x12, 0x34, 0x56, 0x02, 0, 100, 0, 0, 0, 0x01, 0, 0, 0,
+            0x52, 0x54, 0x00, 0x12, 0x34, 0x56, 0x03, 0, 100, 0, 0, 0, 0x00, 0, 0, 0,
+            // The bridge's own address on port 1
+            0x3c, 0x97, 0x0e, 0xa1, 0xb2, 0x01, 0x01, 1,   0, 0, 0, 0, 0x00, 0, 0, 0,
+            // Truncated
+            0x3c, 0x97, 0x0e,
+        ];
+        assert_eq!(parse_fdb(&fdb).values().cloned().collect::<Vec<_>>(), vec![
+            FdbEntry { address: [0x3c, 0x97, 0x0e, 0xa1, 0xb2, 0x01], port: 1,     local: true },
+            FdbEntry { address: [0x52, 0x54, 0x00, 0x12, 0x34, 0x56], port: 0x102, local: false },
+        ]);
+    }
+
+    #[test]
+    fn bridge_ids() {
+        assert_eq!(parse_bridge_id("8000.3c970ea1b201"), Some(vec![0x80, 0x00, 0x3c, 0x97, 0x0e, 0xa1, 0xb2, 0x01]));
+        assert_eq!(parse_bridge_id("8000.3c97"), None);
+        assert_eq!(stp_port_state(3), 5);
+        assert_eq!(stp_port_state(4), 2);
+    }
+}
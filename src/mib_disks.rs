@@ -1,67 +1,108 @@
 use std::collections::{BTreeMap,HashSet};
 use value::Value;
 use oid::OID;
+use table::{Table,Column};
+use table::Syntax::*;
 use std::fs;
 use std::fs::File;
-use std::mem;
-use std::io::{BufReader,BufRead,Error};
+use std::io::{BufReader,BufRead};
 use std::path::PathBuf;
-use std::ffi::CString;
-use libc;
+use host::Host;
+use filter::{Filter,Rule,Action};
 
+/// What filesystem filter rules can match on
+pub const FILESYSTEM_ATTRIBUTES: &[&str] = &["mountpoint", "device", "fstype", "options"];
+/// What block device filter rules can match on
+pub const DISK_ATTRIBUTES: &[&str] = &["name", "type"];
+
+/**
+ * Only filesystems on block devices, no tmpfs, proc and the like.
+ */
+pub fn filesystem_defaults() -> Vec<Rule> {
+    vec![
+        Rule::globs(Action::Include, &[("device", "/dev/*")]),
+        Rule::globs(Action::Exclude, &[]),
+    ]
+}
+
+pub fn disk_defaults() -> Vec<Rule> {
+    vec![
+        Rule::globs(Action::Exclude, &[("name", "loop*")]),
+    ]
+}
+
+const HR_STORAGE_COLUMNS: &[Column] = &[
+    Column::new( 1, "hrStorageIndex",           Integer),
+    // hrStorageType not supported
+    Column::new( 3, "hrStorageDescr",           OctetString),
+    Column::new( 4, "hrStorageAllocationUnits", Integer),
+    Column::new( 5, "hrStorageSize",            Integer),
+    Column::new( 6, "hrStorageUsed",            Integer),
+    // hrStorageAllocationFailures not supported
+];
+
+const DSK_COLUMNS: &[Column] = &[
+    Column::new( 1,      "dskIndex",       Integer),
+    Column::new( 2,      "dskPath",        OctetString),
+    Column::new( 3,      "dskDevice",      OctetString),
+    Column::new( 4,      "dskMinimum",     Integer),
+    Column::new( 5,      "dskMinPercent",  Integer),
+    Column::new( 6,      "dskTotal",       Integer),
+    Column::new( 7,      "dskAvail",       Integer),
+    Column::new( 8,      "dskUsed",        Integer),
+    Column::optional( 9, "dskPercent",     Integer),
+    Column::optional(10, "dskPercentNode", Integer),
+    // Rest: Unsupported
+];
+
+const DISK_IO_COLUMNS: &[Column] = &[
+    Column::new( 1, "diskIOIndex",     Integer),
+    Column::new( 2, "diskIODevice",    OctetString),
+    Column::new( 3, "diskIONRead",     Counter32),
+    Column::new( 4, "diskIONWritten",  Counter32),
+    Column::new( 5, "diskIOReads",     Counter32),
+    Column::new( 6, "diskIOWrites",    Counter32),
+    // 7, 8: ???
+    Column::new( 9, "diskIOLA1",       Integer),
+    Column::new(10, "diskIOLA5",       Integer),
+    Column::new(11, "diskIOLA15",      Integer),
+    Column::new(12, "diskIONReadX",    Counter64),
+    Column::new(13, "diskIONWrittenX", Counter64),
+];
 
 /**
  * device is some path under /dev. Resolve symlinks down to the actual /dev/something.
  */
-fn resolve_dev_symlinks(input: PathBuf) -> PathBuf {
-    if let Ok(target) = input.read_link() {
-        resolve_dev_symlinks(
-            input.parent()
-                .unwrap()
-                .join(target)
-                .canonicalize()
-                .unwrap()
-        )
-    }
-    else {
-        // Probably not a symlink
-        input
-    }
+fn resolve_dev_symlinks(host: &Host, input: PathBuf) -> PathBuf {
+    host.resolve_symlinks(input)
 }
 
 /**
  * device is a /dev/dm-*. Search /dev/mapper for a more meaningful name.
  */
-fn canonicalize_dm_name(devpath: PathBuf) -> Option<String> {
-    if let Ok(entries) = fs::read_dir("/dev/mapper") {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                // Resolve symlink
-                if let Ok(alias_path) = fs::read_link(entry.path()) {
-                    // Turn symlink "../dm-X" into "/dev/dm-X"
-                    let mut base = PathBuf::from("/dev/mapper");
-                    base.push(&alias_path);
-                    if fs::canonicalize(base).unwrap() == devpath {
-                        // Found our /dev/dm-X! See if the /dev/mapper name has a - in it (LV)
-                        let file_name_string = entry.file_name()
-                            .into_string()
-                            .unwrap();
-                        if file_name_string.contains("-") {
-                            // This is probably an LV
-                            let parts = file_name_string
-                                .splitn(2, "-")
-                                .map(|part| part.replace("--", "-"))
-                                .collect::<Vec<String>>();
-                            let lvpath = format!("{}/{}", parts[0], parts[1]);
-                            // Check if /dev/vg/lv exists
-                            if PathBuf::from("/dev").join(&lvpath).exists() {
-                                return Some(lvpath);
-                            }
-                        }
-                        // Something else, return as-is.
-                        return Some(file_name_string);
+fn canonicalize_dm_name(host: &Host, devpath: PathBuf) -> Option<String> {
+    if let Ok(entries) = fs::read_dir(host.dir("/dev/mapper")) {
+        for entry in entries.flatten() {
+            // Resolve symlink, e.g. "../dm-X" into "/dev/dm-X"
+            if resolve_dev_symlinks(host, PathBuf::from("/dev/mapper").join(entry.file_name())) == devpath {
+                // Found our /dev/dm-X! See if the /dev/mapper name has a - in it (LV)
+                let file_name_string = entry.file_name()
+                    .into_string()
+                    .unwrap();
+                if file_name_string.contains("-") {
+                    // This is probably an LV
+                    let parts = file_name_string
+                        .splitn(2, "-")
+                        .map(|part| part.replace("--", "-"))
+                        .collect::<Vec<String>>();
+                    let lvpath = format!("{}/{}", parts[0], parts[1]);
+                    // Check if /dev/vg/lv exists
+                    if host.path(PathBuf::from("/dev").join(&lvpath)).symlink_metadata().is_ok() {
+                        return Some(lvpath);
                     }
                 }
+                // Something else, return as-is.
+                return Some(file_name_string);
             }
         }
     }
@@ -70,10 +111,15 @@ fn canonicalize_dm_name(devpath: PathBuf) -> Option<String> {
 
 pub fn get_filesystems(
     values: &mut BTreeMap<OID, Value>,
+    host: &Host,
+    filter: &Filter,
     hr_storage_table_oid: &str,
     dsk_table_oid: &str
 ) {
-    if let Ok(diskstats) = File::open("/proc/mounts") {
+    let mut hr_storage_table = Table::new(hr_storage_table_oid, HR_STORAGE_COLUMNS);
+    let mut dsk_table = Table::new(dsk_table_oid, DSK_COLUMNS);
+
+    if let Ok(diskstats) = File::open(host.path("/proc/mounts")) {
         let mut disk_idx = 1;
         let dups : &mut HashSet<u64> = &mut HashSet::new();
 
@@ -84,23 +130,31 @@ pub fn get_filesystems(
             // device mountpoint fstype options dump pass
 
             let device = String::from(parts[0]);
-            let devpath = resolve_dev_symlinks(PathBuf::from(&device));
+            let devpath = resolve_dev_symlinks(host, PathBuf::from(&device));
             let mountpoint = String::from(parts[1]);
 
-            if !device.starts_with("/dev") {
+            let alias =
+                if devpath.to_str().unwrap().starts_with("/dev/dm-") {
+                    // Find a name better suited for dem humans
+                    canonicalize_dm_name(host, devpath)
+                        .map(|name| format!("/dev/{}", name))
+                }
+                else {
+                    None
+                };
+
+            let included = filter.includes(|attribute| match attribute {
+                "mountpoint" => vec![mountpoint.to_owned()],
+                "device"     => vec![device.to_owned()].into_iter().chain(alias.clone()).collect(),
+                "fstype"     => vec![String::from(parts[2])],
+                "options"    => parts[3].split(',').map(String::from).collect(),
+                _            => vec![]
+            });
+            if !included {
                 continue;
             }
 
-            let fsstat = unsafe {
-                let mut fsstat: libc::statvfs64 = mem::zeroed();
-                let path = CString::new(&mountpoint[..]).unwrap();
-                if libc::statvfs64(path.as_ptr(), &mut fsstat) != 0 {
-                    Err(Error::last_os_error())
-                }
-                else {
-                    Ok(fsstat)
-                }
-            };
+            let fsstat = host.statvfs(&mountpoint);
 
             if fsstat.is_err() {
                 // TODO: We should probably log this or sumt'n
@@ -109,117 +163,64 @@ pub fn get_filesystems(
 
             let fsstat = fsstat.unwrap();
 
-            let alias =
-                if devpath.to_str().unwrap().starts_with("/dev/dm-") {
-                    // Find a name better suited for dem humans
-                    canonicalize_dm_name(devpath)
-                        .and_then(|name| Some(format!("/dev/{}", name)))
-                }
-                else {
-                    None
-                };
-
-
             // Filter dups (bind mounts, e.g. docker)
-            if dups.contains(&fsstat.f_fsid) {
+            if dups.contains(&fsstat.fsid) {
                 continue;
             }
             else {
-                dups.insert(fsstat.f_fsid);
+                dups.insert(fsstat.fsid);
             }
 
             // hrStorageTable
 
-            values.insert( // hrStorageIndex
-                OID::from_parts_and_instance(&[hr_storage_table_oid, "1"], disk_idx),
-                Value::Integer(disk_idx as i64)
-            );
-            // hrStorageType not supported
-            values.insert( // hrStorageDescr
-                OID::from_parts_and_instance(&[hr_storage_table_oid, "3"], disk_idx),
-                Value::OctetString(mountpoint.to_owned())
-            );
-            values.insert( // hrStorageAllocationUnits
-                OID::from_parts_and_instance(&[hr_storage_table_oid, "4"], disk_idx),
-                Value::Integer(fsstat.f_frsize as i64)
-            );
-            values.insert( // hrStorageSize
-                OID::from_parts_and_instance(&[hr_storage_table_oid, "5"], disk_idx),
-                Value::Integer(fsstat.f_blocks as i64)
-            );
-            values.insert( // hrStorageUsed
-                OID::from_parts_and_instance(&[hr_storage_table_oid, "6"], disk_idx),
-                Value::Integer((fsstat.f_blocks - fsstat.f_bfree) as i64)
-            );
-            // hrStorageAllocationFailures is unsupported
+            hr_storage_table.row(disk_idx)
+                .set("hrStorageIndex",           Value::Integer(disk_idx as i64))
+                .set("hrStorageDescr",           Value::OctetString(mountpoint.to_owned()))
+                .set("hrStorageAllocationUnits", Value::Integer(fsstat.frsize as i64))
+                .set("hrStorageSize",            Value::Integer(fsstat.blocks as i64))
+                .set("hrStorageUsed",            Value::Integer((fsstat.blocks - fsstat.bfree) as i64));
 
             // dskTable
-            //  dskIndex  dskPath dskDevice dskMinimum dskMinPercent  dskTotal dskAvail  dskUsed
-            //  dskPercent dskPercentNode dskTotalLow dskTotalHigh dskAvailLow dskAvailHigh
-            //  dskUsedLow dskUsedHigh dskErrorFlag                           dskErrorMsg
-
-            values.insert( // dskIndex
-                OID::from_parts_and_instance(&[dsk_table_oid, "1"], disk_idx),
-                Value::Integer(disk_idx as i64)
-            );
-            values.insert( // dskPath
-                OID::from_parts_and_instance(&[dsk_table_oid, "2"], disk_idx),
-                Value::OctetString(mountpoint)
-            );
-            values.insert( // dskDevice
-                OID::from_parts_and_instance(&[dsk_table_oid, "3"], disk_idx),
-                Value::OctetString(alias.or(Some(device)).unwrap())
-            );
-            values.insert( // dskMinimum
-                OID::from_parts_and_instance(&[dsk_table_oid, "4"], disk_idx),
-                Value::Integer(0)
-            );
-            values.insert( // dskMinPercent
-                OID::from_parts_and_instance(&[dsk_table_oid, "5"], disk_idx),
-                Value::Integer(-1)
-            );
-            values.insert( // dskTotal
-                OID::from_parts_and_instance(&[dsk_table_oid, "6"], disk_idx),
-                Value::Integer( (fsstat.f_blocks * fsstat.f_frsize / 1024) as i64 )
-            );
-            values.insert( // dskAvail
-                OID::from_parts_and_instance(&[dsk_table_oid, "7"], disk_idx),
-                Value::Integer( (fsstat.f_bavail * fsstat.f_frsize / 1024) as i64 )
-            );
-
-            let f_bused = fsstat.f_blocks - fsstat.f_bfree;
-
-            values.insert( // dskUsed
-                OID::from_parts_and_instance(&[dsk_table_oid, "8"], disk_idx),
-                Value::Integer( (f_bused * fsstat.f_frsize / 1024) as i64 )
-            );
-            values.insert( // dskPercent
-                OID::from_parts_and_instance(&[dsk_table_oid, "9"], disk_idx),
-                Value::Integer( (f_bused * 100 / fsstat.f_blocks) as i64 )
-            );
-
-            if fsstat.f_files != 0 {
-                let f_fused = fsstat.f_files - fsstat.f_ffree;
-
-                values.insert(// dskPercentNode
-                    OID::from_parts_and_instance(&[dsk_table_oid, "10"], disk_idx),
-                    Value::Integer((f_fused * 100 / fsstat.f_files) as i64)
-                );
+
+            let f_bused = fsstat.blocks - fsstat.bfree;
+
+            dsk_table.row(disk_idx)
+                .set("dskIndex",      Value::Integer(disk_idx as i64))
+                .set("dskPath",       Value::OctetString(mountpoint))
+                .set("dskDevice",     Value::OctetString(alias.unwrap_or(device)))
+                .set("dskMinimum",    Value::Integer(0))
+                .set("dskMinPercent", Value::Integer(-1))
+                .set("dskTotal",      Value::Integer( (fsstat.blocks * fsstat.frsize / 1024) as i64 ))
+                .set("dskAvail",      Value::Integer( (fsstat.bavail * fsstat.frsize / 1024) as i64 ))
+                .set("dskUsed",       Value::Integer( (f_bused * fsstat.frsize / 1024) as i64 ));
+
+            // proc, sysfs and the like have no blocks at all
+            if let Some(percent) = (f_bused * 100).checked_div(fsstat.blocks) {
+                dsk_table.row(disk_idx).set("dskPercent", Value::Integer(percent as i64));
             }
 
-            // Rest: Unsupported
+            if fsstat.files != 0 {
+                let f_fused = fsstat.files - fsstat.ffree;
+                dsk_table.row(disk_idx)
+                    .set("dskPercentNode", Value::Integer((f_fused * 100 / fsstat.files) as i64));
+            }
 
             disk_idx += 1;
         }
     }
+
+    hr_storage_table.insert_into(values);
+    dsk_table.insert_into(values);
 }
 
-pub fn get_disks(values: &mut BTreeMap<OID, Value>, base_oid: &str) {
+pub fn get_disks(values: &mut BTreeMap<OID, Value>, host: &Host, filter: &Filter, base_oid: &str) {
     // UCD-DISKIO-MIB::diskIOTable
     // diskIOIndex diskIODevice diskIONRead diskIONWritten diskIOReads diskIOWrites ...
     // ... diskIOLA1 diskIOLA5 diskIOLA15 diskIONReadX diskIONWrittenX
 
-    if let Ok(diskstats) = File::open("/proc/diskstats") {
+    let mut disk_io_table = Table::new(base_oid, DISK_IO_COLUMNS);
+
+    if let Ok(diskstats) = File::open(host.path("/proc/diskstats")) {
         let mut disk_idx = 1;
 
         for line in BufReader::new(diskstats).lines() {
@@ -235,13 +236,24 @@ pub fn get_disks(values: &mut BTreeMap<OID, Value>, base_oid: &str) {
             let devpath = PathBuf::from(format!("/dev/{}", device));
             let mut alias = None;
 
-            if device.starts_with("loop") {
-                continue;
-            }
-
             if device.starts_with("dm-") {
                 // Find a name better suited for dem humans
-                alias = canonicalize_dm_name(devpath);
+                alias = canonicalize_dm_name(host, devpath);
+            }
+
+            let included = filter.includes(|attribute| match attribute {
+                "name" => vec![device.to_owned()].into_iter().chain(alias.clone()).collect(),
+                "type" => {
+                    let sys = PathBuf::from("/sys/class/block").join(&device);
+                    match host.path(sys.join("partition")).exists() {
+                        true  => vec![String::from("partition")],
+                        false => vec![String::from("disk")]
+                    }
+                }
+                _ => vec![]
+            });
+            if !included {
+                continue;
             }
 
             let reads  = parts[3].parse::<u64>().unwrap();
@@ -249,57 +261,24 @@ pub fn get_disks(values: &mut BTreeMap<OID, Value>, base_oid: &str) {
             let read_bytes = parts[5].parse::<u64>().unwrap() * 512;
             let wrtn_bytes = parts[6].parse::<u64>().unwrap() * 512;
 
-            values.insert( // diskIOIndex
-                OID::from_parts_and_instance(&[base_oid,  "1"], disk_idx),
-                Value::Integer(disk_idx as i64)
-            );
-            values.insert( // diskIODevice
-                OID::from_parts_and_instance(&[base_oid,  "2"], disk_idx),
-                Value::OctetString(alias.or(Some(device)).unwrap())
-            );
-            // NRead, NWritten (old sucky 32 bit counters). I hope these conversions are correct :/
-            values.insert( // diskIONRead
-                OID::from_parts_and_instance(&[base_oid,  "3"], disk_idx),
-                Value::Counter32(read_bytes)
-            );
-            values.insert( // diskIONWritten
-                OID::from_parts_and_instance(&[base_oid,  "4"], disk_idx),
-                Value::Counter32(wrtn_bytes)
-            );
-            // reads, writes
-            values.insert( // diskIOReads
-                OID::from_parts_and_instance(&[base_oid,  "5"], disk_idx),
-                Value::Counter32(reads)
-            );
-            values.insert( // diskIOWrites
-                OID::from_parts_and_instance(&[base_oid,  "6"], disk_idx),
-                Value::Counter32(writes)
-            );
-            // 7, 8: ???
-            // diskIOLA1, 5, 15
-            values.insert( // diskIOLA1
-                OID::from_parts_and_instance(&[base_oid,  "9"], disk_idx),
-                Value::Integer(0)
-            );
-            values.insert( // diskIOLA5
-                OID::from_parts_and_instance(&[base_oid, "10"], disk_idx),
-                Value::Integer(0)
-            );
-            values.insert( // diskIOLA15
-                OID::from_parts_and_instance(&[base_oid, "11"], disk_idx),
-                Value::Integer(0)
-            );
-            // NReadX, NWrittenX (new shiny 64 bit counters)
-            values.insert( // diskIONReadX
-                OID::from_parts_and_instance(&[base_oid, "12"], disk_idx),
-                Value::Counter64(read_bytes)
-            );
-            values.insert( // diskIONWrittenX
-                OID::from_parts_and_instance(&[base_oid, "13"], disk_idx),
-                Value::Counter64(wrtn_bytes)
-            );
+            disk_io_table.row(disk_idx)
+                .set("diskIOIndex",     Value::Integer(disk_idx as i64))
+                .set("diskIODevice",    Value::OctetString(alias.unwrap_or(device)))
+                // NRead, NWritten (old sucky 32 bit counters). I hope these conversions are correct :/
+                .set("diskIONRead",     Value::Counter32(read_bytes))
+                .set("diskIONWritten",  Value::Counter32(wrtn_bytes))
+                .set("diskIOReads",     Value::Counter32(reads))
+                .set("diskIOWrites",    Value::Counter32(writes))
+                .set("diskIOLA1",       Value::Integer(0))
+                .set("diskIOLA5",       Value::Integer(0))
+                .set("diskIOLA15",      Value::Integer(0))
+                // NReadX, NWrittenX (new shiny 64 bit counters)
+                .set("diskIONReadX",    Value::Counter64(read_bytes))
+                .set("diskIONWrittenX", Value::Counter64(wrtn_bytes));
 
             disk_idx += 1;
         }
     }
+
+    disk_io_table.insert_into(values);
 }
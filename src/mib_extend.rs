@@ -2,51 +2,81 @@ use std::collections::{BTreeMap};
 use value::Value;
 use oid::OID;
 use std::process::Command;
-use yaml_rust::Yaml;
+use std::os::unix::process::ExitStatusExt;
+use config::Extend;
+use table::{Table,Column};
+use table::Syntax::*;
 
-pub fn get_extend(values: &mut BTreeMap<OID, Value>, conf: &Option<Yaml>, extend_oid: &str) {
-    if conf.is_none() {
-        return;
-    }
-    let conf = conf.as_ref().unwrap();
-    for (name, command) in conf["extend"].as_hash().unwrap() {
-        let name = name
-            .as_str()
-            .expect(&format!("Name is not a string: {:?}", name));
-        let namepart = OID::asciify_part(&String::from(name));
-        let lenpart = format!("{}", name.len());
-
-        let output = Command::new(command["cmd"].as_str().expect("no command given"))
-            .args(
-                command["args"]
-                    .as_vec()
-                    .unwrap_or(&vec![])
-                    .iter()
-                    .map(|arg| arg.as_str().expect("arg is not a string"))
-                    .collect::<Vec<&str>>()
-            )
-            .output()
-            .expect("Could not execute command");
-
-        let output_string = String::from_utf8(output.stdout).unwrap();
-        let output_first = output_string.lines().nth(0).unwrap_or("");
+// NET-SNMP-EXTEND-MIB::nsExtendOutput1Table, indexed by nsExtendToken
+const EXTEND_OUTPUT_COLUMNS: &[Column] = &[
+    Column::new( 1, "nsExtendOutput1Line", OctetString),
+    Column::new( 2, "nsExtendOutputFull",  OctetString),
+    Column::new( 3, "nsExtendOutNumLines", Integer),
+    Column::new( 4, "nsExtendResult",      Integer),
+];
+
+/// nsExtendResult of commands that cannot be run at all, as a shell reports it
+const RESULT_NOT_RUN: i64 = 127;
+
+pub fn get_extend(values: &mut BTreeMap<OID, Value>, extends: &[Extend], extend_oid: &str) {
+    let mut extend_table = Table::new(extend_oid, EXTEND_OUTPUT_COLUMNS);
+
+    for extend in extends {
+        let name = extend.name.as_str();
+
+        let (output_string, result) = match Command::new(&extend.cmd).args(&extend.args).output() {
+            Ok(output) => {
+                // Killed by a signal, which shells report as 128 + the signal
+                let result = output.status.code()
+                    .or_else(|| output.status.signal().map(|signal| 128 + signal))
+                    .map_or(RESULT_NOT_RUN, |code| code as i64);
+                (String::from_utf8_lossy(&output.stdout).into_owned(), result)
+            }
+            Err(_) => (String::new(), RESULT_NOT_RUN)
+        };
+        let output_first = output_string.lines().next().unwrap_or("");
         let output_lines = output_string.lines().count();
 
-        values.insert( // nsExtendOutput1Line = 1
-            OID::from_parts(&[extend_oid, "1", &lenpart, &namepart]),
-            Value::OctetString(String::from(output_first))
-        );
-        values.insert( // nsExtendOutputFull = 2
-            OID::from_parts(&[extend_oid, "2", &lenpart, &namepart]),
-            Value::OctetString(String::from(output_string.trim_end()).to_owned())
-        );
-        values.insert( // nsExtendOutNumLines = 3
-            OID::from_parts(&[extend_oid, "3", &lenpart, &namepart]),
-            Value::Integer(output_lines as i64)
-        );
-        values.insert( // nsExtendResult = 4
-            OID::from_parts(&[extend_oid, "4", &lenpart, &namepart]),
-            Value::Integer(output.status.code().unwrap() as i64)
-        );
+        extend_table.row(name)
+            .set("nsExtendOutput1Line",  Value::OctetString(String::from(output_first)))
+            .set("nsExtendOutputFull",   Value::OctetString(String::from(output_string.trim_end())))
+            .set("nsExtendOutNumLines",  Value::Integer(output_lines as i64))
+            .set("nsExtendResult",       Value::Integer(result));
+    }
+
+    extend_table.insert_into(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extend(name: &str, cmd: &str, args: &[&str]) -> Extend {
+        Extend {
+            name: String::from(name),
+            cmd:  String::from(cmd),
+            args: args.iter().map(|&arg| String::from(arg)).collect(),
+        }
+    }
+
+    #[test]
+    fn failing_commands() {
+        let extends = [
+            extend("missing", "/nonexistent/command", &[]),
+            extend("binary",  "/bin/sh", &["-c", "printf 'ok\\377\\n'"]),
+            extend("killed",  "/bin/sh", &["-c", "kill -9 $$"]),
+        ];
+        let mut values = BTreeMap::new();
+        get_extend(&mut values, &extends, "1.3.6.1.4.1.8072.1.3.2.3.1");
+
+        let value = |column: u32, name: &str| {
+            let index = name.bytes().map(|byte| byte.to_string()).collect::<Vec<String>>().join(".");
+            values[&OID::from_parts(&[&format!("1.3.6.1.4.1.8072.1.3.2.3.1.{}.{}.{}", column, name.len(), index)])].clone()
+        };
+        assert_eq!(value(4, "missing"), Value::Integer(127));
+        assert_eq!(value(1, "missing"), Value::OctetString(String::new()));
+        assert_eq!(value(1, "binary"), Value::OctetString(String::from("ok\u{fffd}")));
+        assert_eq!(value(4, "binary"), Value::Integer(0));
+        assert_eq!(value(4, "killed"), Value::Integer(137));
     }
 }
@@ -0,0 +1,749 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use libc;
+use value::{Value,u32_from_file};
+use oid::OID;
+use table::{Table,Column,Index};
+use table::Syntax::*;
+use host::Host;
+use mib_net::{IncludedInterfaces,parse_phys_address};
+use mib_sys;
+use netlink;
+use netlink::{Address,Route,NextHop,Neighbor};
+
+// Entries of the tables get_addresses fills, relative to mib-2
+const IP_ADDRESS_ENTRY:        &str = "4.34.1";
+const IP_ADDRESS_PREFIX_ENTRY: &str = "4.32.1";
+const IP_ADDR_ENTRY:           &str = "4.20.1";
+
+// Entries of the tables get_neighbors fills, relative to mib-2
+const IP_NET_TO_PHYSICAL_ENTRY: &str = "4.35.1";
+const IP_NET_TO_MEDIA_ENTRY:    &str = "4.22.1";
+
+const ADDRESS_COLUMNS: &[Column] = &[
+    Column::new( 3, "ipAddressIfIndex",     Integer),
+    Column::new( 4, "ipAddressType",        Integer),
+    Column::new( 5, "ipAddressPrefix",      ObjectIdentifier),
+    Column::new( 6, "ipAddressOrigin",      Integer),
+    Column::new( 7, "ipAddressStatus",      Integer),
+    Column::new( 8, "ipAddressCreated",     TimeTicks),
+    Column::new( 9, "ipAddressLastChanged", TimeTicks),
+    Column::new(10, "ipAddressRowStatus",   Integer),
+    Column::new(11, "ipAddressStorageType", Integer),
+];
+
+const PREFIX_COLUMNS: &[Column] = &[
+    Column::new(5, "ipAddressPrefixOrigin",               Integer),
+    Column::new(6, "ipAddressPrefixOnLinkFlag",           Integer),
+    Column::new(7, "ipAddressPrefixAutonomousFlag",       Integer),
+    Column::new(8, "ipAddressPrefixAdvPreferredLifetime", Gauge32),
+    Column::new(9, "ipAddressPrefixAdvValidLifetime",     Gauge32),
+];
+
+const ADDR_COLUMNS: &[Column] = &[
+    Column::new(1, "ipAdEntAddr",         IpAddress),
+    Column::new(2, "ipAdEntIfIndex",      Integer),
+    Column::new(3, "ipAdEntNetMask",      IpAddress),
+    Column::new(4, "ipAdEntBcastAddr",    Integer),
+    Column::new(5, "ipAdEntReasmMaxSize", Integer),
+];
+
+const INET_CIDR_ROUTE_COLUMNS: &[Column] = &[
+    Column::new( 7, "inetCidrRouteIfIndex",   Integer),
+    Column::new( 8, "inetCidrRouteType",      Integer),
+    Column::new( 9, "inetCidrRouteProto",     Integer),
+    Column::new(11, "inetCidrRouteNextHopAS", Gauge32),
+    Column::new(12, "inetCidrRouteMetric1",   Integer),
+    Column::new(13, "inetCidrRouteMetric2",   Integer),
+    Column::new(14, "inetCidrRouteMetric3",   Integer),
+    Column::new(15, "inetCidrRouteMetric4",   Integer),
+    Column::new(16, "inetCidrRouteMetric5",   Integer),
+    Column::new(17, "inetCidrRouteStatus",    Integer),
+];
+
+const IP_CIDR_ROUTE_COLUMNS: &[Column] = &[
+    Column::new( 1, "ipCidrRouteDest",      IpAddress),
+    Column::new( 2, "ipCidrRouteMask",      IpAddress),
+    Column::new( 3, "ipCidrRouteTos",       Integer),
+    Column::new( 4, "ipCidrRouteNextHop",   IpAddress),
+    Column::new( 5, "ipCidrRouteIfIndex",   Integer),
+    Column::new( 6, "ipCidrRouteType",      Integer),
+    Column::new( 7, "ipCidrRouteProto",     Integer),
+    Column::new( 9, "ipCidrRouteInfo",      ObjectIdentifier),
+    Column::new(10, "ipCidrRouteNextHopAS", Integer),
+    Column::new(11, "ipCidrRouteMetric1",   Integer),
+    Column::new(12, "ipCidrRouteMetric2",   Integer),
+    Column::new(13, "ipCidrRouteMetric3",   Integer),
+    Column::new(14, "ipCidrRouteMetric4",   Integer),
+    Column::new(15, "ipCidrRouteMetric5",   Integer),
+    Column::new(16, "ipCidrRouteStatus",    Integer),
+];
+
+const NET_TO_PHYSICAL_COLUMNS: &[Column] = &[
+    Column::new(     4, "ipNetToPhysicalPhysAddress", OctetString),
+    // Not known without netlink
+    Column::optional(5, "ipNetToPhysicalLastUpdated", TimeTicks),
+    Column::new(     6, "ipNetToPhysicalType",        Integer),
+    Column::new(     7, "ipNetToPhysicalState",       Integer),
+    Column::new(     8, "ipNetToPhysicalRowStatus",   Integer),
+];
+
+const NET_TO_MEDIA_COLUMNS: &[Column] = &[
+    Column::new(1, "ipNetToMediaIfIndex",     Integer),
+    Column::new(2, "ipNetToMediaPhysAddress", OctetString),
+    Column::new(3, "ipNetToMediaNetAddress",  IpAddress),
+    Column::new(4, "ipNetToMediaType",        Integer),
+];
+
+/// InetAddressType
+const INET_UNKNOWN: u32 = 0;
+const INET_IPV4:    u32 = 1;
+const INET_IPV6:    u32 = 2;
+const INET_IPV6Z:   u32 = 4;
+
+/// IpAddressOriginTC
+const ORIGIN_MANUAL:    i64 = 2;
+const ORIGIN_DHCP:      i64 = 4;
+const ORIGIN_LINKLAYER: i64 = 5;
+const ORIGIN_RANDOM:    i64 = 6;
+
+/// IpAddressPrefixOriginTC
+const PREFIX_MANUAL:    i64 = 2;
+const PREFIX_WELLKNOWN: i64 = 3;
+const PREFIX_DHCP:      i64 = 4;
+const PREFIX_ROUTERADV: i64 = 5;
+
+/// IpAddressStatusTC
+const STATUS_PREFERRED:  i64 = 1;
+const STATUS_DEPRECATED: i64 = 2;
+const STATUS_TENTATIVE:  i64 = 6;
+const STATUS_DUPLICATE:  i64 = 7;
+const STATUS_OPTIMISTIC: i64 = 8;
+
+const TRUE:  i64 = 1;
+const FALSE: i64 = 2;
+
+/// The kernel reassembles datagrams of any size
+const REASM_MAX_SIZE: i64 = 65535;
+
+/// inetCidrRouteType, ipCidrRouteType lacks blackhole
+const ROUTE_OTHER:     i64 = 1;
+const ROUTE_REJECT:    i64 = 2;
+const ROUTE_LOCAL:     i64 = 3;
+const ROUTE_REMOTE:    i64 = 4;
+const ROUTE_BLACKHOLE: i64 = 5;
+
+/// Value of metrics a routing protocol does not use
+const METRIC_UNUSED: i64 = -1;
+
+// RTF_* route flags in /proc/net/route and /proc/net/ipv6_route
+const RTF_GATEWAY:  u32 = 0x0002;
+const RTF_DYNAMIC:  u32 = 0x0010;
+const RTF_MODIFIED: u32 = 0x0020;
+const RTF_REJECT:   u32 = 0x0200;
+const RTF_ADDRCONF: u32 = 0x40000;
+const RTF_LOCAL:    u32 = 0x80000000;
+
+// ATF_* flags in /proc/net/arp
+const ATF_COM:  u32 = 0x02;
+const ATF_PERM: u32 = 0x04;
+
+// ipNetToPhysicalType and ipNetToMediaType
+const NEIGHBOR_DYNAMIC: i64 = 3;
+const NEIGHBOR_STATIC:  i64 = 4;
+
+fn is_ipv6(address: &Address) -> bool {
+    address.family == libc::AF_INET6 as u8
+}
+
+fn is_link_local_bytes(bytes: &[u8]) -> bool {
+    bytes.len() == 16 && bytes[0] == 0xfe && bytes[1] & 0xc0 == 0x80
+}
+
+fn is_link_local(address: &Address) -> bool {
+    is_ipv6(address) && is_link_local_bytes(&address.address)
+}
+
+/**
+ * InetAddressType and InetAddress of an address. Link-local IPv6 addresses are
+ * only unique with their zone, which Linux identifies by the ifIndex.
+ */
+fn inet_address(bytes: &[u8], zone: u32) -> (u32, Vec<u8>) {
+    if bytes.len() == 4 {
+        (INET_IPV4, bytes.to_vec())
+    }
+    else if is_link_local_bytes(bytes) {
+        let mut zoned = bytes.to_vec();
+        zoned.extend_from_slice(&zone.to_be_bytes());
+        (INET_IPV6Z, zoned)
+    }
+    else {
+        (INET_IPV6, bytes.to_vec())
+    }
+}
+
+/**
+ * The address with all bits beyond the prefix length cleared.
+ */
+fn prefix_of(address: &[u8], prefix_len: u8) -> Vec<u8> {
+    address.iter().enumerate()
+        .map(|(pos, &byte)| {
+            let bits = (prefix_len as usize).saturating_sub(8 * pos).min(8);
+            if bits == 0 { 0 } else { byte & (0xffu8 << (8 - bits)) }
+        })
+        .collect()
+}
+
+/**
+ * How an address was configured, as far as the kernel's flags tell. IPv4
+ * addresses with a limited lifetime come from DHCP, IPv6 ones from stateless
+ * autoconfiguration, either based on the link-layer address or random (RFC 4941).
+ * IFA_F_TEMPORARY is IFA_F_SECONDARY for IPv4, so only IPv6 addresses can be random.
+ */
+fn address_origin(address: &Address) -> i64 {
+    if is_link_local(address) {
+        ORIGIN_LINKLAYER
+    }
+    else if is_ipv6(address) && address.flags & netlink::IFA_F_TEMPORARY != 0 {
+        ORIGIN_RANDOM
+    }
+    else if address.flags & netlink::IFA_F_PERMANENT != 0 {
+        ORIGIN_MANUAL
+    }
+    else if is_ipv6(address) {
+        ORIGIN_LINKLAYER
+    }
+    else {
+        ORIGIN_DHCP
+    }
+}
+
+fn prefix_origin(address: &Address) -> i64 {
+    if is_link_local(address) {
+        return PREFIX_WELLKNOWN;
+    }
+    match address_origin(address) {
+        ORIGIN_MANUAL => PREFIX_MANUAL,
+        ORIGIN_DHCP   => PREFIX_DHCP,
+        _             => PREFIX_ROUTERADV
+    }
+}
+
+fn address_status(flags: u32) -> i64 {
+    if flags & netlink::IFA_F_DADFAILED != 0 {
+        STATUS_DUPLICATE
+    }
+    else if flags & netlink::IFA_F_OPTIMISTIC != 0 {
+        STATUS_OPTIMISTIC
+    }
+    else if flags & netlink::IFA_F_TENTATIVE != 0 {
+        STATUS_TENTATIVE
+    }
+    else if flags & netlink::IFA_F_DEPRECATED != 0 {
+        STATUS_DEPRECATED
+    }
+    else {
+        STATUS_PREFERRED
+    }
+}
+
+/**
+ * The bytes as an IpAddress, or None if they are not 4 of them.
+ */
+fn ipv4(bytes: &[u8]) -> Option<[u8; 4]> {
+    if bytes.len() != 4 {
+        return None;
+    }
+    let mut address = [0u8; 4];
+    address.copy_from_slice(bytes);
+    Some(address)
+}
+
+/**
+ * The OIDs of the tables get_addresses fills.
+ */
+pub fn address_subtrees(mib2_oid: &str) -> Vec<String> {
+    [IP_ADDRESS_ENTRY, IP_ADDRESS_PREFIX_ENTRY, IP_ADDR_ENTRY].iter()
+        .map(|entry| format!("{}.{}", mib2_oid, entry))
+        .collect()
+}
+
+/**
+ * Export the IPv4 and IPv6 addresses of all interfaces in the ifTable. Needs
+ * netlink, without it the tables are left empty.
+ */
+pub fn get_addresses(values: &mut BTreeMap<OID, Value>, host: &Host, interfaces: &IncludedInterfaces, mib2_oid: &str) {
+    let addresses = match host.addr_dump().map(|dump| netlink::parse_addresses(&dump)) {
+        Ok(Ok(addresses)) => addresses,
+        _ => return
+    };
+    let interfaces = interfaces.get(host);
+
+    let mut address_table = Table::new(&format!("{}.{}", mib2_oid, IP_ADDRESS_ENTRY), ADDRESS_COLUMNS);
+    let prefix_oid = format!("{}.{}", mib2_oid, IP_ADDRESS_PREFIX_ENTRY);
+    let mut prefix_table = Table::new(&prefix_oid, PREFIX_COLUMNS);
+    let mut addr_table = Table::new(&format!("{}.{}", mib2_oid, IP_ADDR_ENTRY), ADDR_COLUMNS);
+
+    for address in addresses.iter().filter(|address| interfaces.contains(&address.index)) {
+        let (addr_type, inet) = inet_address(&address.address, address.index);
+        let prefix_type = if is_ipv6(address) { INET_IPV6 } else { INET_IPV4 };
+        let prefix = prefix_of(&address.address, address.prefix_len);
+        let index = address.index;
+
+        let prefix_index = (index, prefix_type, &prefix[..], address.prefix_len as u32);
+        // Points to the first column of the prefix's row in ipAddressPrefixTable
+        let mut pointer = OID::from_parts(&[&prefix_oid, "5"]).as_vec().to_owned();
+        prefix_index.append_to(&mut pointer);
+
+        address_table.row((addr_type, &inet[..]))
+            .set("ipAddressIfIndex",     Value::Integer(index as i64))
+            .set("ipAddressType",        Value::Integer(1)) // unicast
+            .set("ipAddressPrefix",      Value::ObjectIdentifier(OID::from_vec(&pointer)))
+            .set("ipAddressOrigin",      Value::Integer(address_origin(address)))
+            .set("ipAddressStatus",      Value::Integer(address_status(address.flags)))
+            // Hundredths of a second since boot, like sysUpTime
+            .set("ipAddressCreated",     Value::Timeticks(address.created))
+            .set("ipAddressLastChanged", Value::Timeticks(address.updated))
+            .set("ipAddressRowStatus",   Value::Integer(1)) // active
+            .set("ipAddressStorageType", Value::Integer(2)); // volatile
+
+        let autonomous = prefix_origin(address) == PREFIX_ROUTERADV;
+        prefix_table.row(prefix_index)
+            .set("ipAddressPrefixOrigin",               Value::Integer(prefix_origin(address)))
+            .set("ipAddressPrefixOnLinkFlag",           Value::Integer(TRUE))
+            .set("ipAddressPrefixAutonomousFlag",       Value::Integer(if autonomous { TRUE } else { FALSE }))
+            .set("ipAddressPrefixAdvPreferredLifetime", Value::Unsigned32(address.preferred_lifetime))
+            .set("ipAddressPrefixAdvValidLifetime",     Value::Unsigned32(address.valid_lifetime));
+
+        // The legacy table is indexed by address alone, so the first interface wins
+        if !is_ipv6(address) {
+            let (key, netmask) = match (ipv4(&address.address), ipv4(&prefix_of(&[0xff; 4], address.prefix_len))) {
+                (Some(key), Some(netmask)) => (key, netmask),
+                _ => continue
+            };
+            if addr_table.has_row(&key) {
+                continue;
+            }
+            // Least significant bit of the broadcast address, all-ones unless told otherwise
+            let bcast = address.broadcast.as_ref().map_or(1, |brd| brd[3] as i64 & 1);
+            addr_table.row(key)
+                .set("ipAdEntAddr",         Value::IpAddress(key))
+                .set("ipAdEntIfIndex",      Value::Integer(index as i64))
+                .set("ipAdEntNetMask",      Value::IpAddress(netmask))
+                .set("ipAdEntBcastAddr",    Value::Integer(bcast))
+                .set("ipAdEntReasmMaxSize", Value::Integer(REASM_MAX_SIZE));
+        }
+    }
+    address_table.insert_into(values);
+    prefix_table.insert_into(values);
+    addr_table.insert_into(values);
+}
+
+/**
+ * Parse the hex digits of an IPv6 address, or anything else printed as a sequence of bytes.
+ */
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len() / 2).map(|pos| u8::from_str_radix(&hex[2 * pos..2 * pos + 2], 16).ok()).collect()
+}
+
+/**
+ * IPv4 addresses in /proc/net/route are __be32 printed as a host integer.
+ */
+fn parse_proc_ipv4(hex: &str) -> Option<Vec<u8>> {
+    u32::from_str_radix(hex, 16).ok().map(|address| address.to_ne_bytes().to_vec())
+}
+
+/**
+ * Guess who added a route from what /proc tells about it.
+ */
+fn proc_protocol(flags: u32, gateway: &Option<Vec<u8>>) -> u8 {
+    if flags & (RTF_DYNAMIC | RTF_MODIFIED) != 0 {
+        netlink::RTPROT_REDIRECT
+    }
+    else if flags & RTF_ADDRCONF != 0 {
+        netlink::RTPROT_RA
+    }
+    else if gateway.is_none() {
+        netlink::RTPROT_KERNEL
+    }
+    else {
+        netlink::RTPROT_UNSPEC
+    }
+}
+
+/**
+ * The routes of the main table as listed in /proc, for when netlink is not
+ * available. /proc lists only the first next hop of multipath routes.
+ */
+fn proc_routes(host: &Host) -> Vec<Route> {
+    let ifindex = |ifname: &str| u32_from_file(&host.path(
+        PathBuf::from("/sys/class/net").join(ifname).join("ifindex")
+    )).unwrap_or(0);
+    let route = |family: libc::c_int, dst: Vec<u8>, dst_len: u32, gateway: Option<Vec<u8>>, flags: u32, metric: u32, ifname: &str| Route {
+        family:     family as u8,
+        dst,
+        dst_len:    dst_len as u8,
+        table:      netlink::RT_TABLE_MAIN,
+        protocol:   proc_protocol(flags, &gateway),
+        route_type: if flags & RTF_REJECT != 0 { netlink::RTN_UNREACHABLE } else { netlink::RTN_UNICAST },
+        priority:   metric,
+        nexthops:   vec![NextHop { index: ifindex(ifname), gateway }],
+        ..Route::default()
+    };
+    let mut routes = Vec::new();
+
+    if let Ok(file) = File::open(host.path("/proc/net/route")) {
+        for line in BufReader::new(file).lines().skip(1) {
+            let line = match line {
+                Ok(line) => line,
+                Err(_)   => break
+            };
+            // Iface Destination Gateway Flags RefCnt Use Metric Mask MTU Window IRTT
+            let parts = line.split_whitespace().collect::<Vec<&str>>();
+            if parts.len() < 8 {
+                continue;
+            }
+            let flags = u32::from_str_radix(parts[3], 16).unwrap_or(0);
+            let fields = (parse_proc_ipv4(parts[1]), parse_proc_ipv4(parts[2]), parts[6].parse::<u32>(), parse_proc_ipv4(parts[7]));
+            if let (Some(dst), Some(gateway), Ok(metric), Some(mask)) = fields {
+                let dst_len = mask.iter().map(|byte| byte.count_ones()).sum();
+                let gateway = if flags & RTF_GATEWAY != 0 { Some(gateway) } else { None };
+                routes.push(route(libc::AF_INET, dst, dst_len, gateway, flags, metric, parts[0]));
+            }
+        }
+    }
+
+    if let Ok(file) = File::open(host.path("/proc/net/ipv6_route")) {
+        for line in BufReader::new(file).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_)   => break
+            };
+            // Destination, its prefix length, source, its prefix length, next hop, metric, refcnt, use, flags, iface
+            let parts = line.split_whitespace().collect::<Vec<&str>>();
+            if parts.len() < 10 {
+                continue;
+            }
+            let hex = |idx: usize| u32::from_str_radix(parts[idx], 16).ok();
+            let (dst, gateway) = match (parse_hex_bytes(parts[0]), parse_hex_bytes(parts[4])) {
+                (Some(dst), Some(gateway)) => (dst, gateway),
+                _ => continue
+            };
+            let (dst_len, metric, flags) = match (hex(1), hex(5), hex(8)) {
+                (Some(dst_len), Some(metric), Some(flags)) => (dst_len, metric, flags),
+                _ => continue
+            };
+            // ipv6_route lists all tables, but these are what the local table holds.
+            // The unreachable default route with the highest metric is the kernel's null entry.
+            if flags & RTF_LOCAL != 0 || dst[0] == 0xff || (flags & RTF_REJECT != 0 && metric == u32::MAX) {
+                continue;
+            }
+            let gateway = if flags & RTF_GATEWAY != 0 { Some(gateway) } else { None };
+            routes.push(route(libc::AF_INET6, dst, dst_len, gateway, flags, metric, parts[9]));
+        }
+    }
+    routes
+}
+
+/**
+ * IANAipRouteProtocol of the daemon that added a route. ipCidrRouteProto uses the same values.
+ */
+fn route_protocol(protocol: u8) -> i64 {
+    match protocol {
+        netlink::RTPROT_KERNEL                        =>  2, // local
+        netlink::RTPROT_BOOT | netlink::RTPROT_STATIC =>  3, // netmgmt
+        netlink::RTPROT_REDIRECT | netlink::RTPROT_RA =>  4, // icmp
+        netlink::RTPROT_RIP                           =>  8,
+        netlink::RTPROT_ISIS                          =>  9,
+        netlink::RTPROT_OSPF                          => 13,
+        netlink::RTPROT_BGP                           => 14,
+        netlink::RTPROT_EIGRP                         => 16,
+        _                                             =>  1  // other, e.g. dhcp
+    }
+}
+
+fn route_type(route: &Route, nexthop: &NextHop) -> i64 {
+    match route.route_type {
+        netlink::RTN_UNICAST if nexthop.gateway.is_some() => ROUTE_REMOTE,
+        netlink::RTN_UNICAST                              => ROUTE_LOCAL,
+        netlink::RTN_BLACKHOLE                            => ROUTE_BLACKHOLE,
+        netlink::RTN_UNREACHABLE | netlink::RTN_PROHIBIT  => ROUTE_REJECT,
+        _                                                 => ROUTE_OTHER
+    }
+}
+
+/**
+ * Export the IPv4 and IPv6 routes of the main routing table, one row per
+ * next hop of multipath routes. Rows with the same destination and next hop
+ * would have the same index, so only the one with the lowest metric is listed.
+ */
+pub fn get_routes(values: &mut BTreeMap<OID, Value>, host: &Host, base_oid: &str) {
+    let mut routes = match host.route_dump().map(|dump| netlink::parse_routes(&dump)) {
+        Ok(Ok(routes)) => routes,
+        _ => proc_routes(host)
+    };
+    // Leave out what lives in the local table, like broadcast and multicast routes
+    let types = [netlink::RTN_UNICAST, netlink::RTN_BLACKHOLE, netlink::RTN_UNREACHABLE, netlink::RTN_PROHIBIT];
+    routes.retain(|route| route.table == netlink::RT_TABLE_MAIN && types.contains(&route.route_type));
+    routes.sort_by_key(|route| route.priority);
+
+    let mut inet_table = Table::new(&format!("{}.7.1", base_oid), INET_CIDR_ROUTE_COLUMNS);
+    let mut cidr_table = Table::new(&format!("{}.4.1", base_oid), IP_CIDR_ROUTE_COLUMNS);
+    let mut inet_count = 0;
+    let mut cidr_count = 0;
+    // zeroDotZero, sunt does not tell routes apart by policy
+    let policy = OID::from_parts(&["0.0"]);
+
+    for route in &routes {
+        let metric = route.priority.min(i32::MAX as u32) as i64;
+        for nexthop in &route.nexthops {
+            let (dest_type, dest) = inet_address(&route.dst, nexthop.index);
+            let (next_hop_type, next_hop) = match nexthop.gateway {
+                Some(ref gateway) => inet_address(gateway, nexthop.index),
+                None              => (INET_UNKNOWN, vec![])
+            };
+            let index = (dest_type, &dest[..], route.dst_len as u32, policy.clone(), next_hop_type, &next_hop[..]);
+            if inet_table.has_row(&index) {
+                continue;
+            }
+            inet_count += 1;
+            inet_table.row(index)
+                .set("inetCidrRouteIfIndex",   Value::Integer(nexthop.index as i64))
+                .set("inetCidrRouteType",      Value::Integer(route_type(route, nexthop)))
+                .set("inetCidrRouteProto",     Value::Integer(route_protocol(route.protocol)))
+                .set("inetCidrRouteNextHopAS", Value::Unsigned32(0))
+                .set("inetCidrRouteMetric1",   Value::Integer(metric))
+                .set("inetCidrRouteMetric2",   Value::Integer(METRIC_UNUSED))
+                .set("inetCidrRouteMetric3",   Value::Integer(METRIC_UNUSED))
+                .set("inetCidrRouteMetric4",   Value::Integer(METRIC_UNUSED))
+                .set("inetCidrRouteMetric5",   Value::Integer(METRIC_UNUSED))
+                .set("inetCidrRouteStatus",    Value::Integer(1)); // active
+
+            if dest_type != INET_IPV4 {
+                continue;
+            }
+            let (dest, mask) = match (ipv4(&route.dst), ipv4(&prefix_of(&[0xff; 4], route.dst_len))) {
+                (Some(dest), Some(mask)) => (dest, mask),
+                _ => continue
+            };
+            let next_hop = match nexthop.gateway {
+                Some(ref gateway) => match ipv4(gateway) {
+                    Some(gateway) => gateway,
+                    None          => continue
+                },
+                None              => [0; 4]
+            };
+            let index = (dest, mask, route.tos as u32, next_hop);
+            if cidr_table.has_row(&index) {
+                continue;
+            }
+            let cidr_type = match route_type(route, nexthop) {
+                ROUTE_BLACKHOLE => ROUTE_REJECT,
+                other           => other
+            };
+            cidr_count += 1;
+            cidr_table.row(index)
+                .set("ipCidrRouteDest",      Value::IpAddress(dest))
+                .set("ipCidrRouteMask",      Value::IpAddress(mask))
+                .set("ipCidrRouteTos",       Value::Integer(route.tos as i64))
+                .set("ipCidrRouteNextHop",   Value::IpAddress(next_hop))
+                .set("ipCidrRouteIfIndex",   Value::Integer(nexthop.index as i64))
+                .set("ipCidrRouteType",      Value::Integer(cidr_type))
+                .set("ipCidrRouteProto",     Value::Integer(route_protocol(route.protocol)))
+                .set("ipCidrRouteInfo",      Value::ObjectIdentifier(policy.clone()))
+                .set("ipCidrRouteNextHopAS", Value::Integer(0))
+                .set("ipCidrRouteMetric1",   Value::Integer(metric))
+                .set("ipCidrRouteMetric2",   Value::Integer(METRIC_UNUSED))
+                .set("ipCidrRouteMetric3",   Value::Integer(METRIC_UNUSED))
+                .set("ipCidrRouteMetric4",   Value::Integer(METRIC_UNUSED))
+                .set("ipCidrRouteMetric5",   Value::Integer(METRIC_UNUSED))
+                .set("ipCidrRouteStatus",    Value::Integer(1)); // active
+        }
+    }
+    values.insert(OID::from_parts(&[base_oid, "6.0"]), Value::Unsigned32(inet_count));
+    values.insert(OID::from_parts(&[base_oid, "3.0"]), Value::Unsigned32(cidr_count));
+    inet_table.insert_into(values);
+    cidr_table.insert_into(values);
+}
+
+/**
+ * The ARP cache as listed in /proc/net/arp, for when netlink is not available.
+ * /proc only tells whether an entry is complete, not whether it is reachable.
+ */
+fn proc_neighbors(host: &Host) -> Vec<Neighbor> {
+    let mut neighbors = Vec::new();
+    let file = match File::open(host.path("/proc/net/arp")) {
+        Ok(file) => file,
+        Err(_)   => return neighbors
+    };
+    for line in BufReader::new(file).lines().skip(1) {
+        let line = match line {
+            Ok(line) => line,
+            Err(_)   => break
+        };
+        // IP address, HW type, Flags, HW address, Mask, Device
+        let parts = line.split_whitespace().collect::<Vec<&str>>();
+        if parts.len() < 6 {
+            continue;
+        }
+        let (address, flags) = match (parts[0].parse::<Ipv4Addr>(), u32::from_str_radix(parts[2].trim_start_matches("0x"), 16)) {
+            (Ok(address), Ok(flags)) => (address, flags),
+            _ => continue
+        };
+        let state =
+            if flags & ATF_PERM != 0 { netlink::NUD_PERMANENT }
+            else if flags & ATF_COM != 0 { 0 }
+            else { netlink::NUD_INCOMPLETE };
+        neighbors.push(Neighbor {
+            family:  libc::AF_INET as u8,
+            index:   u32_from_file(&host.path(PathBuf::from("/sys/class/net").join(parts[5]).join("ifindex"))).unwrap_or(0),
+            state,
+            address: address.octets().to_vec(),
+            lladdr:  if flags & ATF_COM != 0 { Some(parse_phys_address(parts[3])) } else { None },
+            updated: None,
+        });
+    }
+    neighbors
+}
+
+/**
+ * ipNetToPhysicalState of a NUD_* state. The kernel does not verify static
+ * entries, and /proc does not tell the state of dynamic ones.
+ */
+fn neighbor_state(state: u16) -> i64 {
+    match state {
+        netlink::NUD_REACHABLE  => 1,
+        netlink::NUD_STALE      => 2,
+        netlink::NUD_DELAY      => 3,
+        netlink::NUD_PROBE      => 4,
+        netlink::NUD_FAILED     => 5, // invalid
+        netlink::NUD_INCOMPLETE => 7,
+        _                       => 6  // unknown
+    }
+}
+
+/**
+ * The OIDs of the tables get_neighbors fills.
+ */
+pub fn neighbor_subtrees(mib2_oid: &str) -> Vec<String> {
+    [IP_NET_TO_PHYSICAL_ENTRY, IP_NET_TO_MEDIA_ENTRY].iter()
+        .map(|entry| format!("{}.{}", mib2_oid, entry))
+        .collect()
+}
+
+/**
+ * Export the ARP and NDP caches of the interfaces in the ifTable, falling
+ * back to /proc/net/arp for IPv4 without netlink. Entries of addresses that
+ * need no resolution, like multicast ones, are left out as `ip neigh` does.
+ */
+pub fn get_neighbors(values: &mut BTreeMap<OID, Value>, host: &Host, interfaces: &IncludedInterfaces, mib2_oid: &str) {
+    let neighbors = match host.neigh_dump().map(|dump| netlink::parse_neighbors(&dump)) {
+        Ok(Ok(neighbors)) => neighbors,
+        _ => proc_neighbors(host)
+    };
+    let interfaces = interfaces.get(host);
+    let now = mib_sys::uptime(host).unwrap_or(0);
+
+    let mut physical_table = Table::new(&format!("{}.{}", mib2_oid, IP_NET_TO_PHYSICAL_ENTRY), NET_TO_PHYSICAL_COLUMNS);
+    let mut media_table = Table::new(&format!("{}.{}", mib2_oid, IP_NET_TO_MEDIA_ENTRY), NET_TO_MEDIA_COLUMNS);
+    let families = [libc::AF_INET as u8, libc::AF_INET6 as u8];
+    let neighbors = neighbors.iter()
+        .filter(|neighbor| families.contains(&neighbor.family) && interfaces.contains(&neighbor.index))
+        .filter(|neighbor| neighbor.state & netlink::NUD_NOARP == 0);
+
+    for neighbor in neighbors {
+        // Link-local addresses need no zone, the ifIndex is part of the index already
+        let addr_type = if neighbor.family == libc::AF_INET6 as u8 { INET_IPV6 } else { INET_IPV4 };
+        let phys_address = neighbor.lladdr.clone().unwrap_or_default();
+        let neighbor_type = if neighbor.state & netlink::NUD_PERMANENT != 0 { NEIGHBOR_STATIC } else { NEIGHBOR_DYNAMIC };
+
+        let mut row = physical_table.row((neighbor.index, addr_type, &neighbor.address[..]));
+        row.set("ipNetToPhysicalPhysAddress", Value::Octets(phys_address.clone()))
+            .set("ipNetToPhysicalType",       Value::Integer(neighbor_type))
+            .set("ipNetToPhysicalState",      Value::Integer(neighbor_state(neighbor.state)))
+            .set("ipNetToPhysicalRowStatus",  Value::Integer(1)); // active
+        if let Some(updated) = neighbor.updated {
+            // updated is how long ago, in hundredths of a second like the clock, which wraps around
+            row.set("ipNetToPhysicalLastUpdated", Value::Timeticks(now.wrapping_sub(updated)));
+        }
+
+        // The legacy table has no state, so it only lists resolved IPv4 neighbors
+        if let (INET_IPV4, Some(_), Some(address)) = (addr_type, &neighbor.lladdr, ipv4(&neighbor.address)) {
+            media_table.row((neighbor.index, address))
+                .set("ipNetToMediaIfIndex",     Value::Integer(neighbor.index as i64))
+                .set("ipNetToMediaPhysAddress", Value::Octets(phys_address))
+                .set("ipNetToMediaNetAddress",  Value::IpAddress(address))
+                .set("ipNetToMediaType",        Value::Integer(neighbor_type));
+        }
+    }
+    physical_table.insert_into(values);
+    media_table.insert_into(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use netlink::INFINITY_LIFE_TIME;
+
+    fn address(family: libc::c_int, bytes: &[u8], flags: u32) -> Address {
+        Address {
+            family:             family as u8,
+            index:              7,
+            address:            bytes.to_vec(),
+            prefix_len:         64,
+            broadcast:          None,
+            flags,
+            scope:              0,
+            preferred_lifetime: INFINITY_LIFE_TIME,
+            valid_lifetime:     INFINITY_LIFE_TIME,
+            created:            0,
+            updated:            0,
+        }
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(prefix_of(&[192, 168, 20, 5], 24), vec![192, 168, 20, 0]);
+        assert_eq!(prefix_of(&[10, 77, 0, 2], 32), vec![10, 77, 0, 2]);
+        assert_eq!(prefix_of(&[255, 255, 255, 255], 20), vec![255, 255, 240, 0]);
+        assert_eq!(prefix_of(&[10, 0, 0, 1], 0), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn origins_and_zones() {
+        let mut link_local = [0u8; 16];
+        link_local[0] = 0xfe;
+        link_local[1] = 0x80;
+        link_local[15] = 1;
+        let link_local = address(libc::AF_INET6, &link_local, netlink::IFA_F_PERMANENT);
+        assert_eq!(address_origin(&link_local), ORIGIN_LINKLAYER);
+        assert_eq!(prefix_origin(&link_local), PREFIX_WELLKNOWN);
+        let (addr_type, inet) = inet_address(&link_local.address, link_local.index);
+        assert_eq!(addr_type, INET_IPV6Z);
+        assert_eq!(&inet[16..], &[0, 0, 0, 7]);
+
+        let mut global = [0u8; 16];
+        global[0] = 0x20;
+        global[1] = 0x01;
+        assert_eq!(address_origin(&address(libc::AF_INET6, &global, netlink::IFA_F_TEMPORARY)), ORIGIN_RANDOM);
+        assert_eq!(address_origin(&address(libc::AF_INET6, &global, 0)), ORIGIN_LINKLAYER);
+        // IFA_F_SECONDARY, not IFA_F_TEMPORARY
+        assert_eq!(address_origin(&address(libc::AF_INET, &[10, 0, 0, 5], 0x01)), ORIGIN_DHCP);
+        assert_eq!(address_origin(&address(libc::AF_INET, &[10, 0, 0, 5], 0x81)), ORIGIN_MANUAL);
+    }
+
+    #[test]
+    fn statuses() {
+        assert_eq!(address_status(netlink::IFA_F_PERMANENT), STATUS_PREFERRED);
+        assert_eq!(address_status(netlink::IFA_F_DEPRECATED), STATUS_DEPRECATED);
+        assert_eq!(address_status(netlink::IFA_F_TENTATIVE), STATUS_TENTATIVE);
+        assert_eq!(address_status(netlink::IFA_F_TENTATIVE | netlink::IFA_F_OPTIMISTIC), STATUS_OPTIMISTIC);
+        assert_eq!(address_status(netlink::IFA_F_TENTATIVE | netlink::IFA_F_DADFAILED), STATUS_DUPLICATE);
+    }
+}
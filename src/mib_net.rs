@@ -1,181 +1,902 @@
-use std::collections::{BTreeMap};
-use value::{Value,str_from_file,u32_from_file};
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use value::{Value,str_from_file,u32_from_file,u64_from_file};
 use oid::OID;
+use table::{Table,TableDef,Column};
+use table::Syntax::*;
+use std::fs;
 use std::fs::File;
 use std::io::{BufReader,BufRead};
 use std::path::PathBuf;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use host::Host;
+use netlink;
+use netlink::{Link,LinkStats};
+use ethtool;
+use mib_sys;
+use filter::{Filter,Rule,Action};
 
-#[derive(Debug)]
+/// What interface filter rules can match on
+pub const INTERFACE_ATTRIBUTES: &[&str] = &["name", "class"];
+
+/**
+ * No VM interfaces, VPN tunnels and loopback.
+ */
+pub fn interface_defaults() -> Vec<Rule> {
+    vec![
+        Rule::globs(Action::Exclude, &[("class", "virtual")]),
+    ]
+}
+
+const IF_COLUMNS: &[Column] = &[
+    Column::new( 1,      "ifIndex",           Integer),
+    Column::new( 2,      "ifDescr",           OctetString),
+    Column::new( 3,      "ifType",            Integer),
+    Column::new( 4,      "ifMtu",             Integer),
+    Column::new( 5,      "ifSpeed",           Gauge32),
+    Column::new( 6,      "ifPhysAddress",     OctetString),
+    Column::new( 7,      "ifAdminStatus",     Integer),
+    Column::new( 8,      "ifOperStatus",      Integer),
+    Column::new( 9,      "ifLastChange",      TimeTicks),
+    Column::new(10,      "ifInOctets",        Counter32),
+    Column::new(11,      "ifInUcastPkts",     Counter32),
+    Column::new(12,      "ifInNUcastPkts",    Counter32),
+    Column::new(13,      "ifInDiscards",      Counter32),
+    Column::new(14,      "ifInErrors",        Counter32),
+    Column::new(15,      "ifInUnknownProtos", Counter32),
+    Column::new(16,      "ifOutOctets",       Counter32),
+    Column::new(17,      "ifOutUcastPkts",    Counter32),
+    Column::optional(18, "ifOutNUcastPkts",   Counter32),
+    Column::new(19,      "ifOutDiscards",     Counter32),
+    Column::new(20,      "ifOutErrors",       Counter32),
+    // ifOutQLen not supported
+    // ifSpecific not supported
+];
+
+// IF-MIB::ifXTable
+const IF_X_COLUMNS: &[Column] = &[
+    Column::new( 1,      "ifName",                     OctetString),
+    Column::new( 2,      "ifInMulticastPkts",          Counter32),
+    Column::optional( 3, "ifInBroadcastPkts",          Counter32),
+    Column::optional( 4, "ifOutMulticastPkts",         Counter32),
+    Column::optional( 5, "ifOutBroadcastPkts",         Counter32),
+    Column::new( 6,      "ifHCInOctets",               Counter64),
+    Column::new( 7,      "ifHCInUcastPkts",            Counter64),
+    Column::new( 8,      "ifHCInMulticastPkts",        Counter64),
+    Column::optional( 9, "ifHCInBroadcastPkts",        Counter64),
+    Column::new(10,      "ifHCOutOctets",              Counter64),
+    Column::new(11,      "ifHCOutUcastPkts",           Counter64),
+    Column::optional(12, "ifHCOutMulticastPkts",       Counter64),
+    Column::optional(13, "ifHCOutBroadcastPkts",       Counter64),
+    Column::new(14,      "ifLinkUpDownTrapEnable",     Integer),
+    Column::new(15,      "ifHighSpeed",                Gauge32),
+    Column::new(16,      "ifPromiscuousMode",          Integer),
+    Column::new(17,      "ifConnectorPresent",         Integer),
+    Column::new(18,      "ifAlias",                    OctetString),
+    Column::new(19,      "ifCounterDiscontinuityTime", TimeTicks),
+];
+
+// IF-MIB::ifStackTable, indexed by ifStackHigherLayer and ifStackLowerLayer
+const IF_STACK_COLUMNS: &[Column] = &[
+    Column::new(3, "ifStackStatus", Integer),
+];
+
+// IF-INVERTED-STACK-MIB::ifInvStackTable, indexed the other way around
+const IF_INV_STACK_COLUMNS: &[Column] = &[
+    Column::new(1, "ifInvStackStatus", Integer),
+];
+
+/// RowStatus active
+const ROW_ACTIVE: i64 = 1;
+
+const LINK_SETTINGS_COLUMNS: &[Column] = &[
+    Column::optional(1, "suntLinkSpeed",           Gauge32).describe("Speed in Mbit/s. Missing while it is unknown, e.g. because the link is down."),
+    Column::new(     2, "suntLinkDuplex",          Integer).describe("unknown(1), halfDuplex(2) or fullDuplex(3), like dot3StatsDuplexStatus."),
+    Column::new(     3, "suntLinkAutoneg",         Integer).describe("true(1) if the speed and duplex are autonegotiated, false(2) otherwise."),
+    Column::new(     4, "suntLinkPort",            OctetString).describe("Connector as ethtool calls it: tp, aui, bnc, mii, fibre, da, none or other."),
+    Column::new(     5, "suntLinkSupportedModes",  OctetString)
+        .describe("Link modes the NIC supports, e.g. 1000baseT/Full, separated by spaces."),
+    Column::new(     6, "suntLinkAdvertisedModes", OctetString)
+        .describe("Link modes the NIC offers to its link partner during autonegotiation, separated by spaces."),
+];
+
+const DRIVER_STATS_COLUMNS: &[Column] = &[
+    Column::new(1, "suntDriverStatName",  OctetString).describe("Name of the counter as the driver calls it, e.g. rx_missed_errors."),
+    Column::new(2, "suntDriverStatValue", Counter64).describe("Value of the counter."),
+];
+
+pub const LINK_SETTINGS_TABLE: TableDef = TableDef {
+    name:        "suntLinkSettingsTable",
+    entry_oid:   "1.3.6.1.4.1.2147483647.1.3.1",
+    description: "Speed, duplex and autonegotiation of the interfaces in the ifTable whose driver knows about them, from ethtool.",
+    index:       &["ifIndex"],
+    columns:     LINK_SETTINGS_COLUMNS,
+};
+
+pub const DRIVER_STATS_TABLE: TableDef = TableDef {
+    name:        "suntDriverStatsTable",
+    entry_oid:   "1.3.6.1.4.1.2147483647.1.4.1",
+    description: "Counters that drivers of the interfaces in the ifTable report through ethtool, such as ring buffer drops \
+                  and per-queue packets. Which counters there are differs by driver.",
+    index:       &["ifIndex", "suntDriverStatName"],
+    columns:     DRIVER_STATS_COLUMNS,
+};
+
+// EtherLike-MIB::dot3StatsTable, for Ethernet NICs. Linux does not count
+// collisions by how many attempts they took, nor SQE test or symbol errors.
+const DOT3_STATS_COLUMNS: &[Column] = &[
+    Column::new( 1, "dot3StatsIndex",                     Integer),
+    Column::new( 2, "dot3StatsAlignmentErrors",           Counter32),
+    Column::new( 3, "dot3StatsFCSErrors",                 Counter32),
+    Column::new( 8, "dot3StatsLateCollisions",            Counter32),
+    Column::new( 9, "dot3StatsExcessiveCollisions",       Counter32),
+    Column::new(10, "dot3StatsInternalMacTransmitErrors", Counter32),
+    Column::new(11, "dot3StatsCarrierSenseErrors",        Counter32),
+    Column::new(13, "dot3StatsFrameTooLongs",             Counter32),
+    Column::new(16, "dot3StatsInternalMacReceiveErrors",  Counter32),
+    Column::new(19, "dot3StatsDuplexStatus",              Integer),
+];
+
+// Entries of the standard tables get_interfaces fills, relative to mib-2
+const IF_ENTRY:           &str = "2.2.1";
+const IF_X_ENTRY:         &str = "31.1.1.1";
+const IF_STACK_ENTRY:     &str = "31.1.2.1";
+const DOT3_STATS_ENTRY:   &str = "10.7.2.1";
+const IF_INV_STACK_ENTRY: &str = "77.1.1.1";
+
+// ARPHRD_* from linux/if_arp.h, as found in /sys/class/net/*/type
+const ARPHRD_ETHER:              u16 = 1;
+const ARPHRD_INFINIBAND:         u16 = 32;
+const ARPHRD_PPP:                u16 = 512;
+const ARPHRD_TUNNEL:             u16 = 768;
+const ARPHRD_TUNNEL6:            u16 = 769;
+const ARPHRD_LOOPBACK:           u16 = 772;
+const ARPHRD_SIT:                u16 = 776;
+const ARPHRD_IPGRE:              u16 = 778;
+const ARPHRD_IEEE80211:          u16 = 801;
+const ARPHRD_IEEE80211_PRISM:    u16 = 802;
+const ARPHRD_IEEE80211_RADIOTAP: u16 = 803;
+const ARPHRD_IP6GRE:             u16 = 823;
+const ARPHRD_NONE:               u16 = 65534;
+
+/// Set in /sys/class/net/*/tun_flags for TAP devices
+const IFF_TAP: u32 = 0x0002;
+
+// IF_OPER_* from linux/if.h, the RFC 2863 operational states as netlink reports them
+const IF_OPER_UNKNOWN:        u8 = 0;
+const IF_OPER_NOTPRESENT:     u8 = 1;
+const IF_OPER_DOWN:           u8 = 2;
+const IF_OPER_LOWERLAYERDOWN: u8 = 3;
+const IF_OPER_TESTING:        u8 = 4;
+const IF_OPER_DORMANT:        u8 = 5;
+const IF_OPER_UP:             u8 = 6;
+
+// IFF_* from linux/if.h, as found in /sys/class/net/*/flags
+const IFF_UP:      u32 = 0x0001;
+const IFF_PROMISC: u32 = 0x0100;
+
+/**
+ * What the last refresh saw of an interface, to tell when it changed.
+ */
+struct IfaceState {
+    ifindex:         Option<u32>,
+    oper_status:     i64,
+    carrier_changes: Option<u32>,
+    counters:        Vec<u64>,
+    last_change:     u32,
+    discontinuity:   u32,
+}
+
+/**
+ * ifLastChange and ifCounterDiscontinuityTime of all interfaces.
+ *
+ * The kernel doesn't keep timestamps for either, so the collector compares
+ * each refresh to the previous one. Changes that happened before the first
+ * refresh are reported as 0, like the MIB asks for changes from before the
+ * agent was started.
+ */
+#[derive(Default)]
+pub struct InterfaceHistory {
+    refreshed: bool,
+    ifaces:    HashMap<String, IfaceState>,
+}
+
+impl InterfaceHistory {
+    pub fn new() -> InterfaceHistory {
+        InterfaceHistory { refreshed: false, ifaces: HashMap::new() }
+    }
+
+    /**
+     * Record the current state of an interface, returning its ifLastChange and ifCounterDiscontinuityTime.
+     */
+    fn update(&mut self, ifname: &str, now: u32, current: IfaceState) -> (u32, u32) {
+        let since = if self.refreshed { now } else { 0 };
+        let state = match self.ifaces.remove(ifname) {
+            // Interface has been deleted and created again, or the driver has been reloaded
+            Some(ref previous) if previous.ifindex != current.ifindex => IfaceState { last_change: now, discontinuity: now, ..current },
+            Some(previous) => {
+                let changed = previous.oper_status != current.oper_status || previous.carrier_changes != current.carrier_changes;
+                let reset = previous.counters.iter().zip(current.counters.iter()).any(|(before, after)| after < before);
+                IfaceState {
+                    last_change:   if changed { now } else { previous.last_change },
+                    discontinuity: if reset { now } else { previous.discontinuity },
+                    ..current
+                }
+            }
+            None => IfaceState { last_change: since, discontinuity: since, ..current }
+        };
+        let times = (state.last_change, state.discontinuity);
+        self.ifaces.insert(String::from(ifname), state);
+        times
+    }
+
+    /**
+     * Forget interfaces that are gone after a refresh.
+     */
+    fn refreshed(&mut self, seen: &[String]) {
+        self.ifaces.retain(|ifname, _| seen.contains(ifname));
+        self.refreshed = true;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum IfaceClass {
-    Physical,
+    Ethernet,
+    Wireless,
+    Infiniband,
+    Loopback,
     Bonding,
-    VLAN,
+    Vlan,
     Bridge,
-    Virtual
+    Tunnel,
+    WireGuard,
+    Tun,
+    Tap,
+    Veth,
+    Macvlan,
+    Ppp,
+    Other,
+}
+
+impl IfaceClass {
+    /**
+     * What filter rules call this class, along with physical or virtual if it's one of those.
+     */
+    fn names(&self) -> Vec<&'static str> {
+        match *self {
+            IfaceClass::Ethernet   => vec!["ethernet", "physical"],
+            IfaceClass::Wireless   => vec!["wireless", "physical"],
+            IfaceClass::Infiniband => vec!["infiniband", "physical"],
+            IfaceClass::Loopback   => vec!["loopback", "virtual"],
+            IfaceClass::Bonding    => vec!["bonding"],
+            IfaceClass::Vlan       => vec!["vlan"],
+            IfaceClass::Bridge     => vec!["bridge"],
+            IfaceClass::Tunnel     => vec!["tunnel", "virtual"],
+            IfaceClass::WireGuard  => vec!["wireguard", "virtual"],
+            IfaceClass::Tun        => vec!["tun", "virtual"],
+            IfaceClass::Tap        => vec!["tap", "virtual"],
+            IfaceClass::Veth       => vec!["veth", "virtual"],
+            IfaceClass::Macvlan    => vec!["macvlan", "virtual"],
+            IfaceClass::Ppp        => vec!["ppp", "virtual"],
+            IfaceClass::Other      => vec!["other", "virtual"],
+        }
+    }
+
+    /**
+     * IANAifType of the class.
+     */
+    fn if_type(&self) -> i64 {
+        match *self {
+            IfaceClass::Ethernet   => 6,    // ethernetCsmacd
+            IfaceClass::Wireless   => 71,   // ieee80211
+            IfaceClass::Infiniband => 199,  // infiniband
+            IfaceClass::Loopback   => 24,   // softwareLoopback
+            IfaceClass::Bonding    => 161,  // ieee8023adLag
+            IfaceClass::Vlan       => 135,  // l2vlan
+            IfaceClass::Bridge     => 209,  // bridge
+            IfaceClass::Tunnel     => 131,  // tunnel
+            IfaceClass::WireGuard  => 131,  // tunnel
+            IfaceClass::Tun        => 131,  // tunnel
+            IfaceClass::Tap        => 6,    // ethernetCsmacd, as far as the host can tell
+            IfaceClass::Veth       => 6,    // ethernetCsmacd
+            IfaceClass::Macvlan    => 6,    // ethernetCsmacd
+            IfaceClass::Ppp        => 23,   // ppp
+            IfaceClass::Other      => 1,    // other
+        }
+    }
 }
 
 /**
- * Given a device name such as virbr0, figure out what kind of interface that is.
+ * Parse a MAC address like 52:54:00:12:34:56. Interfaces without one have an empty file.
  */
-fn classify_interface(ifname: &String) -> IfaceClass {
-    let sys = PathBuf::from("/sys/class/net").join(ifname);
-    if sys.join("device").exists() {
-        return IfaceClass::Physical;
+pub fn parse_phys_address(address: &str) -> Vec<u8> {
+    address.split(':')
+        .filter_map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect()
+}
+
+/**
+ * Given an interface such as virbr0, figure out what kind of interface that is:
+ * First by its ARP hardware type, then by the kind of driver and finally by
+ * the files drivers leave in sysfs.
+ */
+fn classify_interface(host: &Host, link: &Link, vlans: &[(String, String)]) -> IfaceClass {
+    let sys = PathBuf::from("/sys/class/net").join(&link.name);
+    let exists = |name: &str| host.path(sys.join(name)).exists();
+
+    match link.if_type {
+        ARPHRD_LOOPBACK   => return IfaceClass::Loopback,
+        ARPHRD_INFINIBAND => return IfaceClass::Infiniband,
+        ARPHRD_PPP        => return IfaceClass::Ppp,
+        ARPHRD_TUNNEL | ARPHRD_TUNNEL6 | ARPHRD_SIT | ARPHRD_IPGRE | ARPHRD_IP6GRE => return IfaceClass::Tunnel,
+        ARPHRD_IEEE80211 | ARPHRD_IEEE80211_PRISM | ARPHRD_IEEE80211_RADIOTAP => return IfaceClass::Wireless,
+        ARPHRD_ETHER | ARPHRD_NONE => (),
+        _ => return IfaceClass::Other
     }
-    if sys.join("bonding").exists() {
+
+    // Netlink tells the kind of driver, sysfs the DEVTYPE, which agree for the common ones
+    match link.kind.as_deref() {
+        Some("bond")      => return IfaceClass::Bonding,
+        Some("vlan")      => return IfaceClass::Vlan,
+        Some("bridge")    => return IfaceClass::Bridge,
+        Some("wlan")      => return IfaceClass::Wireless,
+        Some("wireguard") => return IfaceClass::WireGuard,
+        Some("veth")      => return IfaceClass::Veth,
+        Some("tun") if link.if_type == ARPHRD_ETHER => return IfaceClass::Tap,
+        Some("tun")       => return IfaceClass::Tun,
+        Some("macvlan") | Some("macvtap") | Some("ipvlan") => return IfaceClass::Macvlan,
+        _ => ()
+    }
+
+    // Older kernels and some drivers do not set DEVTYPE
+    if exists("bonding") {
         return IfaceClass::Bonding;
     }
-    if sys.join("bridge").exists() {
+    if exists("bridge") {
         return IfaceClass::Bridge;
     }
-    if sys.join("master").exists() {
-        if let Ok(vconfig) = File::open("/proc/net/vlan/config") {
-            for vcline in BufReader::new(vconfig).lines().skip(2) {
-                if vcline.unwrap().split_whitespace().nth(0).unwrap() == ifname {
-                    return IfaceClass::VLAN;
-                }
-            }
-        }
+    if let Some(flags) = str_from_file(&host.path(sys.join("tun_flags"))) {
+        return match u32::from_str_radix(flags.trim_start_matches("0x"), 16) {
+            Ok(flags) if flags & IFF_TAP != 0 => IfaceClass::Tap,
+            _ => IfaceClass::Tun
+        };
+    }
+    if exists("wireless") || exists("phy80211") {
+        return IfaceClass::Wireless;
+    }
+    if vlans.iter().any(|(vlan, _)| *vlan == link.name) {
+        return IfaceClass::Vlan;
+    }
+    if link.if_type == ARPHRD_NONE {
+        // Layer 3 only and not a TUN device, so some kind of tunnel
+        return IfaceClass::Tunnel;
+    }
+    if exists("device") {
+        return IfaceClass::Ethernet;
     }
-    return IfaceClass::Virtual;
+
+    // Virtual ethernet devices that are linked to another interface
+    if link.link.is_some() {
+        let has_lower = fs::read_dir(host.dir(&sys))
+            .map(|entries| entries
+                .filter_map(|entry| entry.ok())
+                .any(|entry| entry.file_name().to_string_lossy().starts_with("lower_")))
+            .unwrap_or(false);
+        // macvlans sit on top of the interface they're linked to, veths are linked to their peer
+        return if has_lower { IfaceClass::Macvlan } else { IfaceClass::Veth };
+    }
+    IfaceClass::Other
 }
 
-pub fn get_interfaces(values: &mut BTreeMap<OID, Value>, if_table_oid: &str, extended_oid: &str) {
-    // ifTable
+/**
+ * What sysfs knows about an interface, in the shape of an RTM_NEWLINK message.
+ * The kind is the DEVTYPE from uevent, the stats are left to the caller.
+ */
+fn sysfs_link(host: &Host, ifname: &str) -> Link {
+    let sys = PathBuf::from("/sys/class/net").join(ifname);
+    let read = |name: &str| str_from_file(&host.path(sys.join(name)));
+    let number = |name: &str| u32_from_file(&host.path(sys.join(name)));
 
-    if let Ok(netdevstats) = File::open("/proc/net/dev") {
-        let mut iface_idx = 1;
+    let index = number("ifindex").unwrap_or(0);
+    let iflink = number("iflink");
+    Link {
+        index,
+        name:            String::from(ifname),
+        if_type:         read("type").and_then(|if_type| if_type.parse().ok()).unwrap_or(ARPHRD_ETHER),
+        flags:           read("flags")
+            .and_then(|flags| u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok())
+            .unwrap_or(0),
+        mtu:             number("mtu"),
+        operstate:       read("operstate").map(|operstate| match operstate.as_str() {
+            "up"             => IF_OPER_UP,
+            "down"           => IF_OPER_DOWN,
+            "notpresent"     => IF_OPER_NOTPRESENT,
+            "lowerlayerdown" => IF_OPER_LOWERLAYERDOWN,
+            "testing"        => IF_OPER_TESTING,
+            "dormant"        => IF_OPER_DORMANT,
+            _                => IF_OPER_UNKNOWN,
+        }),
+        address:         read("address").map(|address| parse_phys_address(&address)),
+        // Like netlink, only report links to other interfaces
+        link:            iflink.filter(|&iflink| iflink != index),
+        master:          None,
+        alias:           read("ifalias"),
+        carrier_changes: number("carrier_changes"),
+        kind:            fs::read_to_string(host.path(sys.join("uevent"))).ok()
+            .and_then(|uevent| uevent.lines()
+                .find(|line| line.starts_with("DEVTYPE="))
+                .map(|line| String::from(&line["DEVTYPE=".len()..]))),
+        stats:           None,
+    }
+}
 
+/**
+ * All interfaces in /proc/net/dev, for when netlink is not available.
+ */
+fn sysfs_links(host: &Host) -> Vec<Link> {
+    let mut links = Vec::new();
+    if let Ok(netdevstats) = File::open(host.path("/proc/net/dev")) {
         for line in BufReader::new(netdevstats).lines().skip(2) {
-            let line = line.unwrap();
+            let line = match line {
+                Ok(line) => line,
+                Err(_)   => break
+            };
             let parts = line.split_whitespace().collect::<Vec<&str>>();
             // Parts:
             //    Interface: (yes, there's a colon in there)
             // Rx bytes packets errs drop fifo frame compressed multicast
             // Tx bytes packets errs drop fifo colls carrier compressed
+            if parts.len() < 17 {
+                continue;
+            }
+            let counters = match parts[1..17].iter().map(|part| part.parse::<u64>()).collect::<Result<Vec<u64>, _>>() {
+                Ok(counters) => counters,
+                Err(_)       => continue
+            };
+
+            let ifname = parts[0].trim_end_matches(':');
+            let counter = |idx: usize| counters[idx - 1];
+            // /proc/net/dev lumps the others together in "frame" and "carrier"
+            let statistic = |name: &str| u64_from_file(&host.path(
+                PathBuf::from("/sys/class/net").join(ifname).join("statistics").join(name)
+            )).unwrap_or(0);
+            let mut link = sysfs_link(host, ifname);
+            link.stats = Some(LinkStats {
+                rx_packets:   counter(2),
+                tx_packets:   counter(10),
+                rx_bytes:     counter(1),
+                tx_bytes:     counter(9),
+                rx_errors:    counter(3),
+                tx_errors:    counter(11),
+                rx_dropped:   counter(4),
+                tx_dropped:   counter(12),
+                multicast:    counter(8),
+                rx_nohandler: statistic("rx_nohandler"),
+
+                collisions:        counter(14),
+                rx_length_errors:  statistic("rx_length_errors"),
+                rx_over_errors:    statistic("rx_over_errors"),
+                rx_crc_errors:     statistic("rx_crc_errors"),
+                rx_frame_errors:   statistic("rx_frame_errors"),
+                rx_fifo_errors:    counter(5),
+                tx_aborted_errors: statistic("tx_aborted_errors"),
+                tx_carrier_errors: statistic("tx_carrier_errors"),
+                tx_fifo_errors:    counter(13),
+                tx_window_errors:  statistic("tx_window_errors"),
+            });
+            links.push(link);
+        }
+    }
+    links
+}
+
+/**
+ * VLAN interfaces and the interfaces they sit on, from /proc/net/vlan/config.
+ */
+fn vlan_devices(host: &Host) -> Vec<(String, String)> {
+    let mut vlans = Vec::new();
+    if let Ok(vconfig) = File::open(host.path("/proc/net/vlan/config")) {
+        for vcline in BufReader::new(vconfig).lines().skip(2) {
+            // bond0.20       | 20  | bond0
+            let vcline = match vcline {
+                Ok(vcline) => vcline,
+                Err(_)     => break
+            };
+            let parts = vcline.split('|').map(str::trim).collect::<Vec<&str>>();
+            if parts.len() == 3 {
+                vlans.push((String::from(parts[0]), String::from(parts[2])));
+            }
+        }
+    }
+    vlans
+}
+
+/**
+ * (higher, lower) pairs of interfaces stacked on or below the given one.
+ *
+ * Recent kernels link both directions with lower_* and upper_*, older ones
+ * only have brif/ for bridges and /proc/net/vlan/config for VLANs.
+ */
+fn stack_links(host: &Host, ifname: &str, vlans: &[(String, String)]) -> Vec<(String, String)> {
+    let sys = PathBuf::from("/sys/class/net").join(ifname);
+    let mut links = Vec::new();
+    let entries = |dir: PathBuf| fs::read_dir(host.dir(dir))
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<String>>())
+        .unwrap_or_default();
+
+    for entry in entries(sys.clone()) {
+        if let Some(lower) = entry.strip_prefix("lower_") {
+            links.push((String::from(ifname), String::from(lower)));
+        }
+        else if let Some(upper) = entry.strip_prefix("upper_") {
+            links.push((String::from(upper), String::from(ifname)));
+        }
+    }
+    for port in entries(sys.join("brif")) {
+        links.push((String::from(ifname), port));
+    }
+    for (vlan, device) in vlans {
+        if vlan == ifname {
+            links.push((vlan.to_owned(), device.to_owned()));
+        }
+    }
+    links
+}
+
+/**
+ * All interfaces from a single netlink dump, or from /proc/net/dev and sysfs
+ * if netlink is not available. Also tells which of the two it was.
+ */
+fn get_links(host: &Host) -> (Vec<Link>, bool) {
+    match host.link_dump().map(|dump| netlink::parse_links(&dump)) {
+        Ok(Ok(links)) => (links, true),
+        _ => (sysfs_links(host), false)
+    }
+}
 
-            let ifname = String::from(parts[0].trim_end_matches(":"));
-            let ifsys = PathBuf::from("/sys/class/net").join(&ifname);
-            let ifclass = classify_interface(&ifname);
+/**
+ * Whether the interface passes the filter and thus is in the ifTable.
+ */
+fn includes(filter: &Filter, ifname: &str, ifclass: IfaceClass) -> bool {
+    filter.includes(|attribute| match attribute {
+        "name"  => vec![ifname.to_owned()],
+        "class" => ifclass.names().into_iter().map(String::from).collect(),
+        _       => vec![]
+    })
+}
+
+/// When the ifIndexes were last looked up, and what they were
+type IndexCache = Arc<Mutex<Option<(Instant, BTreeSet<u32>)>>>;
+
+/**
+ * ifIndexes of the interfaces in the ifTable, for tables that refer to them.
+ *
+ * Shared by the collectors of these tables, so that the links are only
+ * dumped and classified once per max_age rather than once per collector.
+ */
+#[derive(Clone)]
+pub struct IncludedInterfaces {
+    filter:  Filter,
+    max_age: Duration,
+    cached:  IndexCache,
+}
+
+impl IncludedInterfaces {
+    pub fn new(filter: Filter, max_age: Duration) -> IncludedInterfaces {
+        IncludedInterfaces { filter, max_age, cached: Arc::new(Mutex::new(None)) }
+    }
 
-            match ifclass {
-                IfaceClass::Virtual => continue,
-                _ => ()
+    pub fn get(&self, host: &Host) -> BTreeSet<u32> {
+        let mut cached = self.cached.lock().unwrap();
+        match *cached {
+            Some((updated, ref indexes)) if updated.elapsed() < self.max_age => return indexes.clone(),
+            _ => ()
+        }
+        let vlans = vlan_devices(host);
+        let indexes = get_links(host).0.iter()
+            .filter(|link| includes(&self.filter, &link.name, classify_interface(host, link, &vlans)))
+            .map(|link| link.index)
+            .collect::<BTreeSet<u32>>();
+        *cached = Some((Instant::now(), indexes.clone()));
+        indexes
+    }
+}
+
+/**
+ * The OIDs of the tables get_interfaces fills.
+ */
+pub fn interface_subtrees(mib2_oid: &str) -> Vec<String> {
+    let mut subtrees = [IF_ENTRY, IF_X_ENTRY, IF_STACK_ENTRY, DOT3_STATS_ENTRY, IF_INV_STACK_ENTRY].iter()
+        .map(|entry| format!("{}.{}", mib2_oid, entry))
+        .collect::<Vec<String>>();
+    subtrees.extend([LINK_SETTINGS_TABLE.entry_oid, DRIVER_STATS_TABLE.entry_oid].iter().map(|oid| oid.to_string()));
+    subtrees
+}
+
+/**
+ * Fill the ifTable, ifXTable, ifStackTable, ifInvStackTable and dot3StatsTable,
+ * which all live below mib-2 (1.3.6.1.2.1), and sunt's own interface tables.
+ */
+pub fn get_interfaces(values: &mut BTreeMap<OID, Value>, host: &Host, filter: &Filter, history: &mut InterfaceHistory, mib2_oid: &str) {
+    let mut if_table = Table::new(&format!("{}.{}", mib2_oid, IF_ENTRY), IF_COLUMNS);
+    let mut if_x_table = Table::new(&format!("{}.{}", mib2_oid, IF_X_ENTRY), IF_X_COLUMNS);
+    let mut stack_table = Table::new(&format!("{}.{}", mib2_oid, IF_STACK_ENTRY), IF_STACK_COLUMNS);
+    let mut dot3_stats_table = Table::new(&format!("{}.{}", mib2_oid, DOT3_STATS_ENTRY), DOT3_STATS_COLUMNS);
+    let mut link_settings_table = Table::from_def(&LINK_SETTINGS_TABLE);
+    let mut driver_stats_table = Table::from_def(&DRIVER_STATS_TABLE);
+    let mut inv_stack_table = Table::new(&format!("{}.{}", mib2_oid, IF_INV_STACK_ENTRY), IF_INV_STACK_COLUMNS);
+    let mut seen = Vec::new();
+    let mut indexes = BTreeSet::new();
+    let mut links = BTreeSet::new();
+    let vlans = vlan_devices(host);
+
+    // Same clock as sysUpTime
+    let now = mib_sys::uptime(host).unwrap_or(0);
+
+    let (ifaces, from_netlink) = get_links(host);
+    let ethtool = host.ethtool();
+    let by_name = ifaces.iter()
+        .map(|link| (link.name.as_str(), link.index))
+        .collect::<HashMap<&str, u32>>();
+
+    for link in &ifaces {
+        let ifname = &link.name;
+        let ifsys = PathBuf::from("/sys/class/net").join(ifname);
+        let ifclass = classify_interface(host, link, &vlans);
+
+        if !includes(filter, ifname, ifclass) {
+            continue;
+        }
+
+        if from_netlink {
+            if let Some(master) = link.master {
+                links.insert((master, link.index));
+            }
+            if let (Some(lower), IfaceClass::Vlan) | (Some(lower), IfaceClass::Macvlan) = (link.link, ifclass) {
+                links.insert((link.index, lower));
+            }
+        }
+        else {
+            for (higher, lower) in stack_links(host, ifname, &vlans) {
+                if let (Some(&higher), Some(&lower)) = (by_name.get(higher.as_str()), by_name.get(lower.as_str())) {
+                    links.insert((higher, lower));
+                }
             }
+        }
+
+        let stats = link.stats.clone().unwrap_or_default();
+        // Only NICs have anything to tell via ethtool that is worth the ioctls
+        let physical = ifclass.names().contains(&"physical");
+        let settings = if physical { ethtool.link_settings(ifname).ok() } else { None };
+        // 0 if unknown, e.g. for virtual interfaces or with a host root
+        let speed = settings.as_ref().and_then(|settings| settings.speed).unwrap_or(0);
+        let oper_status = if link.operstate == Some(IF_OPER_UP) { 1 } else { 2 };
+
+        // Linux only counts received multicast packets separately, broadcasts
+        // and everything sent are in the unicast counters. The other
+        // non-unicast counters are left out, as 0 would claim there were none.
+        let in_ucast = stats.rx_packets.saturating_sub(stats.multicast);
+
+        let (last_change, discontinuity) = history.update(ifname, now, IfaceState {
+            ifindex:         Some(link.index),
+            oper_status,
+            carrier_changes: link.carrier_changes,
+            counters:        vec![stats.rx_bytes, stats.rx_packets, stats.tx_bytes, stats.tx_packets],
+            last_change:     0,
+            discontinuity:   0,
+        });
+        seen.push(ifname.to_owned());
+        indexes.insert(link.index);
+
+        if_table.row(link.index)
+            .set("ifIndex",           Value::Integer(link.index as i64))
+            .set("ifDescr",           Value::OctetString(ifname.to_owned()))
+            .set("ifType",            Value::Integer(ifclass.if_type()))
+            .set("ifMtu",             Value::Integer(link.mtu.unwrap_or(0) as i64))
+            .set("ifSpeed",           Value::Unsigned32(speed.saturating_mul(1000000)))
+            .set("ifPhysAddress",     Value::Octets(link.address.clone().unwrap_or_default()))
+            .set("ifAdminStatus",     Value::Integer(if link.flags & IFF_UP != 0 { 1 } else { 2 }))
+            .set("ifOperStatus",      Value::Integer(oper_status))
+            .set("ifLastChange",      Value::Timeticks(last_change))
+            .set("ifInOctets",        Value::Counter32(stats.rx_bytes))
+            .set("ifInUcastPkts",     Value::Counter32(in_ucast))
+            .set("ifInNUcastPkts",    Value::Counter32(stats.multicast))
+            .set("ifInDiscards",      Value::Counter32(stats.rx_dropped))
+            .set("ifInErrors",        Value::Counter32(stats.rx_errors))
+            .set("ifInUnknownProtos", Value::Counter32(stats.rx_nohandler))
+            .set("ifOutOctets",       Value::Counter32(stats.tx_bytes))
+            .set("ifOutUcastPkts",    Value::Counter32(stats.tx_packets))
+            .set("ifOutDiscards",     Value::Counter32(stats.tx_dropped))
+            .set("ifOutErrors",       Value::Counter32(stats.tx_errors));
+
+        if_x_table.row(link.index)
+            .set("ifName",                     Value::OctetString(ifname.to_owned()))
+            .set("ifInMulticastPkts",          Value::Counter32(stats.multicast))
+            .set("ifHCInOctets",               Value::Counter64(stats.rx_bytes))
+            .set("ifHCInUcastPkts",            Value::Counter64(in_ucast))
+            .set("ifHCInMulticastPkts",        Value::Counter64(stats.multicast))
+            .set("ifHCOutOctets",              Value::Counter64(stats.tx_bytes))
+            .set("ifHCOutUcastPkts",           Value::Counter64(stats.tx_packets))
+            // We don't send traps
+            .set("ifLinkUpDownTrapEnable",     Value::Integer(2))
+            .set("ifHighSpeed",                Value::Unsigned32(speed))
+            .set("ifPromiscuousMode",          Value::Integer(if link.flags & IFF_PROMISC != 0 { 1 } else { 2 }))
+            .set("ifConnectorPresent",         Value::Integer(if ifclass.names().contains(&"physical") { 1 } else { 2 }))
+            .set("ifAlias",                    Value::OctetString(link.alias.clone().unwrap_or_default()))
+            .set("ifCounterDiscontinuityTime", Value::Timeticks(discontinuity));
+
+        let duplex = settings.as_ref().map(|settings| match settings.duplex {
+            ethtool::DUPLEX_HALF => 2,
+            ethtool::DUPLEX_FULL => 3,
+            _                    => 1,
+        });
+        if let Some(ref settings) = settings {
+            let modes = |bits: &Vec<u32>| bits.iter()
+                .map(|&bit| ethtool::link_mode_name(bit))
+                .collect::<Vec<String>>()
+                .join(" ");
+            link_settings_table.row(link.index)
+                .set("suntLinkDuplex",          Value::Integer(duplex.unwrap_or(1)))
+                .set("suntLinkAutoneg",         Value::Integer(if settings.autoneg { 1 } else { 2 }))
+                .set("suntLinkPort",            Value::OctetStr(ethtool::port_name(settings.port)))
+                .set("suntLinkSupportedModes",  Value::OctetString(modes(&settings.supported)))
+                .set("suntLinkAdvertisedModes", Value::OctetString(modes(&settings.advertising)));
+            if let Some(speed) = settings.speed {
+                link_settings_table.row(link.index).set("suntLinkSpeed", Value::Unsigned32(speed));
+            }
+        }
+        let driver_stats = if physical { ethtool.driver_stats(ifname).unwrap_or_default() } else { Vec::new() };
+        for (name, value) in driver_stats {
+            driver_stats_table.row((link.index, name.as_str()))
+                .set("suntDriverStatValue", Value::Counter64(value));
+        }
 
-            // ifTable
-
-            values.insert( // ifIndex
-                OID::from_parts_and_instance(&[if_table_oid, "1"], iface_idx),
-                Value::Integer(iface_idx as i64)
-            );
-            values.insert( // ifDescr
-                OID::from_parts_and_instance(&[if_table_oid, "2"], iface_idx),
-                Value::OctetString(ifname.to_owned())
-            );
-            values.insert( // ifType
-                OID::from_parts_and_instance(&[if_table_oid, "3"], iface_idx),
-                Value::Integer(match ifclass {
-                    IfaceClass::Physical if ifname.starts_with("wl") => 71,
-                    IfaceClass::VLAN     => 135,
-                    _                    => 6
-                } as i64)
-            );
-            values.insert( // ifMtu
-                OID::from_parts_and_instance(&[if_table_oid, "4"], iface_idx),
-                Value::Integer(u32_from_file(&ifsys.join("mtu")).unwrap() as i64)
-            );
-            values.insert( // ifSpeed
-                OID::from_parts_and_instance(&[if_table_oid, "5"], iface_idx),
-                Value::Unsigned32(
-                    u32_from_file(&ifsys.join("speed")).or(Some(0)).unwrap().saturating_mul(1000000)
-                )
-            );
-            // ifPhysAddress not supported
-            // ifAdminStatus not supported
-            values.insert( // ifOperStatus
-                OID::from_parts_and_instance(&[if_table_oid, "8"], iface_idx),
-                Value::Integer(
-                    match str_from_file(&ifsys.join("operstate")).unwrap() == "up" {
-                        true  => 1,
-                        false => 2
-                    }
-                )
-            );
-            // ifLastChange not supported
-            values.insert( // ifInOctets
-                OID::from_parts_and_instance(&[if_table_oid, "10"], iface_idx),
-                Value::Counter32(parts[1].parse::<u64>().unwrap())
-            );
-            values.insert( // ifInUcastPkts
-                OID::from_parts_and_instance(&[if_table_oid, "11"], iface_idx),
-                Value::Counter32(parts[2].parse::<u64>().unwrap())
-            );
-            values.insert( // ifInNUcastPkts
-                OID::from_parts_and_instance(&[if_table_oid, "12"], iface_idx),
-                Value::Counter32(parts[8].parse::<u64>().unwrap())
-            );
-            values.insert( // ifInDiscards
-                OID::from_parts_and_instance(&[if_table_oid, "13"], iface_idx),
-                Value::Counter32(parts[4].parse::<u64>().unwrap())
-            );
-            values.insert( // ifInErrors
-                OID::from_parts_and_instance(&[if_table_oid, "14"], iface_idx),
-                Value::Counter32(parts[3].parse::<u64>().unwrap())
-            );
-            // IfInUnknownProtos not supported
-            values.insert( // ifOutOctets
-                OID::from_parts_and_instance(&[if_table_oid, "16"], iface_idx),
-                Value::Counter32(parts[9].parse::<u64>().unwrap())
-            );
-            values.insert( // ifOutUcastPkts
-                OID::from_parts_and_instance(&[if_table_oid, "17"], iface_idx),
-                Value::Counter32(parts[10].parse::<u64>().unwrap())
-            );
-            // ifOutNUcastPkts not supported
-            values.insert( // ifOutDiscards
-                OID::from_parts_and_instance(&[if_table_oid, "19"], iface_idx),
-                Value::Counter32(parts[12].parse::<u64>().unwrap())
-            );
-            values.insert( // ifOutErrors
-                OID::from_parts_and_instance(&[if_table_oid, "20"], iface_idx),
-                Value::Counter32(parts[11].parse::<u64>().unwrap())
-            );
-            // ifOutQLen not supported
-            // ifSpecific not supported
-
-            // extended Table (no idea if it has a name)
-
-            values.insert( // ifName
-                OID::from_parts_and_instance(&[extended_oid, "1"], iface_idx),
-                Value::OctetString(ifname.to_owned())
-            );
-            values.insert( // ifHCInOctets
-                OID::from_parts_and_instance(&[extended_oid, "6"], iface_idx),
-                Value::Counter64(parts[1].parse::<u64>().unwrap())
-            );
-            values.insert( // ifHCInUcastPkts
-                OID::from_parts_and_instance(&[extended_oid, "7"], iface_idx),
-                Value::Counter64(parts[2].parse::<u64>().unwrap())
-            );
-            values.insert( // ifHCOutOctets
-                OID::from_parts_and_instance(&[extended_oid, "10"], iface_idx),
-                Value::Counter64(parts[9].parse::<u64>().unwrap())
-            );
-            values.insert( // ifHCOutUcastPkts
-                OID::from_parts_and_instance(&[extended_oid, "11"], iface_idx),
-                Value::Counter64(parts[10].parse::<u64>().unwrap())
-            );
-            values.insert( // ifHighSpeed
-                OID::from_parts_and_instance(&[extended_oid, "15"], iface_idx),
-                Value::Unsigned32(
-                    u32_from_file(&ifsys.join("speed")).or(Some(0)).unwrap()
-                )
-            );
-
-            iface_idx += 1;
+        if ifclass == IfaceClass::Ethernet {
+            // Without ethtool, sysfs knows the duplex but fails to read it while the link is down
+            let duplex = duplex.unwrap_or_else(|| match str_from_file(&host.path(ifsys.join("duplex"))) {
+                Some(ref duplex) if duplex == "half" => 2,
+                Some(ref duplex) if duplex == "full" => 3,
+                _                                    => 1,
+            });
+            // The kernel counts late collisions as tx_window_errors and frames
+            // given up on after too many collisions as tx_aborted_errors.
+            // rx_length_errors include runts, most drivers have no counter of their own for them.
+            dot3_stats_table.row(link.index)
+                .set("dot3StatsIndex",                     Value::Integer(link.index as i64))
+                .set("dot3StatsAlignmentErrors",           Value::Counter32(stats.rx_frame_errors))
+                .set("dot3StatsFCSErrors",                 Value::Counter32(stats.rx_crc_errors))
+                .set("dot3StatsLateCollisions",            Value::Counter32(stats.tx_window_errors))
+                .set("dot3StatsExcessiveCollisions",       Value::Counter32(stats.tx_aborted_errors))
+                .set("dot3StatsInternalMacTransmitErrors", Value::Counter32(stats.tx_fifo_errors))
+                .set("dot3StatsCarrierSenseErrors",        Value::Counter32(stats.tx_carrier_errors))
+                .set("dot3StatsFrameTooLongs",             Value::Counter32(stats.rx_length_errors))
+                .set("dot3StatsInternalMacReceiveErrors",  Value::Counter32(stats.rx_fifo_errors))
+                .set("dot3StatsDuplexStatus",              Value::Integer(duplex));
         }
     }
+
+    history.refreshed(&seen);
+
+    // Only stack interfaces that are in the ifTable, the others are 0 just like
+    // the top and bottom of the stack.
+    let mut stack = links.into_iter()
+        .filter(|&(higher, lower)| indexes.contains(&higher) && indexes.contains(&lower))
+        .collect::<BTreeSet<(u32, u32)>>();
+    for &idx in &indexes {
+        if !stack.iter().any(|&(_, lower)| lower == idx) {
+            stack.insert((0, idx));
+        }
+        if !stack.iter().any(|&(higher, _)| higher == idx) {
+            stack.insert((idx, 0));
+        }
+    }
+    for (higher, lower) in stack {
+        stack_table.row((higher, lower))
+            .set("ifStackStatus", Value::Integer(ROW_ACTIVE));
+        inv_stack_table.row((lower, higher))
+            .set("ifInvStackStatus", Value::Integer(ROW_ACTIVE));
+    }
+
+    if_table.insert_into(values);
+    if_x_table.insert_into(values);
+    stack_table.insert_into(values);
+    inv_stack_table.insert_into(values);
+    dot3_stats_table.insert_into(values);
+    link_settings_table.insert_into(values);
+    driver_stats_table.insert_into(values);
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
 
+    #[test]
+    fn classify_virtual_interfaces() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let files: &[(&str, &str)] = &[
+            ("tap0/type",       "1"),
+            ("tap0/tun_flags",  "0x1002"),
+            ("tun0/type",       "65534"),
+            ("tun0/tun_flags",  "0x1001"),
+            ("mv0/type",        "1"),
+            ("mv0/ifindex",     "5"),
+            ("mv0/iflink",      "2"),
+            ("mv0/lower_eth0",  ""),
+            ("veth0/type",      "1"),
+            ("veth0/ifindex",   "6"),
+            ("veth0/iflink",    "7"),
+            ("gre1/type",       "778"),
+            ("sit0/type",       "776"),
+            ("dummy0/type",     "1"),
+            ("can0/type",       "280"),
+        ];
+        for &(path, content) in files {
+            let path = root.join("sys/class/net").join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let host = Host::new(root);
+        let classify_interface = |host: &Host, ifname: &str| classify_interface(host, &sysfs_link(host, ifname), &[]);
+        assert_eq!(classify_interface(&host, "tap0"),   IfaceClass::Tap);
+        assert_eq!(classify_interface(&host, "tun0"),   IfaceClass::Tun);
+        assert_eq!(classify_interface(&host, "mv0"),    IfaceClass::Macvlan);
+        assert_eq!(classify_interface(&host, "veth0"),  IfaceClass::Veth);
+        assert_eq!(classify_interface(&host, "gre1"),   IfaceClass::Tunnel);
+        assert_eq!(classify_interface(&host, "sit0"),   IfaceClass::Tunnel);
+        assert_eq!(classify_interface(&host, "dummy0"), IfaceClass::Other);
+        assert_eq!(classify_interface(&host, "can0"),   IfaceClass::Other);
+    }
+
+    #[test]
+    fn tracks_changes_across_refreshes() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let sys = root.join("sys/class/net/eth0");
+        fs::create_dir_all(root.join("proc/net")).unwrap();
+        fs::create_dir_all(&sys).unwrap();
+        for &(name, content) in &[("type", "1"), ("device", ""), ("ifindex", "2"), ("mtu", "1500"), ("operstate", "up"), ("carrier_changes", "1")] {
+            fs::write(sys.join(name), content).unwrap();
+        }
+        let set_stats = |uptime: &str, rx_bytes: u64| {
+            fs::write(root.join("proc/uptime"), format!("{} 0.00", uptime)).unwrap();
+            fs::write(root.join("proc/net/dev"), format!(
+                "Inter-|   Receive\n face |bytes\n  eth0: {} 10 0 0 0 0 0 0 500 5 0 0 0 0 0 0\n", rx_bytes
+            )).unwrap();
+        };
+
+        let host = Host::new(root);
+        let filter = Filter::new(&[], interface_defaults());
+        let mut history = InterfaceHistory::new();
+        let mut refresh = |ifindex: u32| {
+            let mut values = BTreeMap::new();
+            get_interfaces(&mut values, &host, &filter, &mut history, "1");
+            let cell = |column: &str| values[&OID::from_parts(&[column, &ifindex.to_string()])].clone();
+            (cell("1.2.2.1.9"), cell("1.31.1.1.1.19"))
+        };
+
+        set_stats("100.50", 1000);
+        assert_eq!(refresh(2), (Value::Timeticks(0), Value::Timeticks(0)));
+        set_stats("200.50", 2000);
+        assert_eq!(refresh(2), (Value::Timeticks(0), Value::Timeticks(0)));
+
+        fs::write(sys.join("operstate"), "down").unwrap();
+        fs::write(sys.join("carrier_changes"), "2").unwrap();
+        set_stats("300.50", 3000);
+        assert_eq!(refresh(2), (Value::Timeticks(30050), Value::Timeticks(0)));
+
+        // Counters going backwards mean the driver reset them
+        set_stats("400.50", 10);
+        assert_eq!(refresh(2), (Value::Timeticks(30050), Value::Timeticks(40050)));
+
+        // Interface deleted and created again
+        fs::write(sys.join("ifindex"), "7").unwrap();
+        set_stats("500.50", 20);
+        assert_eq!(refresh(7), (Value::Timeticks(50050), Value::Timeticks(50050)));
+    }
+}
@@ -0,0 +1,595 @@
+use std::collections::BTreeMap;
+use std::fs::{self,File};
+use std::io::{BufRead,BufReader};
+use std::path::PathBuf;
+use value::{Value,u32_from_file};
+use oid::OID;
+use table::{Table,Row,Column,TableDef};
+use table::Syntax::*;
+use host::Host;
+use mib_net::IncludedInterfaces;
+
+/// Counters of /proc/net/snmp, /proc/net/snmp6 and /proc/net/netstat, by group (e.g. TcpExt) and name
+type Counters = BTreeMap<String, BTreeMap<String, i64>>;
+
+/// Group and name of a counter
+type CounterName = (&'static str, &'static str);
+
+/// The ip, icmp, tcp and udp groups below mib-2
+const IP_GROUP:   u32 = 4;
+const ICMP_GROUP: u32 = 5;
+const TCP_GROUP:  u32 = 6;
+const UDP_GROUP:  u32 = 7;
+
+// Entries of the ipSystemStatsTable, ipIfStatsTable and icmpStatsTable, relative to their groups
+const SYSTEM_STATS_ENTRY: &str = "31.1.1";
+const IF_STATS_ENTRY:     &str = "31.3.1";
+const ICMP_STATS_ENTRY:   &str = "29.1";
+
+// Objects of the ip, icmp, tcp and udp groups and the counters they come from.
+// Objects without a syntax are Counter32.
+const IP_SCALARS: &[(u32, &str)] = &[
+    ( 3, "InReceives"),
+    ( 4, "InHdrErrors"),
+    ( 5, "InAddrErrors"),
+    ( 6, "ForwDatagrams"),
+    ( 7, "InUnknownProtos"),
+    ( 8, "InDiscards"),
+    ( 9, "InDelivers"),
+    (10, "OutRequests"),
+    (11, "OutDiscards"),
+    (12, "OutNoRoutes"),
+    (14, "ReasmReqds"),
+    (15, "ReasmOKs"),
+    (16, "ReasmFails"),
+    (17, "FragOKs"),
+    (18, "FragFails"),
+    (19, "FragCreates"),
+];
+
+/// ipForwarding, ipDefaultTTL and ipReasmTimeout are settings rather than counters
+const IP_SETTINGS: &[(u32, &str)] = &[
+    ( 1, "Forwarding"),
+    ( 2, "DefaultTTL"),
+    (13, "ReasmTimeout"),
+];
+
+const ICMP_SCALARS: &[(u32, &str)] = &[
+    ( 1, "InMsgs"),
+    ( 2, "InErrors"),
+    ( 3, "InDestUnreachs"),
+    ( 4, "InTimeExcds"),
+    ( 5, "InParmProbs"),
+    ( 6, "InSrcQuenchs"),
+    ( 7, "InRedirects"),
+    ( 8, "InEchos"),
+    ( 9, "InEchoReps"),
+    (10, "InTimestamps"),
+    (11, "InTimestampReps"),
+    (12, "InAddrMasks"),
+    (13, "InAddrMaskReps"),
+    (14, "OutMsgs"),
+    (15, "OutErrors"),
+    (16, "OutDestUnreachs"),
+    (17, "OutTimeExcds"),
+    (18, "OutParmProbs"),
+    (19, "OutSrcQuenchs"),
+    (20, "OutRedirects"),
+    (21, "OutEchos"),
+    (22, "OutEchoReps"),
+    (23, "OutTimestamps"),
+    (24, "OutTimestampReps"),
+    (25, "OutAddrMasks"),
+    (26, "OutAddrMaskReps"),
+];
+
+/// Linux counts TCP over IPv4 and IPv6 together, like the TCP-MIB does
+const TCP_SCALARS: &[(u32, &str)] = &[
+    ( 5, "ActiveOpens"),
+    ( 6, "PassiveOpens"),
+    ( 7, "AttemptFails"),
+    ( 8, "EstabResets"),
+    (10, "InSegs"),
+    (11, "OutSegs"),
+    (12, "RetransSegs"),
+    (14, "InErrs"),
+    (15, "OutRsts"),
+];
+
+const TCP_SETTINGS: &[(u32, &str)] = &[
+    (1, "RtoAlgorithm"),
+    (2, "RtoMin"),
+    (3, "RtoMax"),
+    (4, "MaxConn"),
+];
+
+/// tcpCurrEstab is a Gauge32, tcpHCInSegs and tcpHCOutSegs are 64 bit versions of tcpInSegs and tcpOutSegs
+const TCP_CURR_ESTAB: u32 = 9;
+const TCP_HC_SCALARS: &[(u32, &str)] = &[
+    (17, "InSegs"),
+    (18, "OutSegs"),
+];
+
+/// The UDP-MIB counts IPv4 and IPv6 together, Linux separately
+const UDP_SCALARS: &[(u32, &str)] = &[
+    (1, "InDatagrams"),
+    (2, "NoPorts"),
+    (3, "InErrors"),
+    (4, "OutDatagrams"),
+];
+
+const UDP_HC_SCALARS: &[(u32, &str)] = &[
+    (8, "InDatagrams"),
+    (9, "OutDatagrams"),
+];
+
+/// ipv6IpForwarding and ipv6IpDefaultHopLimit, which /proc/net/snmp6 lacks
+const IPV6_FORWARDING:        u32 = 25;
+const IPV6_DEFAULT_HOP_LIMIT: u32 = 26;
+
+const SYSTEM_STATS_COLUMNS: &[Column] = &[
+    Column::new(      3, "ipSystemStatsInReceives",         Counter32),
+    Column::new(      4, "ipSystemStatsHCInReceives",       Counter64),
+    Column::new(      5, "ipSystemStatsInOctets",           Counter32),
+    Column::new(      6, "ipSystemStatsHCInOctets",         Counter64),
+    Column::new(      7, "ipSystemStatsInHdrErrors",        Counter32),
+    Column::new(      8, "ipSystemStatsInNoRoutes",         Counter32),
+    Column::new(      9, "ipSystemStatsInAddrErrors",       Counter32),
+    Column::new(     10, "ipSystemStatsInUnknownProtos",    Counter32),
+    Column::new(     11, "ipSystemStatsInTruncatedPkts",    Counter32),
+    Column::new(     12, "ipSystemStatsInForwDatagrams",    Counter32),
+    Column::new(     13, "ipSystemStatsHCInForwDatagrams",  Counter64),
+    Column::new(     14, "ipSystemStatsReasmReqds",         Counter32),
+    Column::new(     15, "ipSystemStatsReasmOKs",           Counter32),
+    Column::new(     16, "ipSystemStatsReasmFails",         Counter32),
+    Column::new(     17, "ipSystemStatsInDiscards",         Counter32),
+    Column::new(     18, "ipSystemStatsInDelivers",         Counter32),
+    Column::new(     19, "ipSystemStatsHCInDelivers",       Counter64),
+    Column::new(     20, "ipSystemStatsOutRequests",        Counter32),
+    Column::new(     21, "ipSystemStatsHCOutRequests",      Counter64),
+    Column::new(     22, "ipSystemStatsOutNoRoutes",        Counter32),
+    Column::new(     23, "ipSystemStatsOutForwDatagrams",   Counter32),
+    Column::new(     24, "ipSystemStatsHCOutForwDatagrams", Counter64),
+    Column::new(     25, "ipSystemStatsOutDiscards",        Counter32),
+    Column::new(     27, "ipSystemStatsOutFragOKs",         Counter32),
+    Column::new(     28, "ipSystemStatsOutFragFails",       Counter32),
+    Column::new(     29, "ipSystemStatsOutFragCreates",     Counter32),
+    // Only kernels since 6.3 count transmitted datagrams
+    Column::optional(30, "ipSystemStatsOutTransmits",       Counter32),
+    Column::optional(31, "ipSystemStatsHCOutTransmits",     Counter64),
+    Column::new(     32, "ipSystemStatsOutOctets",          Counter32),
+    Column::new(     33, "ipSystemStatsHCOutOctets",        Counter64),
+    Column::new(     34, "ipSystemStatsInMcastPkts",        Counter32),
+    Column::new(     35, "ipSystemStatsHCInMcastPkts",      Counter64),
+    Column::new(     36, "ipSystemStatsInMcastOctets",      Counter32),
+    Column::new(     37, "ipSystemStatsHCInMcastOctets",    Counter64),
+    Column::new(     38, "ipSystemStatsOutMcastPkts",       Counter32),
+    Column::new(     39, "ipSystemStatsHCOutMcastPkts",     Counter64),
+    Column::new(     40, "ipSystemStatsOutMcastOctets",     Counter32),
+    Column::new(     41, "ipSystemStatsHCOutMcastOctets",   Counter64),
+    // IPv6 has no broadcasts
+    Column::optional(42, "ipSystemStatsInBcastPkts",        Counter32),
+    Column::optional(43, "ipSystemStatsHCInBcastPkts",      Counter64),
+    Column::optional(44, "ipSystemStatsOutBcastPkts",       Counter32),
+    Column::optional(45, "ipSystemStatsHCOutBcastPkts",     Counter64),
+    Column::new(     46, "ipSystemStatsDiscontinuityTime",  TimeTicks),
+];
+
+const IF_STATS_COLUMNS: &[Column] = &[
+    Column::new(      3, "ipIfStatsInReceives",         Counter32),
+    Column::new(      4, "ipIfStatsHCInReceives",       Counter64),
+    Column::new(      5, "ipIfStatsInOctets",           Counter32),
+    Column::new(      6, "ipIfStatsHCInOctets",         Counter64),
+    Column::new(      7, "ipIfStatsInHdrErrors",        Counter32),
+    Column::new(      8, "ipIfStatsInNoRoutes",         Counter32),
+    Column::new(      9, "ipIfStatsInAddrErrors",       Counter32),
+    Column::new(     10, "ipIfStatsInUnknownProtos",    Counter32),
+    Column::new(     11, "ipIfStatsInTruncatedPkts",    Counter32),
+    Column::new(     12, "ipIfStatsInForwDatagrams",    Counter32),
+    Column::new(     13, "ipIfStatsHCInForwDatagrams",  Counter64),
+    Column::new(     14, "ipIfStatsReasmReqds",         Counter32),
+    Column::new(     15, "ipIfStatsReasmOKs",           Counter32),
+    Column::new(     16, "ipIfStatsReasmFails",         Counter32),
+    Column::new(     17, "ipIfStatsInDiscards",         Counter32),
+    Column::new(     18, "ipIfStatsInDelivers",         Counter32),
+    Column::new(     19, "ipIfStatsHCInDelivers",       Counter64),
+    Column::new(     20, "ipIfStatsOutRequests",        Counter32),
+    Column::new(     21, "ipIfStatsHCOutRequests",      Counter64),
+    Column::new(     22, "ipIfStatsOutNoRoutes",        Counter32),
+    Column::new(     23, "ipIfStatsOutForwDatagrams",   Counter32),
+    Column::new(     24, "ipIfStatsHCOutForwDatagrams", Counter64),
+    Column::new(     25, "ipIfStatsOutDiscards",        Counter32),
+    Column::new(     27, "ipIfStatsOutFragOKs",         Counter32),
+    Column::new(     28, "ipIfStatsOutFragFails",       Counter32),
+    Column::new(     29, "ipIfStatsOutFragCreates",     Counter32),
+    // Only kernels since 6.3 count transmitted datagrams
+    Column::optional(30, "ipIfStatsOutTransmits",       Counter32),
+    Column::optional(31, "ipIfStatsHCOutTransmits",     Counter64),
+    Column::new(     32, "ipIfStatsOutOctets",          Counter32),
+    Column::new(     33, "ipIfStatsHCOutOctets",        Counter64),
+    Column::new(     34, "ipIfStatsInMcastPkts",        Counter32),
+    Column::new(     35, "ipIfStatsHCInMcastPkts",      Counter64),
+    Column::new(     36, "ipIfStatsInMcastOctets",      Counter32),
+    Column::new(     37, "ipIfStatsHCInMcastOctets",    Counter64),
+    Column::new(     38, "ipIfStatsOutMcastPkts",       Counter32),
+    Column::new(     39, "ipIfStatsHCOutMcastPkts",     Counter64),
+    Column::new(     40, "ipIfStatsOutMcastOctets",     Counter32),
+    Column::new(     41, "ipIfStatsHCOutMcastOctets",   Counter64),
+    // IPv6 has no broadcasts
+    Column::optional(42, "ipIfStatsInBcastPkts",        Counter32),
+    Column::optional(43, "ipIfStatsHCInBcastPkts",      Counter64),
+    Column::optional(44, "ipIfStatsOutBcastPkts",       Counter32),
+    Column::optional(45, "ipIfStatsHCOutBcastPkts",     Counter64),
+    Column::new(     46, "ipIfStatsDiscontinuityTime",  TimeTicks),
+];
+
+/**
+ * Where the ipSystemStatsTable counters are kept: Name of the column without
+ * its ipSystemStats(HC) prefix, and group and name of the IPv4 and the IPv6
+ * counter. Counters with an HC column are listed in SYSTEM_STATS_HC as well.
+ */
+const SYSTEM_STATS: &[(&str, CounterName, CounterName)] = &[
+    ("InReceives",       ("Ip",    "InReceives"),      ("Ip6", "InReceives")),
+    ("InOctets",         ("IpExt", "InOctets"),        ("Ip6", "InOctets")),
+    ("InHdrErrors",      ("Ip",    "InHdrErrors"),     ("Ip6", "InHdrErrors")),
+    ("InNoRoutes",       ("IpExt", "InNoRoutes"),      ("Ip6", "InNoRoutes")),
+    ("InAddrErrors",     ("Ip",    "InAddrErrors"),    ("Ip6", "InAddrErrors")),
+    ("InUnknownProtos",  ("Ip",    "InUnknownProtos"), ("Ip6", "InUnknownProtos")),
+    ("InTruncatedPkts",  ("IpExt", "InTruncatedPkts"), ("Ip6", "InTruncatedPkts")),
+    // Linux only counts datagrams it managed to forward
+    ("InForwDatagrams",  ("Ip",    "ForwDatagrams"),   ("Ip6", "OutForwDatagrams")),
+    ("ReasmReqds",       ("Ip",    "ReasmReqds"),      ("Ip6", "ReasmReqds")),
+    ("ReasmOKs",         ("Ip",    "ReasmOKs"),        ("Ip6", "ReasmOKs")),
+    ("ReasmFails",       ("Ip",    "ReasmFails"),      ("Ip6", "ReasmFails")),
+    ("InDiscards",       ("Ip",    "InDiscards"),      ("Ip6", "InDiscards")),
+    ("InDelivers",       ("Ip",    "InDelivers"),      ("Ip6", "InDelivers")),
+    ("OutRequests",      ("Ip",    "OutRequests"),     ("Ip6", "OutRequests")),
+    ("OutNoRoutes",      ("Ip",    "OutNoRoutes"),     ("Ip6", "OutNoRoutes")),
+    ("OutForwDatagrams", ("Ip",    "ForwDatagrams"),   ("Ip6", "OutForwDatagrams")),
+    ("OutDiscards",      ("Ip",    "OutDiscards"),     ("Ip6", "OutDiscards")),
+    ("OutFragOKs",       ("Ip",    "FragOKs"),         ("Ip6", "FragOKs")),
+    ("OutFragFails",     ("Ip",    "FragFails"),       ("Ip6", "FragFails")),
+    ("OutFragCreates",   ("Ip",    "FragCreates"),     ("Ip6", "FragCreates")),
+    ("OutTransmits",     ("Ip",    "OutTransmits"),    ("Ip6", "OutTransmits")),
+    ("OutOctets",        ("IpExt", "OutOctets"),       ("Ip6", "OutOctets")),
+    ("InMcastPkts",      ("IpExt", "InMcastPkts"),     ("Ip6", "InMcastPkts")),
+    ("InMcastOctets",    ("IpExt", "InMcastOctets"),   ("Ip6", "InMcastOctets")),
+    ("OutMcastPkts",     ("IpExt", "OutMcastPkts"),    ("Ip6", "OutMcastPkts")),
+    ("OutMcastOctets",   ("IpExt", "OutMcastOctets"),  ("Ip6", "OutMcastOctets")),
+    ("InBcastPkts",      ("IpExt", "InBcastPkts"),     ("Ip6", "InBcastPkts")),
+    ("OutBcastPkts",     ("IpExt", "OutBcastPkts"),    ("Ip6", "OutBcastPkts")),
+];
+
+const SYSTEM_STATS_HC: &[&str] = &[
+    "InReceives", "InOctets", "InForwDatagrams", "InDelivers", "OutRequests", "OutForwDatagrams", "OutTransmits",
+    "OutOctets", "InMcastPkts", "InMcastOctets", "OutMcastPkts", "OutMcastOctets", "InBcastPkts", "OutBcastPkts",
+];
+
+const ICMP_STATS_COLUMNS: &[Column] = &[
+    Column::new(2, "icmpStatsInMsgs",    Counter32),
+    Column::new(3, "icmpStatsInErrors",  Counter32),
+    Column::new(4, "icmpStatsOutMsgs",   Counter32),
+    Column::new(5, "icmpStatsOutErrors", Counter32),
+];
+
+const NETSTAT_COLUMNS: &[Column] = &[
+    Column::new(1, "suntNetstatGroup", OctetString).describe("Group of the counter, e.g. TcpExt or Udp6."),
+    Column::new(2, "suntNetstatName",  OctetString).describe("Name of the counter as the kernel calls it, e.g. ListenOverflows."),
+    Column::new(3, "suntNetstatValue", Counter64).describe("Value of the counter."),
+];
+
+pub const NETSTAT_TABLE: TableDef = TableDef {
+    name:        "suntNetstatTable",
+    entry_oid:   "1.3.6.1.4.1.2147483647.1.5.1",
+    description: "Linux specific protocol counters the IP, TCP and UDP MIBs have no objects for, as `nstat` shows them: \
+                  Everything from /proc/net/netstat, e.g. TcpExt ListenOverflows, and the UDP buffer errors.",
+    index:       &["suntNetstatGroup", "suntNetstatName"],
+    columns:     NETSTAT_COLUMNS,
+};
+
+/// Counters of the Udp groups that are only in the suntNetstatTable
+const UDP_EXTRA: &[&str] = &["RcvbufErrors", "SndbufErrors", "InCsumErrors", "IgnoredMulti", "MemErrors"];
+
+/// InetVersion
+const IPV4: u32 = 1;
+const IPV6: u32 = 2;
+
+/**
+ * Counters are unsigned, but Tcp MaxConn is -1.
+ */
+fn parse_counter(text: &str) -> Option<i64> {
+    text.parse::<i64>().ok().or_else(|| text.parse::<u64>().ok().map(|value| value as i64))
+}
+
+/**
+ * Parse /proc/net/snmp or /proc/net/netstat, where each group is a line of
+ * names followed by a line of values, e.g. "Tcp: RtoAlgorithm RtoMin ...", "Tcp: 1 200 ...".
+ */
+fn parse_group_lines<R: BufRead>(reader: R, counters: &mut Counters) {
+    let mut names: Option<(String, Vec<String>)> = None;
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_)   => break
+        };
+        let mut parts = line.split_whitespace();
+        let group = match parts.next() {
+            Some(group) => group.trim_end_matches(':').to_owned(),
+            None        => continue
+        };
+        match names.take() {
+            Some((ref header, ref header_names)) if *header == group => {
+                let group = counters.entry(group).or_default();
+                for (name, value) in header_names.iter().zip(parts) {
+                    if let Some(value) = parse_counter(value) {
+                        group.insert(name.to_owned(), value);
+                    }
+                }
+            }
+            _ => names = Some((group, parts.map(String::from).collect()))
+        }
+    }
+}
+
+/**
+ * Parse /proc/net/snmp6, with lines like "Ip6InReceives 123". The group is
+ * the part up to the 6, e.g. Icmp6 for Icmp6InMsgs.
+ */
+fn parse_snmp6<R: BufRead>(reader: R, counters: &mut Counters) {
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_)   => break
+        };
+        let mut parts = line.split_whitespace();
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if let (Some(pos), Some(value)) = (name.find('6'), parse_counter(value)) {
+                counters.entry(name[..=pos].to_owned()).or_default()
+                    .insert(name[pos + 1..].to_owned(), value);
+            }
+        }
+    }
+}
+
+fn read_counters(host: &Host) -> Counters {
+    let mut counters = Counters::new();
+    for name in &["/proc/net/snmp", "/proc/net/netstat"] {
+        if let Ok(file) = File::open(host.path(name)) {
+            parse_group_lines(BufReader::new(file), &mut counters);
+        }
+    }
+    if let Ok(file) = File::open(host.path("/proc/net/snmp6")) {
+        parse_snmp6(BufReader::new(file), &mut counters);
+    }
+    counters
+}
+
+fn counter(counters: &Counters, group: &str, name: &str) -> Option<i64> {
+    counters.get(group).and_then(|group| group.get(name)).cloned()
+}
+
+/**
+ * Fill a row of the ipSystemStatsTable or ipIfStatsTable, whose columns are
+ * named like those in SYSTEM_STATS with the given prefix.
+ */
+fn set_ip_stats(row: &mut Row, prefix: &str, counters: &Counters, version: u32) {
+    for &(name, v4, v6) in SYSTEM_STATS {
+        let (group, counter_name) = if version == IPV4 { v4 } else { v6 };
+        if let Some(value) = counter(counters, group, counter_name) {
+            row.set(&format!("{}{}", prefix, name), Value::Counter32(value as u64));
+            if SYSTEM_STATS_HC.contains(&name) {
+                row.set(&format!("{}HC{}", prefix, name), Value::Counter64(value as u64));
+            }
+        }
+    }
+    row.set(&format!("{}DiscontinuityTime", prefix), Value::Timeticks(0));
+}
+
+/**
+ * The OIDs of the scalars and tables get_protocol_stats and get_interface_stats fill.
+ * The ip, tcp and udp groups also hold tables of other collectors, so each
+ * scalar is a subtree of its own.
+ */
+pub fn netstat_subtrees(mib2_oid: &str) -> Vec<String> {
+    let numbers = |scalars: &[&[(u32, &str)]]| scalars.iter()
+        .flat_map(|scalars| scalars.iter().map(|&(number, _)| number))
+        .collect::<Vec<u32>>();
+    let mut subtrees = Vec::new();
+    let mut ip = numbers(&[IP_SCALARS, IP_SETTINGS]);
+    ip.extend_from_slice(&[IPV6_FORWARDING, IPV6_DEFAULT_HOP_LIMIT]);
+    let mut tcp = numbers(&[TCP_SCALARS, TCP_SETTINGS, TCP_HC_SCALARS]);
+    tcp.push(TCP_CURR_ESTAB);
+    for &(group, ref numbers) in &[(IP_GROUP, ip), (ICMP_GROUP, numbers(&[ICMP_SCALARS])), (TCP_GROUP, tcp), (UDP_GROUP, numbers(&[UDP_SCALARS, UDP_HC_SCALARS]))] {
+        subtrees.extend(numbers.iter().map(|number| format!("{}.{}.{}", mib2_oid, group, number)));
+    }
+    subtrees.push(format!("{}.{}.{}", mib2_oid, IP_GROUP, SYSTEM_STATS_ENTRY));
+    subtrees.push(format!("{}.{}.{}", mib2_oid, IP_GROUP, IF_STATS_ENTRY));
+    subtrees.push(format!("{}.{}.{}", mib2_oid, ICMP_GROUP, ICMP_STATS_ENTRY));
+    subtrees.push(NETSTAT_TABLE.entry_oid.to_owned());
+    subtrees
+}
+
+/**
+ * Export the ip, icmp, tcp and udp scalars, the ipSystemStatsTable and
+ * icmpStatsTable, and everything Linux counts beyond them in the suntNetstatTable.
+ */
+pub fn get_protocol_stats(values: &mut BTreeMap<OID, Value>, host: &Host, mib2_oid: &str) {
+    let ip_oid = &format!("{}.{}", mib2_oid, IP_GROUP);
+    let icmp_oid = &format!("{}.{}", mib2_oid, ICMP_GROUP);
+    let tcp_oid = &format!("{}.{}", mib2_oid, TCP_GROUP);
+    let udp_oid = &format!("{}.{}", mib2_oid, UDP_GROUP);
+    let counters = read_counters(host);
+    let mut scalar = |base_oid: &str, number: u32, value: Value<'static>| {
+        values.insert(OID::from_parts(&[base_oid, &number.to_string(), "0"]), value);
+    };
+
+    for &(number, name) in IP_SETTINGS {
+        if let Some(value) = counter(&counters, "Ip", name) {
+            scalar(ip_oid, number, Value::Integer(value));
+        }
+    }
+    for &(number, name) in IP_SCALARS {
+        if let Some(value) = counter(&counters, "Ip", name) {
+            scalar(ip_oid, number, Value::Counter32(value as u64));
+        }
+    }
+    if let Some(forwarding) = u32_from_file(&host.path("/proc/sys/net/ipv6/conf/all/forwarding")) {
+        scalar(ip_oid, IPV6_FORWARDING, Value::Integer(if forwarding == 1 { 1 } else { 2 }));
+    }
+    if let Some(hop_limit) = u32_from_file(&host.path("/proc/sys/net/ipv6/conf/all/hop_limit")) {
+        scalar(ip_oid, IPV6_DEFAULT_HOP_LIMIT, Value::Integer(hop_limit as i64));
+    }
+
+    for &(number, name) in ICMP_SCALARS {
+        if let Some(value) = counter(&counters, "Icmp", name) {
+            scalar(icmp_oid, number, Value::Counter32(value as u64));
+        }
+    }
+
+    for &(number, name) in TCP_SETTINGS {
+        if let Some(value) = counter(&counters, "Tcp", name) {
+            scalar(tcp_oid, number, Value::Integer(value));
+        }
+    }
+    for &(number, name) in TCP_SCALARS {
+        if let Some(value) = counter(&counters, "Tcp", name) {
+            scalar(tcp_oid, number, Value::Counter32(value as u64));
+        }
+    }
+    if let Some(value) = counter(&counters, "Tcp", "CurrEstab") {
+        scalar(tcp_oid, TCP_CURR_ESTAB, Value::Unsigned32(value as u32));
+    }
+    for &(number, name) in TCP_HC_SCALARS {
+        if let Some(value) = counter(&counters, "Tcp", name) {
+            scalar(tcp_oid, number, Value::Counter64(value as u64));
+        }
+    }
+
+    let udp = |name: &str| match (counter(&counters, "Udp", name), counter(&counters, "Udp6", name)) {
+        (None, None) => None,
+        (v4, v6)     => Some(v4.unwrap_or(0) as u64 + v6.unwrap_or(0) as u64)
+    };
+    for &(number, name) in UDP_SCALARS {
+        if let Some(value) = udp(name) {
+            scalar(udp_oid, number, Value::Counter32(value));
+        }
+    }
+    for &(number, name) in UDP_HC_SCALARS {
+        if let Some(value) = udp(name) {
+            scalar(udp_oid, number, Value::Counter64(value));
+        }
+    }
+
+    let mut system_stats_table = Table::new(&format!("{}.{}", ip_oid, SYSTEM_STATS_ENTRY), SYSTEM_STATS_COLUMNS);
+    for &(version, group) in &[(IPV4, "Ip"), (IPV6, "Ip6")] {
+        // No IPv6 without snmp6
+        if !counters.contains_key(group) {
+            continue;
+        }
+        set_ip_stats(&mut system_stats_table.row(version), "ipSystemStats", &counters, version);
+    }
+    system_stats_table.insert_into(values);
+
+    let mut icmp_stats_table = Table::new(&format!("{}.{}", icmp_oid, ICMP_STATS_ENTRY), ICMP_STATS_COLUMNS);
+    for &(version, group) in &[(IPV4, "Icmp"), (IPV6, "Icmp6")] {
+        let stats = ["InMsgs", "InErrors", "OutMsgs", "OutErrors"].iter()
+            .map(|name| counter(&counters, group, name))
+            .collect::<Option<Vec<i64>>>();
+        if let Some(stats) = stats {
+            icmp_stats_table.row(version)
+                .set("icmpStatsInMsgs",    Value::Counter32(stats[0] as u64))
+                .set("icmpStatsInErrors",  Value::Counter32(stats[1] as u64))
+                .set("icmpStatsOutMsgs",   Value::Counter32(stats[2] as u64))
+                .set("icmpStatsOutErrors", Value::Counter32(stats[3] as u64));
+        }
+    }
+    icmp_stats_table.insert_into(values);
+
+    let mut netstat_table = Table::from_def(&NETSTAT_TABLE);
+    let netstat = counters.iter()
+        .filter(|&(group, _)| group.ends_with("Ext"))
+        .flat_map(|(group, names)| names.iter().map(move |(name, &value)| (group, name, value)));
+    let udp_extra = counters.iter()
+        .filter(|&(group, _)| group.starts_with("Udp"))
+        .flat_map(|(group, names)| names.iter()
+            .filter(|&(name, _)| UDP_EXTRA.contains(&name.as_str()))
+            .map(move |(name, &value)| (group, name, value)));
+    for (group, name, value) in netstat.chain(udp_extra) {
+        netstat_table.row((group.as_str(), name.as_str()))
+            .set("suntNetstatValue", Value::Counter64(value as u64));
+    }
+    netstat_table.insert_into(values);
+}
+
+/**
+ * The ifIndex of a /proc/net/dev_snmp6 file, which its first line tells.
+ */
+fn dev_snmp6_index(text: &str) -> Option<u32> {
+    text.lines()
+        .map(|line| line.split_whitespace())
+        .filter_map(|mut parts| match (parts.next(), parts.next()) {
+            (Some("ifIndex"), Some(index)) => index.parse().ok(),
+            _                              => None
+        })
+        .next()
+}
+
+/**
+ * Export the ipIfStatsTable for the interfaces in the ifTable. Linux only
+ * counts IPv6 per interface, in /proc/net/dev_snmp6.
+ */
+pub fn get_interface_stats(values: &mut BTreeMap<OID, Value>, host: &Host, interfaces: &IncludedInterfaces, mib2_oid: &str) {
+    let included = interfaces.get(host);
+    let dir = PathBuf::from("/proc/net/dev_snmp6");
+    let ifnames = fs::read_dir(host.dir(&dir))
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<String>>())
+        .unwrap_or_default();
+
+    let mut if_stats_table = Table::new(&format!("{}.{}.{}", mib2_oid, IP_GROUP, IF_STATS_ENTRY), IF_STATS_COLUMNS);
+    for ifname in ifnames {
+        let text = match fs::read_to_string(host.path(dir.join(&ifname))) {
+            Ok(text) => text,
+            Err(_)   => continue
+        };
+        let index = match dev_snmp6_index(&text) {
+            Some(index) if included.contains(&index) => index,
+            _                                        => continue
+        };
+        let mut counters = Counters::new();
+        parse_snmp6(text.as_bytes(), &mut counters);
+        set_ip_stats(&mut if_stats_table.row((IPV6, index)), "ipIfStats", &counters, IPV6);
+    }
+    if_stats_table.insert_into(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_lines() {
+        let text = "Tcp: RtoAlgorithm RtoMin RtoMax MaxConn ActiveOpens\n\
+                    Tcp: 1 200 120000 -1 230\n\
+                    Udp: InDatagrams NoPorts\n\
+                    Udp: 136 18446744073709551615\n";
+        let mut counters = Counters::new();
+        parse_group_lines(text.as_bytes(), &mut counters);
+        assert_eq!(counter(&counters, "Tcp", "MaxConn"), Some(-1));
+        assert_eq!(counter(&counters, "Tcp", "ActiveOpens"), Some(230));
+        assert_eq!(counter(&counters, "Udp", "NoPorts").map(|value| value as u64), Some(u64::MAX));
+
+        parse_snmp6("Ip6InReceives                   \t5\nIcmp6InType133 2\nUdpLite6InErrors 1\n".as_bytes(), &mut counters);
+        assert_eq!(counter(&counters, "Ip6", "InReceives"), Some(5));
+        assert_eq!(counter(&counters, "Icmp6", "InType133"), Some(2));
+        assert_eq!(counter(&counters, "UdpLite6", "InErrors"), Some(1));
+    }
+
+    #[test]
+    fn dev_snmp6() {
+        assert_eq!(dev_snmp6_index("ifIndex                         \t7\nIp6InReceives 5\n"), Some(7));
+        assert_eq!(dev_snmp6_index("Ip6InReceives 5\n"), None);
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::path::Path;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use value::{Value,str_from_file};
+use oid::OID;
+use table::{Table,Column,TableDef};
+use table::Syntax::*;
+use host::Host;
+
+// Entries of the tables get_sockets fills, relative to mib-2
+const TCP_CONNECTION_ENTRY: &str = "6.19.1";
+const TCP_LISTENER_ENTRY:   &str = "6.20.1";
+const UDP_ENDPOINT_ENTRY:   &str = "7.7.1";
+
+// The index columns of these tables are not-accessible, so each has only a
+// state and the ID of the process owning the socket.
+const TCP_CONNECTION_COLUMNS: &[Column] = &[
+    Column::new(7, "tcpConnectionState",   Integer),
+    Column::new(8, "tcpConnectionProcess", Gauge32),
+];
+
+const TCP_LISTENER_COLUMNS: &[Column] = &[
+    Column::new(4, "tcpListenerProcess", Gauge32),
+];
+
+const UDP_ENDPOINT_COLUMNS: &[Column] = &[
+    Column::new(8, "udpEndpointProcess", Gauge32),
+];
+
+const PROCESS_COLUMNS: &[Column] = &[
+    Column::new(1, "suntSocketProcessId",   Integer).describe("Process ID, as in tcpConnectionProcess and friends."),
+    Column::new(2, "suntSocketProcessName", OctetString).describe("Name of the process' executable, e.g. sshd, as `ps -o comm` shows it."),
+];
+
+pub const PROCESS_TABLE: TableDef = TableDef {
+    name:        "suntSocketProcessTable",
+    entry_oid:   "1.3.6.1.4.1.2147483647.1.6.1",
+    description: "Processes owning the sockets in the tcpConnectionTable, tcpListenerTable and udpEndpointTable, \
+                  indexed by the process ID these tables list.",
+    index:       &["suntSocketProcessId"],
+    columns:     PROCESS_COLUMNS,
+};
+
+/// InetAddressType
+const INET_IPV4: u32 = 1;
+const INET_IPV6: u32 = 2;
+
+/// TCP states as /proc/net/tcp calls them
+const TCP_ESTABLISHED: u8 = 0x01;
+const TCP_SYN_SENT:    u8 = 0x02;
+const TCP_SYN_RECV:    u8 = 0x03;
+const TCP_FIN_WAIT1:   u8 = 0x04;
+const TCP_FIN_WAIT2:   u8 = 0x05;
+const TCP_TIME_WAIT:   u8 = 0x06;
+const TCP_CLOSE:       u8 = 0x07;
+const TCP_CLOSE_WAIT:  u8 = 0x08;
+const TCP_LAST_ACK:    u8 = 0x09;
+const TCP_LISTEN:      u8 = 0x0A;
+const TCP_CLOSING:     u8 = 0x0B;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Socket {
+    local:       Vec<u8>,
+    local_port:  u32,
+    remote:      Vec<u8>,
+    remote_port: u32,
+    state:       u8,
+    inode:       u64,
+}
+
+/**
+ * Addresses in /proc/net/{tcp,udp}{,6} are __be32 words, each printed as a host integer.
+ */
+fn parse_proc_address(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(8) {
+        return None;
+    }
+    let mut address = Vec::new();
+    for pos in (0..hex.len()).step_by(8) {
+        let word = u32::from_str_radix(&hex[pos..pos + 8], 16).ok()?;
+        address.extend_from_slice(&word.to_ne_bytes());
+    }
+    Some(address)
+}
+
+/**
+ * An address and port like 0100007F:0016.
+ */
+fn parse_endpoint(text: &str) -> Option<(Vec<u8>, u32)> {
+    let mut parts = text.splitn(2, ':');
+    match (parts.next().and_then(parse_proc_address), parts.next().and_then(|port| u32::from_str_radix(port, 16).ok())) {
+        (Some(address), Some(port)) => Some((address, port)),
+        _                           => None
+    }
+}
+
+/**
+ * Parse /proc/net/tcp, /proc/net/udp or their IPv6 counterparts.
+ */
+fn parse_sockets<R: BufRead>(reader: R) -> Vec<Socket> {
+    let mut sockets = Vec::new();
+    for line in reader.lines().skip(1) {
+        let line = match line {
+            Ok(line) => line,
+            Err(_)   => break
+        };
+        // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode ...
+        let parts = line.split_whitespace().collect::<Vec<&str>>();
+        if parts.len() < 10 {
+            continue;
+        }
+        let fields = (parse_endpoint(parts[1]), parse_endpoint(parts[2]), u8::from_str_radix(parts[3], 16), parts[9].parse::<u64>());
+        if let (Some((local, local_port)), Some((remote, remote_port)), Ok(state), Ok(inode)) = fields {
+            sockets.push(Socket { local, local_port, remote, remote_port, state, inode });
+        }
+    }
+    sockets
+}
+
+fn read_sockets(host: &Host, protocol: &str) -> Vec<Socket> {
+    let mut sockets = Vec::new();
+    for name in &[protocol.to_owned(), format!("{}6", protocol)] {
+        if let Ok(file) = File::open(host.path(Path::new("/proc/net").join(name))) {
+            sockets.extend(parse_sockets(BufReader::new(file)));
+        }
+    }
+    sockets
+}
+
+/**
+ * The inode of a socket from the target of a file descriptor link, e.g. socket:[18231].
+ */
+fn socket_inode(target: &Path) -> Option<u64> {
+    let target = target.to_str()?;
+    if target.starts_with("socket:[") && target.ends_with(']') {
+        target["socket:[".len()..target.len() - 1].parse().ok()
+    }
+    else {
+        None
+    }
+}
+
+/**
+ * Which process owns each socket, by inode. Sockets that several processes
+ * share, e.g. after a fork, go to the lowest process ID, usually the parent.
+ * Without root, only the agent's own processes can be looked into.
+ */
+fn socket_owners(host: &Host) -> BTreeMap<u64, u32> {
+    // Listed and read without recording, so that captures hold the sockets
+    // rather than every file the processes have open.
+    let mut pids = host.read_dir_raw("/proc")
+        .map(|entries| entries.iter()
+            .filter_map(|entry| entry.file_name().and_then(|name| name.to_str()).and_then(|name| name.parse::<u32>().ok()))
+            .collect::<Vec<u32>>())
+        .unwrap_or_default();
+    pids.sort();
+
+    let mut owners = BTreeMap::new();
+    for pid in pids {
+        for fd in host.read_dir_raw(format!("/proc/{}/fd", pid)).unwrap_or_default() {
+            if let Some(inode) = host.read_link_raw(&fd).ok().and_then(|target| socket_inode(&target)) {
+                host.note_path(&fd);
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    owners
+}
+
+/// When the owners of sockets were last looked up, and what they were
+type OwnerCache = Arc<Mutex<Option<(Instant, Arc<BTreeMap<u64, u32>>)>>>;
+
+/**
+ * The owners of sockets, shared by the collectors of the socket tables and
+ * the suntSocketProcessTable, so that all processes are looked into only
+ * once per max_age.
+ */
+#[derive(Clone)]
+pub struct SocketOwners {
+    max_age: Duration,
+    cached:  OwnerCache,
+}
+
+impl SocketOwners {
+    pub fn new(max_age: Duration) -> SocketOwners {
+        SocketOwners { max_age, cached: Arc::new(Mutex::new(None)) }
+    }
+
+    /**
+     * Process IDs by socket inode.
+     */
+    pub fn get(&self, host: &Host) -> Arc<BTreeMap<u64, u32>> {
+        let mut cached = self.cached.lock().unwrap();
+        match *cached {
+            Some((updated, ref owners)) if updated.elapsed() < self.max_age => return owners.clone(),
+            _ => ()
+        }
+        let owners = Arc::new(socket_owners(host));
+        *cached = Some((Instant::now(), owners.clone()));
+        owners
+    }
+}
+
+/**
+ * tcpConnectionState of a socket state.
+ */
+fn tcp_state(state: u8) -> i64 {
+    match state {
+        TCP_CLOSE       => 1,
+        TCP_LISTEN      => 2,
+        TCP_SYN_SENT    => 3,
+        TCP_SYN_RECV    => 4,
+        TCP_ESTABLISHED => 5,
+        TCP_FIN_WAIT1   => 6,
+        TCP_FIN_WAIT2   => 7,
+        TCP_CLOSE_WAIT  => 8,
+        TCP_LAST_ACK    => 9,
+        TCP_CLOSING     => 10,
+        TCP_TIME_WAIT   => 11,
+        // deleteTCB(12) is only ever written
+        _               => 1
+    }
+}
+
+fn address_type(address: &[u8]) -> u32 {
+    if address.len() == 4 { INET_IPV4 } else { INET_IPV6 }
+}
+
+/**
+ * The OIDs of the tables get_sockets fills.
+ */
+pub fn socket_subtrees(mib2_oid: &str) -> Vec<String> {
+    [TCP_CONNECTION_ENTRY, TCP_LISTENER_ENTRY, UDP_ENDPOINT_ENTRY].iter()
+        .map(|entry| format!("{}.{}", mib2_oid, entry))
+        .collect()
+}
+
+/**
+ * Export the tcpConnectionTable, tcpListenerTable and udpEndpointTable, with
+ * the ID of the process owning each socket, or 0 if it is unknown. Without
+ * owners, processes are not looked into at all and every socket has 0.
+ */
+pub fn get_sockets(values: &mut BTreeMap<OID, Value>, host: &Host, owners: Option<&SocketOwners>, mib2_oid: &str) {
+    let owners = owners.map(|owners| owners.get(host)).unwrap_or_default();
+    let owner = |socket: &Socket| Value::Unsigned32(owners.get(&socket.inode).cloned().unwrap_or(0));
+
+    let mut connection_table = Table::new(&format!("{}.{}", mib2_oid, TCP_CONNECTION_ENTRY), TCP_CONNECTION_COLUMNS);
+    let mut listener_table = Table::new(&format!("{}.{}", mib2_oid, TCP_LISTENER_ENTRY), TCP_LISTENER_COLUMNS);
+    for socket in read_sockets(host, "tcp") {
+        let local = (address_type(&socket.local), &socket.local[..], socket.local_port);
+        if socket.state == TCP_LISTEN {
+            // With SO_REUSEPORT, several sockets can listen on the same port
+            if !listener_table.has_row(&local) {
+                listener_table.row(local).set("tcpListenerProcess", owner(&socket));
+            }
+        }
+        else {
+            let index = (local.0, local.1, local.2, address_type(&socket.remote), &socket.remote[..], socket.remote_port);
+            connection_table.row(index)
+                .set("tcpConnectionState",   Value::Integer(tcp_state(socket.state)))
+                .set("tcpConnectionProcess", owner(&socket));
+        }
+    }
+    connection_table.insert_into(values);
+    listener_table.insert_into(values);
+
+    let mut endpoint_table = Table::new(&format!("{}.{}", mib2_oid, UDP_ENDPOINT_ENTRY), UDP_ENDPOINT_COLUMNS);
+    for socket in read_sockets(host, "udp") {
+        // udpEndpointInstance tells apart sockets bound to the same addresses and ports
+        let index = |instance: u32| (address_type(&socket.local), &socket.local[..], socket.local_port,
+            address_type(&socket.remote), &socket.remote[..], socket.remote_port, instance);
+        let instance = (1..).find(|&instance| !endpoint_table.has_row(&index(instance))).unwrap();
+        endpoint_table.row(index(instance)).set("udpEndpointProcess", owner(&socket));
+    }
+    endpoint_table.insert_into(values);
+}
+
+/**
+ * Export the names of the processes owning sockets, which is something the
+ * TCP-MIB and UDP-MIB only know the IDs of.
+ */
+pub fn get_socket_processes(values: &mut BTreeMap<OID, Value>, host: &Host, owners: &SocketOwners) {
+    let mut process_table = Table::from_def(&PROCESS_TABLE);
+    let owners = owners.get(host);
+    let mut pids = owners.values().cloned().collect::<Vec<u32>>();
+    pids.sort();
+    pids.dedup();
+    for pid in pids {
+        if let Some(name) = str_from_file(&host.path(format!("/proc/{}/comm", pid))) {
+            process_table.row(pid).set("suntSocketProcessName", Value::OctetString(name));
+        }
+    }
+    process_table.insert_into(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_sockets() {
+        let text = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n   \
+                    0: 0100007F:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 18231 1 0 100 0 0 10 0\n";
+        let sockets = parse_sockets(text.as_bytes());
+        assert_eq!(sockets.len(), 1);
+        assert_eq!(sockets[0].local_port, 22);
+        assert_eq!(sockets[0].state, TCP_LISTEN);
+        assert_eq!(sockets[0].inode, 18231);
+        if cfg!(target_endian = "little") {
+            assert_eq!(sockets[0].local, vec![127, 0, 0, 1]);
+            assert_eq!(parse_proc_address("B80D012000002000FF0E973E01B2A1FE"),
+                Some(vec![0x20, 0x01, 0x0d, 0xb8, 0, 0x20, 0, 0, 0x3e, 0x97, 0x0e, 0xff, 0xfe, 0xa1, 0xb2, 0x01]));
+        }
+        assert_eq!(parse_proc_address("0100007"), None);
+    }
+
+    #[test]
+    fn inodes() {
+        assert_eq!(socket_inode(Path::new("socket:[18231]")), Some(18231));
+        assert_eq!(socket_inode(Path::new("pipe:[20415]")), None);
+        assert_eq!(socket_inode(Path::new("/dev/null")), None);
+    }
+}
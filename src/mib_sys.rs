@@ -1,11 +1,10 @@
 use std::collections::BTreeMap;
-use std::path::PathBuf;
-use uname;
-use value::{Value,u32_from_file};
+use value::{Value,str_from_file};
 use oid::OID;
+use host::Host;
 
-pub fn get_system(values: &mut BTreeMap<OID, Value>, base_oid: &str) {
-    if let Ok(info) = uname::uname() {
+pub fn get_system(values: &mut BTreeMap<OID, Value>, host: &Host, base_oid: &str) {
+    if let Some(info) = host.uname() {
         values.insert(
             OID::from_parts(&[base_oid, "1.0"]),
             Value::OctetString(format!(
@@ -27,6 +26,20 @@ pub fn get_system(values: &mut BTreeMap<OID, Value>, base_oid: &str) {
     values.insert(OID::from_parts(&[base_oid, "4.0"]), Value::OctetStr("sunt v0.0.1"));
     values.insert(OID::from_parts(&[base_oid, "6.0"]), Value::OctetStr("the cloud, probably"));
 
-    let uptime = u32_from_file(&PathBuf::from("/proc/uptime")).unwrap();
-    values.insert(OID::from_parts(&[base_oid, "3.0"]), Value::Timeticks(uptime));
+    if let Some(uptime) = uptime(host) {
+        values.insert(OID::from_parts(&[base_oid, "3.0"]), Value::Timeticks(uptime));
+    }
+}
+
+/**
+ * Time since boot in hundredths of a second, the unit of Timeticks, from
+ * /proc/uptime like "1234567.89 9876543.21". Wraps around after 497 days.
+ */
+pub fn uptime(host: &Host) -> Option<u32> {
+    let seconds = str_from_file(&host.path("/proc/uptime"))?
+        .split_whitespace()
+        .next()?
+        .parse::<f64>()
+        .ok()?;
+    Some((seconds * 100.0).round() as u64 as u32)
 }
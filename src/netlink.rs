@@ -0,0 +1,788 @@
+use std::io;
+use std::mem;
+use libc;
+use errors::*;
+
+const NETLINK_ROUTE: libc::c_int = 0;
+
+// Message types and flags from linux/netlink.h and linux/rtnetlink.h
+const NLMSG_ERROR:   u16 = 2;
+const NLMSG_DONE:    u16 = 3;
+const NLM_F_REQUEST: u16 = 0x0001;
+const NLM_F_DUMP:    u16 = 0x0300;
+pub const RTM_NEWLINK: u16 = 16;
+pub const RTM_GETLINK: u16 = 18;
+pub const RTM_NEWADDR: u16 = 20;
+pub const RTM_GETADDR: u16 = 22;
+pub const RTM_NEWROUTE: u16 = 24;
+pub const RTM_GETROUTE: u16 = 26;
+pub const RTM_NEWNEIGH: u16 = 28;
+pub const RTM_GETNEIGH: u16 = 30;
+
+const NLMSG_HDRLEN:  usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const IFADDRMSG_LEN: usize = 8;
+const RTMSG_LEN:     usize = 12;
+const RTNEXTHOP_LEN: usize = 8;
+const NDMSG_LEN:     usize = 12;
+const RTA_HDRLEN:    usize = 4;
+/// Attribute types may have NLA_F_NESTED and NLA_F_NET_BYTEORDER set
+const NLA_TYPE_MASK: u16 = 0x3fff;
+
+// IFLA_* from linux/if_link.h
+const IFLA_ADDRESS:         u16 = 1;
+const IFLA_IFNAME:          u16 = 3;
+const IFLA_MTU:             u16 = 4;
+const IFLA_LINK:            u16 = 5;
+const IFLA_MASTER:          u16 = 10;
+const IFLA_OPERSTATE:       u16 = 16;
+const IFLA_LINKINFO:        u16 = 18;
+const IFLA_IFALIAS:         u16 = 20;
+const IFLA_STATS64:         u16 = 23;
+const IFLA_CARRIER_CHANGES: u16 = 35;
+const IFLA_LINK_NETNSID:    u16 = 37;
+const IFLA_INFO_KIND:       u16 = 1;
+
+// IFA_* from linux/if_addr.h
+const IFA_ADDRESS:   u16 = 1;
+const IFA_LOCAL:     u16 = 2;
+const IFA_BROADCAST: u16 = 4;
+const IFA_CACHEINFO: u16 = 6;
+const IFA_FLAGS:     u16 = 8;
+
+// IFA_F_* address flags
+pub const IFA_F_TEMPORARY:  u32 = 0x01;
+pub const IFA_F_OPTIMISTIC: u32 = 0x04;
+pub const IFA_F_DADFAILED:  u32 = 0x08;
+pub const IFA_F_DEPRECATED: u32 = 0x20;
+pub const IFA_F_TENTATIVE:  u32 = 0x40;
+pub const IFA_F_PERMANENT:  u32 = 0x80;
+
+/// Lifetime of addresses that do not expire
+pub const INFINITY_LIFE_TIME: u32 = 0xffffffff;
+
+// RTA_* from linux/rtnetlink.h
+const RTA_DST:       u16 = 1;
+const RTA_OIF:       u16 = 4;
+const RTA_GATEWAY:   u16 = 5;
+const RTA_PRIORITY:  u16 = 6;
+const RTA_PREFSRC:   u16 = 7;
+const RTA_MULTIPATH: u16 = 9;
+const RTA_TABLE:     u16 = 15;
+
+pub const RT_TABLE_MAIN: u32 = 254;
+
+// RTN_* route types
+pub const RTN_UNICAST:     u8 = 1;
+pub const RTN_BLACKHOLE:   u8 = 6;
+pub const RTN_UNREACHABLE: u8 = 7;
+pub const RTN_PROHIBIT:    u8 = 8;
+
+// RTPROT_* of the daemons that add routes, as far as the MIBs know them
+pub const RTPROT_UNSPEC:   u8 = 0;
+pub const RTPROT_REDIRECT: u8 = 1;
+pub const RTPROT_KERNEL:   u8 = 2;
+pub const RTPROT_BOOT:     u8 = 3;
+pub const RTPROT_STATIC:   u8 = 4;
+pub const RTPROT_RA:       u8 = 9;
+pub const RTPROT_BGP:      u8 = 186;
+pub const RTPROT_ISIS:     u8 = 187;
+pub const RTPROT_OSPF:     u8 = 188;
+pub const RTPROT_RIP:      u8 = 189;
+pub const RTPROT_EIGRP:    u8 = 192;
+
+// NDA_* from linux/neighbour.h
+const NDA_DST:       u16 = 1;
+const NDA_LLADDR:    u16 = 2;
+const NDA_CACHEINFO: u16 = 3;
+
+// NUD_* neighbor states
+pub const NUD_INCOMPLETE: u16 = 0x01;
+pub const NUD_REACHABLE:  u16 = 0x02;
+pub const NUD_STALE:      u16 = 0x04;
+pub const NUD_DELAY:      u16 = 0x08;
+pub const NUD_PROBE:      u16 = 0x10;
+pub const NUD_FAILED:     u16 = 0x20;
+pub const NUD_NOARP:      u16 = 0x40;
+pub const NUD_PERMANENT:  u16 = 0x80;
+
+/**
+ * The parts of struct rtnl_link_stats64 the collectors care about.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub rx_packets:   u64,
+    pub tx_packets:   u64,
+    pub rx_bytes:     u64,
+    pub tx_bytes:     u64,
+    pub rx_errors:    u64,
+    pub tx_errors:    u64,
+    /// Includes rx_missed_errors, like /proc/net/dev does
+    pub rx_dropped:   u64,
+    pub tx_dropped:   u64,
+    pub multicast:    u64,
+    pub rx_nohandler: u64,
+
+    // What rx_errors and tx_errors consist of, as far as the driver knows
+    pub collisions:        u64,
+    pub rx_length_errors:  u64,
+    pub rx_over_errors:    u64,
+    pub rx_crc_errors:     u64,
+    pub rx_frame_errors:   u64,
+    pub rx_fifo_errors:    u64,
+    pub tx_aborted_errors: u64,
+    pub tx_carrier_errors: u64,
+    pub tx_fifo_errors:    u64,
+    pub tx_window_errors:  u64,
+}
+
+/**
+ * A network interface as the kernel describes it in RTM_NEWLINK.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Link {
+    pub index:           u32,
+    pub name:            String,
+    /// ARPHRD_* hardware type
+    pub if_type:         u16,
+    /// IFF_* flags
+    pub flags:           u32,
+    pub mtu:             Option<u32>,
+    /// IF_OPER_* as per RFC 2863
+    pub operstate:       Option<u8>,
+    pub address:         Option<Vec<u8>>,
+    /// Interface this one is linked to, unless that one is in another namespace
+    pub link:            Option<u32>,
+    /// Bond or bridge this one is enslaved to
+    pub master:          Option<u32>,
+    pub alias:           Option<String>,
+    pub carrier_changes: Option<u32>,
+    /// Driver that created a virtual interface, e.g. bond or vlan
+    pub kind:            Option<String>,
+    pub stats:           Option<LinkStats>,
+}
+
+/**
+ * An IPv4 or IPv6 address of an interface as the kernel describes it in RTM_NEWADDR.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Address {
+    /// AF_INET or AF_INET6
+    pub family:             u8,
+    pub index:              u32,
+    /// 4 or 16 bytes, depending on the family
+    pub address:            Vec<u8>,
+    pub prefix_len:         u8,
+    pub broadcast:          Option<Vec<u8>>,
+    /// IFA_F_* flags
+    pub flags:              u32,
+    /// RT_SCOPE_*, e.g. 253 for link-local addresses
+    pub scope:              u8,
+    /// Seconds until the address is deprecated and removed, or INFINITY_LIFE_TIME
+    pub preferred_lifetime: u32,
+    pub valid_lifetime:     u32,
+    /// When the address was added and last changed, in hundredths of a second since boot
+    pub created:            u32,
+    pub updated:            u32,
+}
+
+/**
+ * Where a route sends packets: Out of an interface, possibly via a gateway.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NextHop {
+    pub index:   u32,
+    pub gateway: Option<Vec<u8>>,
+}
+
+/**
+ * A route as the kernel describes it in RTM_NEWROUTE.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Route {
+    /// AF_INET or AF_INET6
+    pub family:     u8,
+    /// All zeros for default routes
+    pub dst:        Vec<u8>,
+    pub dst_len:    u8,
+    pub tos:        u8,
+    pub table:      u32,
+    /// RTPROT_*
+    pub protocol:   u8,
+    /// RTN_*
+    pub route_type: u8,
+    /// The metric
+    pub priority:   u32,
+    pub prefsrc:    Option<Vec<u8>>,
+    /// More than one for multipath routes
+    pub nexthops:   Vec<NextHop>,
+}
+
+/**
+ * An entry of the ARP or NDP cache as the kernel describes it in RTM_NEWNEIGH.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Neighbor {
+    /// AF_INET or AF_INET6, or AF_BRIDGE for the forwarding databases of bridges
+    pub family:  u8,
+    pub index:   u32,
+    /// NUD_*
+    pub state:   u16,
+    pub address: Vec<u8>,
+    /// The MAC address, unless resolving it failed
+    pub lladdr:  Option<Vec<u8>>,
+    /// How long ago the entry was last updated, in hundredths of a second
+    pub updated: Option<u32>,
+}
+
+fn u16_at(buf: &[u8], pos: usize) -> u16 {
+    u16::from_ne_bytes([buf[pos], buf[pos + 1]])
+}
+
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_ne_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]])
+}
+
+fn u64_at(buf: &[u8], pos: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[pos..pos + 8]);
+    u64::from_ne_bytes(bytes)
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/**
+ * A string attribute, which the kernel terminates with a NUL.
+ */
+fn string(payload: &[u8]) -> String {
+    String::from_utf8_lossy(payload.split(|&byte| byte == 0).next().unwrap_or(&[])).into_owned()
+}
+
+/**
+ * The (type, payload) pairs of a sequence of route attributes.
+ */
+pub fn attributes(mut buf: &[u8]) -> Result<Vec<(u16, &[u8])>> {
+    let mut attrs = Vec::new();
+    while buf.len() >= RTA_HDRLEN {
+        let len = u16_at(buf, 0) as usize;
+        if len < RTA_HDRLEN || len > buf.len() {
+            bail!("Truncated netlink attribute");
+        }
+        attrs.push((u16_at(buf, 2) & NLA_TYPE_MASK, &buf[RTA_HDRLEN..len]));
+        buf = &buf[align(len).min(buf.len())..];
+    }
+    Ok(attrs)
+}
+
+/**
+ * The (type, payload) pairs of the messages of a dump, up to NLMSG_DONE.
+ */
+pub fn messages(mut buf: &[u8]) -> Result<Vec<(u16, &[u8])>> {
+    let mut msgs = Vec::new();
+    while buf.len() >= NLMSG_HDRLEN {
+        let len = u32_at(buf, 0) as usize;
+        if len < NLMSG_HDRLEN || len > buf.len() {
+            bail!("Truncated netlink message");
+        }
+        match u16_at(buf, 4) {
+            NLMSG_DONE  => break,
+            NLMSG_ERROR => {
+                let errno = if len >= NLMSG_HDRLEN + 4 { -(u32_at(buf, NLMSG_HDRLEN) as i32) } else { 0 };
+                return Err(io::Error::from_raw_os_error(errno)).chain_err(|| "Netlink request failed");
+            }
+            msg_type => msgs.push((msg_type, &buf[NLMSG_HDRLEN..len]))
+        }
+        buf = &buf[align(len).min(buf.len())..];
+    }
+    Ok(msgs)
+}
+
+/**
+ * Parse the RTM_NEWLINK messages of an RTM_GETLINK dump.
+ */
+pub fn parse_links(dump: &[u8]) -> Result<Vec<Link>> {
+    let mut links = Vec::new();
+    for (msg_type, payload) in messages(dump)? {
+        if msg_type != RTM_NEWLINK {
+            continue;
+        }
+        if payload.len() < IFINFOMSG_LEN {
+            bail!("Truncated ifinfomsg");
+        }
+        // struct ifinfomsg: family, pad, type, index, flags, change
+        let mut link = Link {
+            if_type: u16_at(payload, 2),
+            index:   u32_at(payload, 4),
+            flags:   u32_at(payload, 8),
+            ..Link::default()
+        };
+        let mut other_netns = false;
+        for (attr, value) in attributes(&payload[IFINFOMSG_LEN..])? {
+            match attr {
+                IFLA_ADDRESS => link.address = Some(value.to_vec()),
+                IFLA_IFNAME  => link.name = string(value),
+                IFLA_IFALIAS => link.alias = Some(string(value)),
+                IFLA_MTU            if value.len() >= 4 => link.mtu = Some(u32_at(value, 0)),
+                IFLA_LINK           if value.len() >= 4 => link.link = Some(u32_at(value, 0)),
+                IFLA_MASTER         if value.len() >= 4 => link.master = Some(u32_at(value, 0)),
+                IFLA_CARRIER_CHANGES if value.len() >= 4 => link.carrier_changes = Some(u32_at(value, 0)),
+                IFLA_OPERSTATE      if !value.is_empty() => link.operstate = Some(value[0]),
+                IFLA_LINK_NETNSID => other_netns = true,
+                IFLA_LINKINFO => {
+                    for (info, info_value) in attributes(value)? {
+                        if info == IFLA_INFO_KIND {
+                            link.kind = Some(string(info_value));
+                        }
+                    }
+                }
+                // Older kernels lack rx_nohandler at the end
+                IFLA_STATS64 if value.len() >= 23 * 8 => {
+                    let stat = |idx: usize| u64_at(value, idx * 8);
+                    link.stats = Some(LinkStats {
+                        rx_packets:   stat(0),
+                        tx_packets:   stat(1),
+                        rx_bytes:     stat(2),
+                        tx_bytes:     stat(3),
+                        rx_errors:    stat(4),
+                        tx_errors:    stat(5),
+                        rx_dropped:   stat(6) + stat(15),
+                        tx_dropped:   stat(7),
+                        multicast:    stat(8),
+                        rx_nohandler: if value.len() >= 24 * 8 { stat(23) } else { 0 },
+
+                        collisions:        stat(9),
+                        rx_length_errors:  stat(10),
+                        rx_over_errors:    stat(11),
+                        rx_crc_errors:     stat(12),
+                        rx_frame_errors:   stat(13),
+                        rx_fifo_errors:    stat(14),
+                        tx_aborted_errors: stat(16),
+                        tx_carrier_errors: stat(17),
+                        tx_fifo_errors:    stat(18),
+                        tx_window_errors:  stat(20),
+                    });
+                }
+                _ => ()
+            }
+        }
+        if other_netns {
+            // ifindex of another namespace, meaningless here
+            link.link = None;
+        }
+        links.push(link);
+    }
+    Ok(links)
+}
+
+/**
+ * Parse the RTM_NEWADDR messages of an RTM_GETADDR dump.
+ */
+pub fn parse_addresses(dump: &[u8]) -> Result<Vec<Address>> {
+    let mut addresses = Vec::new();
+    for (msg_type, payload) in messages(dump)? {
+        if msg_type != RTM_NEWADDR {
+            continue;
+        }
+        if payload.len() < IFADDRMSG_LEN {
+            bail!("Truncated ifaddrmsg");
+        }
+        // struct ifaddrmsg: family, prefixlen, flags, scope, index
+        let mut address = Address {
+            family:             payload[0],
+            prefix_len:         payload[1],
+            flags:              payload[2] as u32,
+            scope:              payload[3],
+            index:              u32_at(payload, 4),
+            preferred_lifetime: INFINITY_LIFE_TIME,
+            valid_lifetime:     INFINITY_LIFE_TIME,
+            ..Address::default()
+        };
+        let mut local = None;
+        for (attr, value) in attributes(&payload[IFADDRMSG_LEN..])? {
+            match attr {
+                IFA_ADDRESS   => address.address = value.to_vec(),
+                // On point-to-point links IFA_ADDRESS is the peer's address
+                IFA_LOCAL     => local = Some(value.to_vec()),
+                IFA_BROADCAST => address.broadcast = Some(value.to_vec()),
+                // The 8 bits in ifaddrmsg are not enough for all flags
+                IFA_FLAGS     if value.len() >= 4 => address.flags = u32_at(value, 0),
+                // struct ifa_cacheinfo: preferred, valid, cstamp, tstamp
+                IFA_CACHEINFO if value.len() >= 16 => {
+                    address.preferred_lifetime = u32_at(value, 0);
+                    address.valid_lifetime = u32_at(value, 4);
+                    address.created = u32_at(value, 8);
+                    address.updated = u32_at(value, 12);
+                }
+                _ => ()
+            }
+        }
+        if let Some(local) = local {
+            address.address = local;
+        }
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+/**
+ * The next hops of an RTA_MULTIPATH attribute, an array of struct rtnexthop
+ * each followed by its own attributes.
+ */
+fn parse_multipath(mut buf: &[u8]) -> Result<Vec<NextHop>> {
+    let mut nexthops = Vec::new();
+    while buf.len() >= RTNEXTHOP_LEN {
+        // struct rtnexthop: len, flags, hops, ifindex
+        let len = u16_at(buf, 0) as usize;
+        if len < RTNEXTHOP_LEN || len > buf.len() {
+            bail!("Truncated rtnexthop");
+        }
+        let mut nexthop = NextHop { index: u32_at(buf, 4), gateway: None };
+        for (attr, value) in attributes(&buf[RTNEXTHOP_LEN..len])? {
+            if attr == RTA_GATEWAY {
+                nexthop.gateway = Some(value.to_vec());
+            }
+        }
+        nexthops.push(nexthop);
+        buf = &buf[align(len).min(buf.len())..];
+    }
+    Ok(nexthops)
+}
+
+/**
+ * Parse the RTM_NEWROUTE messages of an RTM_GETROUTE dump.
+ */
+pub fn parse_routes(dump: &[u8]) -> Result<Vec<Route>> {
+    let mut routes = Vec::new();
+    for (msg_type, payload) in messages(dump)? {
+        if msg_type != RTM_NEWROUTE {
+            continue;
+        }
+        if payload.len() < RTMSG_LEN {
+            bail!("Truncated rtmsg");
+        }
+        // struct rtmsg: family, dst_len, src_len, tos, table, protocol, scope, type, flags
+        let family = payload[0];
+        let mut route = Route {
+            family,
+            dst:        vec![0; if family as libc::c_int == libc::AF_INET6 { 16 } else { 4 }],
+            dst_len:    payload[1],
+            tos:        payload[3],
+            table:      payload[4] as u32,
+            protocol:   payload[5],
+            route_type: payload[7],
+            ..Route::default()
+        };
+        let mut nexthop = NextHop::default();
+        let mut multipath = None;
+        for (attr, value) in attributes(&payload[RTMSG_LEN..])? {
+            match attr {
+                RTA_DST       => route.dst = value.to_vec(),
+                RTA_GATEWAY   => nexthop.gateway = Some(value.to_vec()),
+                RTA_PREFSRC   => route.prefsrc = Some(value.to_vec()),
+                RTA_MULTIPATH => multipath = Some(parse_multipath(value)?),
+                RTA_OIF       if value.len() >= 4 => nexthop.index = u32_at(value, 0),
+                RTA_PRIORITY  if value.len() >= 4 => route.priority = u32_at(value, 0),
+                // The 8 bits in rtmsg are not enough for all table IDs
+                RTA_TABLE     if value.len() >= 4 => route.table = u32_at(value, 0),
+                _ => ()
+            }
+        }
+        route.nexthops = multipath.unwrap_or_else(|| vec![nexthop]);
+        routes.push(route);
+    }
+    Ok(routes)
+}
+
+/**
+ * Parse the RTM_NEWNEIGH messages of an RTM_GETNEIGH dump.
+ */
+pub fn parse_neighbors(dump: &[u8]) -> Result<Vec<Neighbor>> {
+    let mut neighbors = Vec::new();
+    for (msg_type, payload) in messages(dump)? {
+        if msg_type != RTM_NEWNEIGH {
+            continue;
+        }
+        if payload.len() < NDMSG_LEN {
+            bail!("Truncated ndmsg");
+        }
+        // struct ndmsg: family, 3 bytes padding, ifindex, state, flags, type
+        let mut neighbor = Neighbor {
+            family: payload[0],
+            index:  u32_at(payload, 4),
+            state:  u16_at(payload, 8),
+            ..Neighbor::default()
+        };
+        for (attr, value) in attributes(&payload[NDMSG_LEN..])? {
+            match attr {
+                NDA_DST       => neighbor.address = value.to_vec(),
+                NDA_LLADDR    => neighbor.lladdr = Some(value.to_vec()),
+                // struct nda_cacheinfo: confirmed, used, updated, refcnt
+                NDA_CACHEINFO if value.len() >= 12 => neighbor.updated = Some(u32_at(value, 8)),
+                _ => ()
+            }
+        }
+        neighbors.push(neighbor);
+    }
+    Ok(neighbors)
+}
+
+struct Socket(libc::c_int);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0); }
+    }
+}
+
+/**
+ * Send a dump request for msg_type with the given family header and return
+ * the raw replies, which can be parsed with messages().
+ */
+pub fn dump(msg_type: u16, header: &[u8]) -> io::Result<Vec<u8>> {
+    let sock = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, NETLINK_ROUTE) };
+    if sock < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let sock = Socket(sock);
+
+    let mut request = Vec::new();
+    request.extend_from_slice(&((NLMSG_HDRLEN + header.len()) as u32).to_ne_bytes());
+    request.extend_from_slice(&msg_type.to_ne_bytes());
+    request.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+    request.extend_from_slice(&1u32.to_ne_bytes());   // seq
+    request.extend_from_slice(&0u32.to_ne_bytes());   // pid, the kernel fills it in
+    request.extend_from_slice(header);
+
+    let sent = unsafe {
+        let mut kernel: libc::sockaddr_nl = mem::zeroed();
+        kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        libc::sendto(sock.0, request.as_ptr() as *const libc::c_void, request.len(), 0,
+            &kernel as *const libc::sockaddr_nl as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t)
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut replies = Vec::new();
+    let mut buf = vec![0u8; 32768];
+    loop {
+        let received = unsafe { libc::recv(sock.0, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if received < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        let chunk = &buf[..received as usize];
+        replies.extend_from_slice(chunk);
+        // Every datagram holds whole messages, the last one of the dump is NLMSG_DONE
+        let mut pos = 0;
+        let mut done = received == 0;
+        while pos + NLMSG_HDRLEN <= chunk.len() {
+            let msg_type = u16_at(chunk, pos + 4);
+            if msg_type == NLMSG_DONE || msg_type == NLMSG_ERROR {
+                done = true;
+            }
+            pos += align((u32_at(chunk, pos) as usize).max(NLMSG_HDRLEN));
+        }
+        if done {
+            return Ok(replies);
+        }
+    }
+}
+
+/**
+ * Ask the kernel for all network interfaces, see parse_links.
+ */
+pub fn dump_links() -> io::Result<Vec<u8>> {
+    // struct ifinfomsg with everything but the family (AF_UNSPEC) zeroed
+    dump(RTM_GETLINK, &[0; IFINFOMSG_LEN])
+}
+
+/**
+ * Ask the kernel for the addresses of all interfaces, see parse_addresses.
+ */
+pub fn dump_addresses() -> io::Result<Vec<u8>> {
+    // struct ifaddrmsg, AF_UNSPEC for both IPv4 and IPv6
+    dump(RTM_GETADDR, &[0; IFADDRMSG_LEN])
+}
+
+/**
+ * Ask the kernel for the routes of all tables, see parse_routes.
+ */
+pub fn dump_routes() -> io::Result<Vec<u8>> {
+    // struct rtmsg, AF_UNSPEC for both IPv4 and IPv6
+    dump(RTM_GETROUTE, &[0; RTMSG_LEN])
+}
+
+/**
+ * Ask the kernel for the ARP and NDP caches, see parse_neighbors.
+ */
+pub fn dump_neighbors() -> io::Result<Vec<u8>> {
+    // struct ndmsg, AF_UNSPEC for both IPv4 and IPv6
+    dump(RTM_GETNEIGH, &[0; NDMSG_LEN])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(attr_type: u16, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&((RTA_HDRLEN + payload.len()) as u16).to_ne_bytes());
+        buf.extend_from_slice(&attr_type.to_ne_bytes());
+        buf.extend_from_slice(payload);
+        buf.resize(align(buf.len()), 0);
+        buf
+    }
+
+    fn message(msg_type: u16, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&((NLMSG_HDRLEN + payload.len()) as u32).to_ne_bytes());
+        buf.extend_from_slice(&msg_type.to_ne_bytes());
+        buf.extend_from_slice(&[0; 10]);
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    #[test]
+    fn parse_link_dump() {
+        let mut ifinfo = vec![0, 0];
+        ifinfo.extend_from_slice(&1u16.to_ne_bytes());
+        ifinfo.extend_from_slice(&6u32.to_ne_bytes());
+        ifinfo.extend_from_slice(&0x1003u32.to_ne_bytes());
+        ifinfo.extend_from_slice(&0u32.to_ne_bytes());
+        ifinfo.extend(attr(IFLA_IFNAME, b"bond0.20\0"));
+        ifinfo.extend(attr(IFLA_MTU, &1500u32.to_ne_bytes()));
+        ifinfo.extend(attr(IFLA_LINK, &5u32.to_ne_bytes()));
+        ifinfo.extend(attr(IFLA_OPERSTATE, &[6]));
+        ifinfo.extend(attr(IFLA_LINKINFO | 0x8000, &attr(IFLA_INFO_KIND, b"vlan\0")));
+        let stats = (0..24u64).flat_map(|stat| stat.to_ne_bytes().to_vec()).collect::<Vec<u8>>();
+        ifinfo.extend(attr(IFLA_STATS64, &stats));
+
+        let mut dump = message(RTM_NEWLINK, &ifinfo);
+        dump.extend(message(NLMSG_DONE, &[0; 4]));
+        dump.extend(message(RTM_NEWLINK, &[0; 3]));
+
+        let links = parse_links(&dump).unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].name, "bond0.20");
+        assert_eq!((links[0].index, links[0].if_type, links[0].flags), (6, 1, 0x1003));
+        assert_eq!((links[0].mtu, links[0].link, links[0].operstate), (Some(1500), Some(5), Some(6)));
+        assert_eq!(links[0].kind, Some(String::from("vlan")));
+        let stats = links[0].stats.clone().unwrap();
+        assert_eq!((stats.rx_bytes, stats.rx_dropped, stats.rx_nohandler), (2, 6 + 15, 23));
+        assert_eq!((stats.rx_crc_errors, stats.tx_window_errors), (12, 20));
+
+        assert!(parse_links(&message(RTM_NEWLINK, &[0; 3])).is_err());
+        let mut error = message(NLMSG_ERROR, &(-1i32).to_ne_bytes());
+        error.extend_from_slice(&[0; 16]);
+        assert!(parse_links(&error).is_err());
+    }
+
+    #[test]
+    fn parse_address_dump() {
+        // 192.0.2.1/24 on a point-to-point link, so IFA_ADDRESS is the peer
+        let mut ifaddr = vec![libc::AF_INET as u8, 24, 0x80, 0];
+        ifaddr.extend_from_slice(&3u32.to_ne_bytes());
+        ifaddr.extend(attr(IFA_ADDRESS, &[192, 0, 2, 2]));
+        ifaddr.extend(attr(IFA_LOCAL, &[192, 0, 2, 1]));
+        ifaddr.extend(attr(IFA_FLAGS, &(IFA_F_PERMANENT | 0x200).to_ne_bytes()));
+        let mut dump = message(RTM_NEWADDR, &ifaddr);
+
+        // A SLAAC address that is about to go away
+        let mut ifaddr = vec![libc::AF_INET6 as u8, 64, IFA_F_DEPRECATED as u8, 0];
+        ifaddr.extend_from_slice(&2u32.to_ne_bytes());
+        ifaddr.extend(attr(IFA_ADDRESS, &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]));
+        let cacheinfo = [0u32, 300, 1200, 4500].iter().flat_map(|word| word.to_ne_bytes().to_vec()).collect::<Vec<u8>>();
+        ifaddr.extend(attr(IFA_CACHEINFO, &cacheinfo));
+        dump.extend(message(RTM_NEWADDR, &ifaddr));
+        dump.extend(message(NLMSG_DONE, &[0; 4]));
+
+        let addresses = parse_addresses(&dump).unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert_eq!((addresses[0].index, addresses[0].prefix_len), (3, 24));
+        assert_eq!(addresses[0].address, vec![192, 0, 2, 1]);
+        assert_eq!(addresses[0].flags, IFA_F_PERMANENT | 0x200);
+        assert_eq!(addresses[0].valid_lifetime, INFINITY_LIFE_TIME);
+        assert_eq!(addresses[1].flags, IFA_F_DEPRECATED);
+        assert_eq!((addresses[1].preferred_lifetime, addresses[1].valid_lifetime), (0, 300));
+        assert_eq!((addresses[1].created, addresses[1].updated), (1200, 4500));
+    }
+
+    #[test]
+    fn parse_route_dump() {
+        // default via 10.0.0.1 dev bond0 proto dhcp metric 100
+        let mut rtmsg = vec![libc::AF_INET as u8, 0, 0, 0, 254, 16, 0, RTN_UNICAST, 0, 0, 0, 0];
+        rtmsg.extend(attr(RTA_TABLE, &254u32.to_ne_bytes()));
+        rtmsg.extend(attr(RTA_PRIORITY, &100u32.to_ne_bytes()));
+        rtmsg.extend(attr(RTA_GATEWAY, &[10, 0, 0, 1]));
+        rtmsg.extend(attr(RTA_OIF, &5u32.to_ne_bytes()));
+        let mut dump = message(RTM_NEWROUTE, &rtmsg);
+
+        // 198.51.100.0/24 proto static nexthop via 10.0.0.2 dev bond0 nexthop dev br0 table 1000
+        let mut rtmsg = vec![libc::AF_INET as u8, 24, 0, 0, 252, RTPROT_STATIC, 0, RTN_UNICAST, 0, 0, 0, 0];
+        rtmsg.extend(attr(RTA_TABLE, &1000u32.to_ne_bytes()));
+        rtmsg.extend(attr(RTA_DST, &[198, 51, 100, 0]));
+        let mut nexthops = vec![16, 0, 0, 0];
+        nexthops.extend_from_slice(&5u32.to_ne_bytes());
+        nexthops.extend(attr(RTA_GATEWAY, &[10, 0, 0, 2]));
+        nexthops.extend_from_slice(&[8, 0, 0, 0]);
+        nexthops.extend_from_slice(&7u32.to_ne_bytes());
+        rtmsg.extend(attr(RTA_MULTIPATH, &nexthops));
+        dump.extend(message(RTM_NEWROUTE, &rtmsg));
+        dump.extend(message(NLMSG_DONE, &[0; 4]));
+
+        let routes = parse_routes(&dump).unwrap();
+        assert_eq!(routes.len(), 2);
+        assert_eq!((routes[0].dst.clone(), routes[0].dst_len, routes[0].table), (vec![0, 0, 0, 0], 0, RT_TABLE_MAIN));
+        assert_eq!((routes[0].protocol, routes[0].priority), (16, 100));
+        assert_eq!(routes[0].nexthops, vec![NextHop { index: 5, gateway: Some(vec![10, 0, 0, 1]) }]);
+        assert_eq!((routes[1].dst.clone(), routes[1].dst_len, routes[1].table), (vec![198, 51, 100, 0], 24, 1000));
+        assert_eq!(routes[1].nexthops, vec![
+            NextHop { index: 5, gateway: Some(vec![10, 0, 0, 2]) },
+            NextHop { index: 7, gateway: None },
+        ]);
+    }
+
+    #[test]
+    fn parse_neighbor_dump() {
+        // 10.0.0.1 dev bond0 lladdr 52:54:00:12:34:56 REACHABLE, updated 2.5s ago
+        let mut ndmsg = vec![libc::AF_INET as u8, 0, 0, 0];
+        ndmsg.extend_from_slice(&5u32.to_ne_bytes());
+        ndmsg.extend_from_slice(&NUD_REACHABLE.to_ne_bytes());
+        ndmsg.extend_from_slice(&[0, 1]);
+        ndmsg.extend(attr(NDA_DST, &[10, 0, 0, 1]));
+        ndmsg.extend(attr(NDA_LLADDR, &[0x52, 0x54, 0, 0x12, 0x34, 0x56]));
+        let cacheinfo = [120u32, 250, 250, 1].iter().flat_map(|word| word.to_ne_bytes().to_vec()).collect::<Vec<u8>>();
+        ndmsg.extend(attr(NDA_CACHEINFO, &cacheinfo));
+        let mut dump = message(RTM_NEWNEIGH, &ndmsg);
+
+        // 10.0.0.7 dev bond0 FAILED
+        let mut ndmsg = vec![libc::AF_INET as u8, 0, 0, 0];
+        ndmsg.extend_from_slice(&5u32.to_ne_bytes());
+        ndmsg.extend_from_slice(&NUD_FAILED.to_ne_bytes());
+        ndmsg.extend_from_slice(&[0, 1]);
+        ndmsg.extend(attr(NDA_DST, &[10, 0, 0, 7]));
+        dump.extend(message(RTM_NEWNEIGH, &ndmsg));
+        dump.extend(message(NLMSG_DONE, &[0; 4]));
+
+        let neighbors = parse_neighbors(&dump).unwrap();
+        assert_eq!(neighbors, vec![
+            Neighbor {
+                family:  libc::AF_INET as u8,
+                index:   5,
+                state:   NUD_REACHABLE,
+                address: vec![10, 0, 0, 1],
+                lladdr:  Some(vec![0x52, 0x54, 0, 0x12, 0x34, 0x56]),
+                updated: Some(250),
+            },
+            Neighbor { family: libc::AF_INET as u8, index: 5, state: NUD_FAILED, address: vec![10, 0, 0, 7], lladdr: None, updated: None },
+        ]);
+        assert!(parse_neighbors(&message(RTM_NEWNEIGH, &[0; 8])).is_err());
+    }
+}
@@ -1,7 +1,7 @@
-use snmp;
 use std::cmp::Ordering;
 use std::fmt;
 
+#[derive(Clone, Debug)]
 pub struct OID {
     oid_str: String,
     oid_vec: Vec<u32>,
@@ -14,7 +14,7 @@ impl OID {
             .split(".")
             .map(|i| i.parse::<u32>().unwrap())
             .collect::<Vec<u32>>();
-        OID { oid_str: oid_str, oid_vec: oid_vec }
+        OID { oid_str, oid_vec }
     }
 
     pub fn from_vec(input: &Vec<u32>) -> OID {
@@ -24,40 +24,19 @@ impl OID {
             .collect::<Vec<String>>()
             .join(".");
         let oid_vec = input.to_owned();
-        OID { oid_str: oid_str, oid_vec: oid_vec }
-    }
-
-    pub fn from_object_identifier(input : snmp::ObjectIdentifier) -> OID {
-        OID::from_string(input.to_string())
+        OID { oid_str, oid_vec }
     }
 
     pub fn from_parts(input: &[&str]) -> OID {
         OID::from_string(input.join("."))
     }
 
-    pub fn from_parts_and_instance(input: &[&str], instance: u32) -> OID {
-        OID::from_string(format!("{}.{}", input.join("."), instance))
-    }
-
-    pub fn asciify_part(input: &String) -> String {
-        // Turn a human-readable word like ntpq_delay into an asciified oid part,
-        // like "110.116.112.113.95.100.101.108.97.121"
-        input.chars()
-            .map(|x| format!("{}", x as u8))
-            .collect::<Vec<String>>()
-            .join(".")
-    }
-
     pub fn as_vec(&self) -> &Vec<u32> {
         &self.oid_vec
     }
 
     pub fn is_subtree_of(&self, subtree: &OID) -> bool {
-        return self.oid_str.starts_with(subtree.str());
-    }
-
-    pub fn str(&self) -> &str {
-        return &self.oid_str;
+        self.oid_vec.starts_with(subtree.as_vec())
     }
 }
 
@@ -71,12 +50,8 @@ impl Eq for OID {}
 
 impl Ord for OID {
     fn cmp(&self, other: &OID) -> Ordering {
-        for (mine, theirs) in self.as_vec().iter().zip(other.as_vec().iter()) {
-            if mine != theirs {
-                return mine.cmp(theirs);
-            }
-        }
-        return Ordering::Equal;
+        // Lexicographical, so that a subtree sorts right before its first child
+        self.as_vec().cmp(other.as_vec())
     }
 }
 
@@ -0,0 +1,106 @@
+use glob;
+use regex::Regex;
+use errors::*;
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+/**
+ * A glob or regular expression to match names against.
+ *
+ * Globs support `*`, `?` and `[...]` and must match the whole name, see the
+ * glob crate. Regular expressions are those of the regex crate and match
+ * anywhere in the name unless anchored.
+ */
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    matcher: Matcher,
+}
+
+impl Pattern {
+    pub fn glob(source: &str) -> Result<Pattern> {
+        let glob = glob::Pattern::new(source)
+            .map_err(|err| format!("Invalid pattern {:?}: {}", source, err.msg))?;
+        Ok(Pattern { matcher: Matcher::Glob(glob) })
+    }
+
+    pub fn regex(source: &str) -> Result<Pattern> {
+        // The message comes last, after the pattern with the position marked
+        let regex = Regex::new(source)
+            .map_err(|err| format!("Invalid pattern {:?}: {}", source, err.to_string().lines().last().unwrap_or("").trim_start_matches("error: ")))?;
+        Ok(Pattern { matcher: Matcher::Regex(regex) })
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self.matcher {
+            Matcher::Glob(ref glob)   => glob.as_str(),
+            Matcher::Regex(ref regex) => regex.as_str(),
+        }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match self.matcher {
+            Matcher::Glob(ref glob)   => glob.matches(text),
+            Matcher::Regex(ref regex) => regex.is_match(text),
+        }
+    }
+}
+
+// Compiled regexes cannot be compared, but the same source compiles to the same one
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        match (&self.matcher, &other.matcher) {
+            (Matcher::Glob(glob), Matcher::Glob(other))     => glob == other,
+            (Matcher::Regex(regex), Matcher::Regex(other)) => regex.as_str() == other.as_str(),
+            _ => false
+        }
+    }
+}
+
+impl Eq for Pattern {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        let glob = |pattern: &str, text: &str| Pattern::glob(pattern).unwrap().matches(text);
+        assert!(glob("loop*", "loop0"));
+        assert!(!glob("loop*", "bigloop0"));
+        assert!(glob("/boot/efi", "/boot/efi"));
+        assert!(!glob("/boot", "/boot/efi"));
+        assert!(glob("/var/*", "/var/lib/lxc"));
+        assert!(glob("sd?", "sda"));
+        assert!(!glob("sd?", "sda1"));
+        assert!(glob("sd[a-c][0-9]", "sdb1"));
+        assert!(!glob("sd[!a-c]*", "sdb1"));
+        assert!(glob("eth[*]", "eth*"));
+        assert!(glob("*", ""));
+    }
+
+    #[test]
+    fn regexes() {
+        let regex = |pattern: &str, text: &str| Pattern::regex(pattern).unwrap().matches(text);
+        assert!(regex("^wg\\d+$", "wg0"));
+        assert!(!regex("^wg\\d+$", "wg"));
+        assert!(regex("veth", "vethab12cd"));
+        assert!(regex("^(eth|en[op])\\d", "eno1"));
+        assert!(!regex("^(eth|en[op])\\d", "ens1"));
+        assert!(regex("^nvme\\d+n\\d+(p\\d+)?$", "nvme0n1p2"));
+        assert!(regex("", "anything"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in &["(eth", "eth)", "*", "[z-a]", "a{3,2}"] {
+            assert!(Pattern::regex(pattern).is_err(), "{} should be invalid", pattern);
+        }
+        assert!(Pattern::glob("sd[a").is_err());
+        assert_eq!(Pattern::glob("sd*").unwrap(), Pattern::glob("sd*").unwrap());
+        assert!(Pattern::glob("sd*").unwrap() != Pattern::regex("sd*").unwrap());
+    }
+}
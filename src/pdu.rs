@@ -0,0 +1,406 @@
+use ber;
+use ber::{Reader,Writer,DecodeError,DecodeResult};
+use oid::OID;
+use value::Value;
+
+/// More varbinds than this in a single message are rejected.
+pub const MAX_VARBINDS: usize = 1024;
+
+// error-status values (RFC 3416)
+pub const NO_ERROR:       i32 = 0;
+pub const TOO_BIG:        i32 = 1;
+pub const NO_SUCH_NAME:   i32 = 2;
+pub const GEN_ERR:        i32 = 5;
+pub const NOT_WRITABLE:   i32 = 17;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2c,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PduType {
+    GetRequest,
+    GetNextRequest,
+    Response,
+    SetRequest,
+    GetBulkRequest,
+    InformRequest,
+    TrapV2,
+    Report,
+}
+
+impl PduType {
+    pub fn tag(&self) -> u8 {
+        match *self {
+            PduType::GetRequest     => 0xA0,
+            PduType::GetNextRequest => 0xA1,
+            PduType::Response       => 0xA2,
+            PduType::SetRequest     => 0xA3,
+            PduType::GetBulkRequest => 0xA5,
+            PduType::InformRequest  => 0xA6,
+            PduType::TrapV2         => 0xA7,
+            PduType::Report         => 0xA8,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> DecodeResult<PduType> {
+        Ok(match tag {
+            0xA0 => PduType::GetRequest,
+            0xA1 => PduType::GetNextRequest,
+            0xA2 => PduType::Response,
+            0xA3 => PduType::SetRequest,
+            0xA5 => PduType::GetBulkRequest,
+            0xA6 => PduType::InformRequest,
+            0xA7 => PduType::TrapV2,
+            0xA8 => PduType::Report,
+            // 0xA4 is the SNMPv1 Trap-PDU, which has a different layout and is obsolete
+            _    => return Err(DecodeError::UnsupportedPdu(tag))
+        })
+    }
+}
+
+/**
+ * One of the PDUs from RFC 3416. They all share the same layout, only
+ * GetBulkRequest reuses error-status and error-index as non-repeaters and
+ * max-repetitions.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pdu {
+    pub pdu_type:     PduType,
+    pub request_id:   i32,
+    pub error_status: i32,
+    pub error_index:  i32,
+    pub varbinds:     Vec<(OID, Value<'static>)>,
+}
+
+impl Pdu {
+    pub fn non_repeaters(&self) -> usize {
+        ::std::cmp::max(self.error_status, 0) as usize
+    }
+
+    pub fn max_repetitions(&self) -> usize {
+        ::std::cmp::max(self.error_index, 0) as usize
+    }
+}
+
+/**
+ * A community-based (v1 or v2c) SNMP message.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub version:   Version,
+    pub community: Vec<u8>,
+    pub pdu:       Pdu,
+}
+
+impl Message {
+    pub fn decode(bytes: &[u8]) -> DecodeResult<Message> {
+        let mut outer = Reader::new(bytes);
+        let mut message = outer.read_sequence(ber::SEQUENCE)?;
+        outer.finish()?;
+
+        let version = match message.read_i32(ber::INTEGER)? {
+            0 => Version::V1,
+            1 => Version::V2c,
+            v => return Err(DecodeError::UnsupportedVersion(v))
+        };
+        let community = message.read_octets(ber::OCTET_STRING)?.to_vec();
+
+        let (tag, content) = message.read_tlv()?;
+        message.finish()?;
+        let pdu_type = PduType::from_tag(tag)?;
+
+        let mut pdu = Reader::new(content);
+        let request_id   = pdu.read_i32(ber::INTEGER)?;
+        let error_status = pdu.read_i32(ber::INTEGER)?;
+        let error_index  = pdu.read_i32(ber::INTEGER)?;
+        let mut varbind_list = pdu.read_sequence(ber::SEQUENCE)?;
+        pdu.finish()?;
+
+        let mut varbinds = Vec::new();
+        while !varbind_list.is_empty() {
+            if varbinds.len() == MAX_VARBINDS {
+                return Err(DecodeError::TooManyVarbinds);
+            }
+            let mut varbind = varbind_list.read_sequence(ber::SEQUENCE)?;
+            let name = ber::decode_oid(varbind.read_expected(ber::OBJECT_IDENTIFIER)?)?;
+            let (value_tag, value_content) = varbind.read_tlv()?;
+            varbind.finish()?;
+            varbinds.push((OID::from_vec(&name), Value::decode(value_tag, value_content)?));
+        }
+
+        Ok(Message {
+            version,
+            community,
+            pdu:       Pdu {
+                pdu_type,
+                request_id,
+                error_status,
+                error_index,
+                varbinds,
+            },
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_sequence(ber::SEQUENCE, |message| {
+            message.write_integer(ber::INTEGER, match self.version {
+                Version::V1  => 0,
+                Version::V2c => 1,
+            });
+            message.write_tlv(ber::OCTET_STRING, &self.community);
+            message.write_sequence(self.pdu.pdu_type.tag(), |pdu| {
+                pdu.write_integer(ber::INTEGER, self.pdu.request_id as i64);
+                pdu.write_integer(ber::INTEGER, self.pdu.error_status as i64);
+                pdu.write_integer(ber::INTEGER, self.pdu.error_index as i64);
+                pdu.write_sequence(ber::SEQUENCE, |varbind_list| {
+                    for (name, value) in &self.pdu.varbinds {
+                        varbind_list.write_sequence(ber::SEQUENCE, |varbind| {
+                            varbind.write_oid(name.as_vec());
+                            value.encode(varbind);
+                        });
+                    }
+                });
+            });
+        });
+        writer.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(s: &str) -> OID {
+        OID::from_parts(&[s])
+    }
+
+    fn message(version: Version, pdu_type: PduType, varbinds: Vec<(OID, Value<'static>)>) -> Message {
+        Message {
+            version,
+            community: b"public".to_vec(),
+            pdu:       Pdu {
+                pdu_type,
+                request_id:   0x12345678,
+                error_status: 0,
+                error_index:  0,
+                varbinds,
+            },
+        }
+    }
+
+    // snmpget -v2c -c public host sysUpTime.0
+    const GET_SYSUPTIME: &[u8] = &[
+        0x30, 0x29, 0x02, 0x01, 0x01, 0x04, 0x06, 0x70, 0x75, 0x62, 0x6c, 0x69, 0x63,
+        0xa0, 0x1c, 0x02, 0x04, 0x12, 0x34, 0x56, 0x78, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+        0x30, 0x0e, 0x30, 0x0c, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x03, 0x00,
+        0x05, 0x00,
+    ];
+
+    #[test]
+    fn decode_get_request() {
+        let msg = Message::decode(GET_SYSUPTIME).unwrap();
+        assert_eq!(msg, message(Version::V2c, PduType::GetRequest, vec![
+            (oid("1.3.6.1.2.1.1.3.0"), Value::Null)
+        ]));
+        assert_eq!(msg.encode(), GET_SYSUPTIME);
+    }
+
+    #[test]
+    fn round_trip_all_pdu_types() {
+        let pdu_types = [
+            PduType::GetRequest, PduType::GetNextRequest, PduType::Response, PduType::SetRequest,
+            PduType::GetBulkRequest, PduType::InformRequest, PduType::TrapV2, PduType::Report,
+        ];
+        for &version in &[Version::V1, Version::V2c] {
+            for &pdu_type in &pdu_types {
+                let msg = message(version, pdu_type, vec![
+                    (oid("1.3.6.1.2.1.1.1.0"), Value::Null),
+                    (oid("1.3.6.1.2.1.2.2.1.10.3"), Value::Counter32(4000000000)),
+                ]);
+                assert_eq!(Message::decode(&msg.encode()).unwrap(), msg);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_all_value_types() {
+        let values = vec![
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(127),
+            Value::Integer(128),
+            Value::Integer(-1),
+            Value::Integer(-129),
+            Value::Integer(i32::MIN as i64),
+            Value::Integer(i32::MAX as i64),
+            Value::Octets(vec![]),
+            Value::Octets(vec![0x00, 0xff, 0x80]),
+            Value::Octets(vec![b'x'; 300]),
+            Value::ObjectIdentifier(oid("1.3.6.1.4.1.2021.4294967295")),
+            Value::ObjectIdentifier(oid("2.999.3")),
+            Value::IpAddress([192, 168, 0, 1]),
+            Value::Counter32(0),
+            Value::Counter32(0xFFFFFFFF),
+            Value::Unsigned32(0x80000000),
+            Value::Timeticks(123456),
+            Value::Opaque(vec![0x9f, 0x78, 0x04, 0x42, 0xf6, 0x00, 0x00]),
+            Value::Counter64(0),
+            Value::Counter64(u64::MAX),
+            Value::NoSuchObject,
+            Value::NoSuchInstance,
+            Value::EndOfMibView,
+        ];
+        let varbinds = values.into_iter()
+            .enumerate()
+            .map(|(idx, value)| (OID::from_vec(&vec![1, 3, 6, 1, 4, 1, 99999, idx as u32]), value))
+            .collect::<Vec<_>>();
+        let msg = message(Version::V2c, PduType::Response, varbinds);
+        assert_eq!(Message::decode(&msg.encode()).unwrap(), msg);
+    }
+
+    #[test]
+    fn get_bulk_fields() {
+        let mut msg = message(Version::V2c, PduType::GetBulkRequest, vec![(oid("1.3.6.1.2.1.2.2"), Value::Null)]);
+        msg.pdu.error_status = 1;
+        msg.pdu.error_index  = 25;
+        let decoded = Message::decode(&msg.encode()).unwrap();
+        assert_eq!(decoded.pdu.non_repeaters(), 1);
+        assert_eq!(decoded.pdu.max_repetitions(), 25);
+    }
+
+    #[test]
+    fn strings_encode_as_octets() {
+        let msg = message(Version::V2c, PduType::Response, vec![
+            (oid("1.3.6.1.2.1.1.5.0"), Value::OctetString(String::from("foo"))),
+            (oid("1.3.6.1.2.1.1.6.0"), Value::OctetStr("bar")),
+        ]);
+        let decoded = Message::decode(&msg.encode()).unwrap();
+        assert_eq!(decoded.pdu.varbinds[0].1, Value::Octets(b"foo".to_vec()));
+        assert_eq!(decoded.pdu.varbinds[1].1, Value::Octets(b"bar".to_vec()));
+    }
+
+    #[test]
+    fn long_lengths() {
+        let msg = message(Version::V2c, PduType::Response, vec![
+            (oid("1.3.6.1.2.1.1.1.0"), Value::Octets(vec![b'x'; 35000])),
+        ]);
+        let encoded = msg.encode();
+        // Length of the message takes two octets
+        assert_eq!(encoded[1], 0x82);
+        assert_eq!(Message::decode(&encoded).unwrap(), msg);
+    }
+
+    #[test]
+    fn truncated() {
+        for len in 0..GET_SYSUPTIME.len() {
+            assert!(Message::decode(&GET_SYSUPTIME[..len]).is_err(), "decoded {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut bytes = GET_SYSUPTIME.to_vec();
+        bytes.push(0x00);
+        assert_eq!(Message::decode(&bytes), Err(DecodeError::TrailingData));
+    }
+
+    #[test]
+    fn indefinite_length() {
+        let mut bytes = GET_SYSUPTIME.to_vec();
+        bytes[1] = 0x80;
+        assert_eq!(Message::decode(&bytes), Err(DecodeError::IndefiniteLength));
+    }
+
+    #[test]
+    fn length_too_long() {
+        assert_eq!(Message::decode(&[0x30, 0x84, 0x00, 0x00, 0x00, 0x01, 0x00]), Err(DecodeError::LengthTooLong));
+        assert_eq!(Message::decode(&[0x30, 0x83, 0x01, 0x00, 0x00]), Err(DecodeError::LengthTooLong));
+        assert_eq!(Message::decode(&[0x30, 0x82, 0x01, 0x00, 0x00]), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut bytes = GET_SYSUPTIME.to_vec();
+        bytes[4] = 3;
+        assert_eq!(Message::decode(&bytes), Err(DecodeError::UnsupportedVersion(3)));
+    }
+
+    #[test]
+    fn unsupported_pdu() {
+        let mut bytes = GET_SYSUPTIME.to_vec();
+        bytes[13] = 0xA4;
+        assert_eq!(Message::decode(&bytes), Err(DecodeError::UnsupportedPdu(0xA4)));
+    }
+
+    #[test]
+    fn unexpected_tag() {
+        let mut bytes = GET_SYSUPTIME.to_vec();
+        bytes[5] = ber::INTEGER;
+        assert_eq!(
+            Message::decode(&bytes),
+            Err(DecodeError::UnexpectedTag { expected: ber::OCTET_STRING, found: ber::INTEGER })
+        );
+    }
+
+    #[test]
+    fn malformed_integers() {
+        // request-id without content
+        let empty = [
+            0x30, 0x11, 0x02, 0x01, 0x01, 0x04, 0x00, 0xa0, 0x0a,
+            0x02, 0x00, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x00,
+        ];
+        assert_eq!(Message::decode(&empty), Err(DecodeError::EmptyInteger));
+
+        // request-id with five content bytes
+        let wide = [
+            0x30, 0x16, 0x02, 0x01, 0x01, 0x04, 0x00, 0xa0, 0x0f,
+            0x02, 0x05, 0x00, 0x12, 0x34, 0x56, 0x78,
+            0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x00,
+        ];
+        assert_eq!(Message::decode(&wide), Err(DecodeError::IntegerOverflow));
+    }
+
+    #[test]
+    fn malformed_values() {
+        let decode = |tag: u8, content: &[u8]| Value::decode(tag, content);
+        assert_eq!(decode(ber::INTEGER, &[]), Err(DecodeError::EmptyInteger));
+        assert_eq!(decode(ber::INTEGER, &[1, 2, 3, 4, 5]), Err(DecodeError::IntegerOverflow));
+        assert_eq!(decode(ber::COUNTER32, &[0x01, 0x00, 0x00, 0x00, 0x00]), Err(DecodeError::IntegerOverflow));
+        assert_eq!(decode(ber::COUNTER32, &[0x80]), Err(DecodeError::IntegerOverflow));
+        assert_eq!(decode(ber::COUNTER32, &[0x00, 0xff, 0xff, 0xff, 0xff]), Ok(Value::Counter32(0xFFFFFFFF)));
+        assert_eq!(decode(ber::COUNTER64, &[0x01, 0, 0, 0, 0, 0, 0, 0, 0]), Err(DecodeError::IntegerOverflow));
+        assert_eq!(decode(ber::NULL, &[0]), Err(DecodeError::InvalidNull));
+        assert_eq!(decode(ber::END_OF_MIB_VIEW, &[0]), Err(DecodeError::InvalidNull));
+        assert_eq!(decode(ber::IP_ADDRESS, &[127, 0, 0]), Err(DecodeError::InvalidIpAddress));
+        assert_eq!(decode(0x01, &[0xff]), Err(DecodeError::UnknownTag(0x01)));
+    }
+
+    #[test]
+    fn malformed_oids() {
+        // Continuation bit set on the last byte
+        assert_eq!(ber::decode_oid(&[0x2b, 0x86]), Err(DecodeError::InvalidOid));
+        // Non-minimal sub-identifier
+        assert_eq!(ber::decode_oid(&[0x2b, 0x80, 0x01]), Err(DecodeError::InvalidOid));
+        // Sub-identifier exceeds 32 bits
+        assert_eq!(ber::decode_oid(&[0x2b, 0x90, 0x80, 0x80, 0x80, 0x00]), Err(DecodeError::InvalidOid));
+        // Too many parts
+        let mut long = vec![0x2b];
+        long.extend(vec![0x01; ber::MAX_OID_LEN]);
+        assert_eq!(ber::decode_oid(&long), Err(DecodeError::OidTooLong));
+        // Empty OIDs are sent by some clients when walking from .1
+        assert_eq!(ber::decode_oid(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn too_many_varbinds() {
+        let varbinds = (0..MAX_VARBINDS as u32 + 1)
+            .map(|idx| (OID::from_vec(&vec![1, 3, 6, idx]), Value::Null))
+            .collect();
+        let msg = message(Version::V2c, PduType::GetRequest, varbinds);
+        assert_eq!(Message::decode(&msg.encode()), Err(DecodeError::TooManyVarbinds));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use oid::OID;
+use value::Value;
+use table::TableDef;
+
+/**
+ * Something that fills one or more subtrees of the MIB with values.
+ *
+ * Providers are only asked to collect when a request actually touches
+ * one of their subtrees, so expensive stuff can live in here without
+ * slowing down requests for cheap ones.
+ */
+pub trait Provider: Send {
+    /// The subtrees this provider is authoritative for. They must not overlap with other providers.
+    fn subtrees(&self) -> Vec<OID>;
+
+    /// Fill values with the current state of the world.
+    fn collect(&mut self, values: &mut BTreeMap<OID, Value<'static>>);
+
+    /// Definitions of the sunt enterprise tables this provider fills, for the generated SUNT-MIB.
+    fn tables(&self) -> Vec<&'static TableDef> {
+        Vec::new()
+    }
+}
+
+/**
+ * Provider that wraps one of the get_* functions of the mib_* modules.
+ */
+pub struct Collector<F> {
+    subtrees: Vec<OID>,
+    tables:   Vec<&'static TableDef>,
+    collect:  F,
+}
+
+impl<F> Collector<F>
+    where F: FnMut(&mut BTreeMap<OID, Value<'static>>)
+{
+    pub fn new(subtrees: &[&str], collect: F) -> Collector<F> {
+        Collector {
+            subtrees: subtrees.iter().map(|subtree| OID::from_parts(&[subtree])).collect(),
+            tables:   Vec::new(),
+            collect,
+        }
+    }
+
+    /**
+     * Declare the sunt enterprise tables the collect function fills.
+     */
+    pub fn with_tables(mut self, tables: &[&'static TableDef]) -> Collector<F> {
+        self.tables.extend_from_slice(tables);
+        self
+    }
+}
+
+impl<F> Provider for Collector<F>
+    where F: FnMut(&mut BTreeMap<OID, Value<'static>>) + Send
+{
+    fn subtrees(&self) -> Vec<OID> {
+        self.subtrees.clone()
+    }
+
+    fn collect(&mut self, values: &mut BTreeMap<OID, Value<'static>>) {
+        (self.collect)(values)
+    }
+
+    fn tables(&self) -> Vec<&'static TableDef> {
+        self.tables.clone()
+    }
+}
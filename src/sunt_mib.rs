@@ -0,0 +1,373 @@
+use std::collections::BTreeSet;
+use errors::*;
+use oid::OID;
+use table::{Syntax,TableDef};
+use mib_net;
+use mib_bonding;
+use mib_bridge;
+use mib_netstat;
+use mib_sockets;
+
+/**
+ * Root of sunt's own objects, enterprises.2147483647. Sunt has no Private
+ * Enterprise Number registered with IANA, so this is a private arc: IANA hands
+ * out numbers in order and is nowhere near 2^31 - 1, the largest one that tools
+ * with signed 32 bit sub-identifiers still get right. See "SUNT-MIB" in the README.
+ */
+pub const SUNT_MIB:         &str = "1.3.6.1.4.1.2147483647";
+/// Tables are suntObjects.N, their entries suntObjects.N.1.
+pub const SUNT_OBJECTS:     &str = "1.3.6.1.4.1.2147483647.1";
+pub const SUNT_CONFORMANCE: &str = "1.3.6.1.4.1.2147483647.2";
+
+/// Every table of sunt's own, whether or not its collector is enabled.
+pub const TABLES: &[&TableDef] = &[
+    &mib_bonding::BOND_TABLE,
+    &mib_bonding::BOND_SLAVE_TABLE,
+    &mib_net::LINK_SETTINGS_TABLE,
+    &mib_net::DRIVER_STATS_TABLE,
+    &mib_netstat::NETSTAT_TABLE,
+    &mib_sockets::PROCESS_TABLE,
+    &mib_bridge::BRIDGE_FDB_TABLE,
+];
+
+/// Index objects that sunt tables borrow from standard MIBs.
+const EXTERNAL_INDEXES: &[(&str, &str)] = &[
+    ("ifIndex", "IF-MIB"),
+];
+
+/// REVISIONs of the module, newest first. Add one whenever a table is added or changed.
+const REVISIONS: &[(&str, &str)] = &[
+    ("202610190000Z", "Initial version."),
+];
+
+fn syntax_name(syntax: Syntax) -> &'static str {
+    match syntax {
+        Syntax::Integer          => "Integer32",
+        Syntax::OctetString      => "OCTET STRING",
+        Syntax::ObjectIdentifier => "OBJECT IDENTIFIER",
+        Syntax::IpAddress        => "IpAddress",
+        Syntax::Counter32        => "Counter32",
+        Syntax::Gauge32          => "Gauge32",
+        Syntax::TimeTicks        => "TimeTicks",
+        Syntax::Counter64        => "Counter64",
+    }
+}
+
+/**
+ * Format text as a quoted DESCRIPTION, wrapped and indented by 8 spaces.
+ */
+fn description(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.replace('"', "'").split_whitespace() {
+        if !line.is_empty() && line.len() + word.len() > 62 {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    format!("        \"{}\"", lines.join("\n         "))
+}
+
+/**
+ * Name of the SEQUENCE type of a table's entries: suntBondTable -> SuntBondEntry.
+ */
+fn entry_type(table: &TableDef) -> String {
+    let entry = entry_name(table);
+    let mut chars = entry.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None        => entry
+    }
+}
+
+fn entry_name(table: &TableDef) -> String {
+    format!("{}Entry", table.name.trim_end_matches("Table"))
+}
+
+/**
+ * Number of the table below suntObjects.
+ */
+fn table_number(table: &TableDef) -> Result<u32> {
+    let objects = OID::from_parts(&[SUNT_OBJECTS]);
+    let entry = OID::from_parts(&[table.entry_oid]);
+    let parts = entry.as_vec();
+    let prefix_len = objects.as_vec().len();
+    if !entry.is_subtree_of(&objects) || parts.len() != prefix_len + 2 || parts[prefix_len + 1] != 1 {
+        bail!("{} is not at {}.N.1", table.name, SUNT_OBJECTS);
+    }
+    Ok(parts[prefix_len])
+}
+
+/**
+ * The SUNT-MIB module declaring all of sunt's tables.
+ */
+pub fn module() -> Result<String> {
+    generate(TABLES)
+}
+
+/**
+ * Generate the SUNT-MIB module declaring the given tables.
+ */
+pub fn generate(tables: &[&TableDef]) -> Result<String> {
+    let mut tables = tables.iter()
+        .map(|&table| Ok((table_number(table)?, table)))
+        .collect::<Result<Vec<_>>>()?;
+    tables.sort_by_key(|&(number, _)| number);
+    for pair in tables.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            bail!("{} and {} share the same OID", pair[0].1.name, pair[1].1.name);
+        }
+    }
+
+    let mut smi_imports = vec!["MODULE-IDENTITY", "OBJECT-IDENTITY", "enterprises"];
+    let mut other_imports = BTreeSet::new();
+    if !tables.is_empty() {
+        smi_imports.push("OBJECT-TYPE");
+        let syntaxes = tables.iter()
+            .flat_map(|&(_, table)| table.columns.iter().map(|column| column.syntax))
+            .collect::<Vec<Syntax>>();
+        for &syntax in &[Syntax::Integer, Syntax::IpAddress, Syntax::Counter32,
+                         Syntax::Gauge32, Syntax::TimeTicks, Syntax::Counter64] {
+            if syntaxes.contains(&syntax) {
+                smi_imports.push(syntax_name(syntax));
+            }
+        }
+        for &(_, table) in &tables {
+            for index in table.index {
+                if let Some(&(name, module)) = EXTERNAL_INDEXES.iter().find(|&&(name, _)| name == *index) {
+                    other_imports.insert((module, name));
+                }
+                else if !table.columns.iter().any(|column| column.name == *index) {
+                    bail!("{} is indexed by unknown object {}", table.name, index);
+                }
+            }
+        }
+    }
+
+    let mut mib = String::new();
+    mib.push_str("SUNT-MIB DEFINITIONS ::= BEGIN\n\n");
+    mib.push_str("-- Generated by sunt, do not edit.\n\n");
+    let mut imports = vec![format!("    {}\n        FROM SNMPv2-SMI", smi_imports.join(", "))];
+    if !tables.is_empty() {
+        imports.push(String::from("    MODULE-COMPLIANCE, OBJECT-GROUP\n        FROM SNMPv2-CONF"));
+    }
+    for &(module, name) in &other_imports {
+        imports.push(format!("    {}\n        FROM {}", name, module));
+    }
+    mib.push_str(&format!("IMPORTS\n{};\n\n", imports.join("\n")));
+
+    mib.push_str(&format!(
+"suntMIB MODULE-IDENTITY
+    LAST-UPDATED \"{}\"
+    ORGANIZATION \"sunt\"
+    CONTACT-INFO
+        \"Michael Ziegler <diese-addy@funzt-halt.net>\"
+    DESCRIPTION
+{}
+{}    ::= {{ enterprises {} }}
+
+suntObjects OBJECT-IDENTITY
+    STATUS      current
+    DESCRIPTION
+        \"Tables exported by sunt.\"
+    ::= {{ suntMIB 1 }}
+
+suntConformance OBJECT-IDENTITY
+    STATUS      current
+    DESCRIPTION
+        \"Conformance information for SUNT-MIB.\"
+    ::= {{ suntMIB 2 }}
+",
+        REVISIONS[0].0,
+        description("Objects exported by the sunt SNMP agent that are not covered by any standard MIB. \
+                     Sunt has no registered enterprise number, this is a private arc."),
+        REVISIONS.iter()
+            .map(|&(revision, text)| format!("    REVISION     \"{}\"\n    DESCRIPTION\n{}\n", revision, description(text)))
+            .collect::<String>(),
+        OID::from_parts(&[SUNT_MIB]).as_vec().last().unwrap(),
+    ));
+
+    for &(number, table) in &tables {
+        let entry = entry_name(table);
+        let entry_type = entry_type(table);
+        let width = table.columns.iter().map(|column| column.name.len()).max().unwrap_or(0);
+
+        mib.push_str(&format!(
+"
+{table} OBJECT-TYPE
+    SYNTAX      SEQUENCE OF {entry_type}
+    MAX-ACCESS  not-accessible
+    STATUS      current
+    DESCRIPTION
+{description}
+    ::= {{ suntObjects {number} }}
+
+{entry} OBJECT-TYPE
+    SYNTAX      {entry_type}
+    MAX-ACCESS  not-accessible
+    STATUS      current
+    DESCRIPTION
+        \"A row of {table}.\"
+    INDEX       {{ {index} }}
+    ::= {{ {table} 1 }}
+
+{entry_type} ::= SEQUENCE {{
+{sequence}
+}}
+",
+            table       = table.name,
+            entry       = entry,
+            entry_type  = entry_type,
+            description = description(table.description),
+            number      = number,
+            index       = table.index.join(", "),
+            sequence    = table.columns.iter()
+                .map(|column| format!("    {:width$} {}", column.name, syntax_name(column.syntax), width = width))
+                .collect::<Vec<String>>()
+                .join(",\n"),
+        ));
+
+        for column in table.columns {
+            // RFC 2578 7.7: Index objects are only there to name rows
+            let access = if table.index.contains(&column.name) { "not-accessible" } else { "read-only" };
+            mib.push_str(&format!(
+"
+{name} OBJECT-TYPE
+    SYNTAX      {syntax}
+    MAX-ACCESS  {access}
+    STATUS      current
+    DESCRIPTION
+{description}
+    ::= {{ {entry} {number} }}
+",
+                name        = column.name,
+                syntax      = syntax_name(column.syntax),
+                access      = access,
+                description = description(column.description),
+                entry       = entry,
+                number      = column.number,
+            ));
+        }
+    }
+
+    if !tables.is_empty() {
+        mib.push_str(&format!(
+"
+suntGroups      OBJECT IDENTIFIER ::= {{ suntConformance 1 }}
+suntCompliances OBJECT IDENTIFIER ::= {{ suntConformance 2 }}
+
+suntCompliance MODULE-COMPLIANCE
+    STATUS      current
+    DESCRIPTION
+        \"What this sunt binary implements.\"
+    MODULE
+        MANDATORY-GROUPS {{ {groups} }}
+    ::= {{ suntCompliances 1 }}
+",
+            groups = tables.iter()
+                .map(|&(_, table)| format!("{}Group", table.name.trim_end_matches("Table")))
+                .collect::<Vec<String>>()
+                .join(", "),
+        ));
+
+        for &(number, table) in &tables {
+            mib.push_str(&format!(
+"
+{group}Group OBJECT-GROUP
+    OBJECTS     {{ {objects} }}
+    STATUS      current
+    DESCRIPTION
+        \"The columns of {table}.\"
+    ::= {{ suntGroups {number} }}
+",
+                group   = table.name.trim_end_matches("Table"),
+                objects = table.columns.iter()
+                    .filter(|column| !table.index.contains(&column.name))
+                    .map(|column| column.name)
+                    .collect::<Vec<&str>>()
+                    .join(",\n                  "),
+                table   = table.name,
+                number  = number,
+            ));
+        }
+    }
+
+    mib.push_str("\nEND\n");
+    Ok(mib)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mib::Mibs;
+    use table::Column;
+    use table::Syntax::*;
+
+    const SNMPV2_SMI: &str = r#"
+SNMPv2-SMI DEFINITIONS ::= BEGIN
+enterprises OBJECT IDENTIFIER ::= { iso 3 6 1 4 1 }
+END
+"#;
+
+    fn load(text: &str) -> Mibs {
+        let mut mibs = Mibs::new();
+        mibs.load_str(SNMPV2_SMI);
+        mibs.load_str(text);
+        mibs
+    }
+
+    const TEST_COLUMNS: &[Column] = &[
+        Column::new(1, "suntTestName",  OctetString).describe("Name of the thing."),
+        Column::new(2, "suntTestCount", Counter64).describe("How often the \"thing\" happened."),
+    ];
+
+    const TEST_TABLE: TableDef = TableDef {
+        name:        "suntTestTable",
+        entry_oid:   "1.3.6.1.4.1.2147483647.1.3.1",
+        description: "Things that happened on an interface.",
+        index:       &["ifIndex", "suntTestName"],
+        columns:     TEST_COLUMNS,
+    };
+
+    #[test]
+    fn generated_mib_resolves() {
+        let text = generate(&[&TEST_TABLE]).unwrap();
+        assert!(text.contains("    ifIndex\n        FROM IF-MIB;\n"));
+        assert!(text.contains("    SYNTAX      SEQUENCE OF SuntTestEntry\n"));
+        assert!(text.contains("        \"How often the 'thing' happened.\"\n"));
+        assert!(text.contains("suntTestName OBJECT-TYPE\n    SYNTAX      OCTET STRING\n    MAX-ACCESS  not-accessible\n"));
+        assert!(text.contains("    OBJECTS     { suntTestCount }\n"));
+
+        let mibs = load(&text);
+        assert_eq!(mibs.resolve_name("SUNT-MIB::suntMIB").unwrap(), OID::from_parts(&[SUNT_MIB]));
+        assert_eq!(mibs.resolve_name("SUNT-MIB::suntTestTable").unwrap(),
+                   OID::from_parts(&[SUNT_OBJECTS, "3"]));
+        assert_eq!(mibs.resolve_name("SUNT-MIB::suntTestCount").unwrap(),
+                   OID::from_parts(&[TEST_TABLE.entry_oid, "2"]));
+        assert_eq!(mibs.resolve_name("SUNT-MIB::suntTestGroup").unwrap(),
+                   OID::from_parts(&[SUNT_CONFORMANCE, "1", "3"]));
+    }
+
+    #[test]
+    fn misplaced_tables() {
+        let misplaced = TableDef { entry_oid: "1.3.6.1.4.1.2147483647.1.3", ..TEST_TABLE };
+        assert!(generate(&[&misplaced]).is_err());
+
+        let unknown_index = TableDef { index: &["suntTestNope"], ..TEST_TABLE };
+        assert!(generate(&[&unknown_index]).is_err());
+
+        assert!(generate(&[&TEST_TABLE, &TEST_TABLE]).is_err());
+    }
+
+    #[test]
+    fn module_resolves() {
+        let mibs = load(&module().unwrap());
+        assert_eq!(mibs.resolve_name("SUNT-MIB::suntBridgeFdbTable").unwrap(),
+                   OID::from_parts(&[SUNT_OBJECTS, "7"]));
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+use errors::*;
+use oid::OID;
+use value::Value;
+
+/**
+ * SMI syntax a column is declared with.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    Integer,
+    OctetString,
+    ObjectIdentifier,
+    IpAddress,
+    Counter32,
+    Gauge32,
+    TimeTicks,
+    Counter64,
+}
+
+impl Syntax {
+    fn of(value: &Value) -> Option<Syntax> {
+        match *value {
+            Value::Integer(_)          => Some(Syntax::Integer),
+            Value::OctetString(_)      => Some(Syntax::OctetString),
+            Value::OctetStr(_)         => Some(Syntax::OctetString),
+            Value::Octets(_)           => Some(Syntax::OctetString),
+            Value::ObjectIdentifier(_) => Some(Syntax::ObjectIdentifier),
+            Value::IpAddress(_)        => Some(Syntax::IpAddress),
+            Value::Counter32(_)        => Some(Syntax::Counter32),
+            Value::Unsigned32(_)       => Some(Syntax::Gauge32),
+            Value::Timeticks(_)        => Some(Syntax::TimeTicks),
+            Value::Counter64(_)        => Some(Syntax::Counter64),
+            _                          => None
+        }
+    }
+}
+
+/**
+ * A columnar object of a conceptual row, e.g. column 2 of dskTable: dskPath.
+ */
+pub struct Column {
+    pub number:      u32,
+    pub name:        &'static str,
+    pub syntax:      Syntax,
+    pub mandatory:   bool,
+    pub description: &'static str,
+}
+
+impl Column {
+    pub const fn new(number: u32, name: &'static str, syntax: Syntax) -> Column {
+        Column { number, name, syntax, mandatory: true, description: "" }
+    }
+
+    /**
+     * A column that may legitimately be missing from a row, e.g. because
+     * the kernel does not report it for every instance.
+     */
+    pub const fn optional(number: u32, name: &'static str, syntax: Syntax) -> Column {
+        Column { number, name, syntax, mandatory: false, description: "" }
+    }
+
+    /**
+     * Add the DESCRIPTION the column gets in the generated SUNT-MIB.
+     */
+    pub const fn describe(self, description: &'static str) -> Column {
+        Column { description, ..self }
+    }
+}
+
+/**
+ * Everything needed to declare a table in a MIB module. Only sunt's own
+ * tables need one, the standard ones are declared by their MIBs.
+ */
+pub struct TableDef {
+    /// Name of the table object, e.g. suntBondTable. The entry is named accordingly.
+    pub name:        &'static str,
+    /// OID of the entry object, as passed to Table::new.
+    pub entry_oid:   &'static str,
+    pub description: &'static str,
+    /// Names of the objects the table is indexed by, in order.
+    pub index:       &'static [&'static str],
+    pub columns:     &'static [Column],
+}
+
+/**
+ * Something that can be used as (part of) a table index, encoded as per RFC 2578 7.7.
+ */
+pub trait Index {
+    fn append_to(&self, suffix: &mut Vec<u32>);
+}
+
+impl Index for u32 {
+    fn append_to(&self, suffix: &mut Vec<u32>) {
+        suffix.push(*self);
+    }
+}
+
+impl Index for &str {
+    fn append_to(&self, suffix: &mut Vec<u32>) {
+        self.as_bytes().append_to(suffix);
+    }
+}
+
+impl Index for String {
+    fn append_to(&self, suffix: &mut Vec<u32>) {
+        self.as_bytes().append_to(suffix);
+    }
+}
+
+impl Index for &[u8] {
+    fn append_to(&self, suffix: &mut Vec<u32>) {
+        suffix.push(self.len() as u32);
+        suffix.extend(self.iter().map(|&byte| byte as u32));
+    }
+}
+
+/// IpAddress indexes are fixed-length and thus not prefixed with their length.
+impl Index for [u8; 4] {
+    fn append_to(&self, suffix: &mut Vec<u32>) {
+        suffix.extend(self.iter().map(|&byte| byte as u32));
+    }
+}
+
+/// So are MacAddress indexes.
+impl Index for [u8; 6] {
+    fn append_to(&self, suffix: &mut Vec<u32>) {
+        suffix.extend(self.iter().map(|&byte| byte as u32));
+    }
+}
+
+/// OBJECT IDENTIFIER indexes are variable-length, like strings.
+impl Index for OID {
+    fn append_to(&self, suffix: &mut Vec<u32>) {
+        suffix.push(self.as_vec().len() as u32);
+        suffix.extend_from_slice(self.as_vec());
+    }
+}
+
+macro_rules! tuple_index {
+    ( $( $name:ident : $idx:tt ),+ ) => {
+        impl<$( $name: Index ),+> Index for ( $( $name, )+ ) {
+            fn append_to(&self, suffix: &mut Vec<u32>) {
+                $( self.$idx.append_to(suffix); )+
+            }
+        }
+    }
+}
+
+tuple_index!(A: 0, B: 1);
+tuple_index!(A: 0, B: 1, C: 2);
+tuple_index!(A: 0, B: 1, C: 2, D: 3);
+tuple_index!(A: 0, B: 1, C: 2, D: 3, E: 4);
+tuple_index!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+tuple_index!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+
+/**
+ * A conceptual table: Columns are declared once, rows are added by index and
+ * filled by column name. Cells that are never set are left out of the tree.
+ */
+pub struct Table<'a> {
+    oid:     Vec<u32>,
+    columns: &'static [Column],
+    /// Columns that are not-accessible because the index already carries them.
+    index:   &'static [&'static str],
+    rows:    BTreeMap<Vec<u32>, Vec<Option<Value<'a>>>>,
+}
+
+pub struct Row<'t, 'a: 't> {
+    columns: &'static [Column],
+    cells:   &'t mut Vec<Option<Value<'a>>>,
+}
+
+impl<'a> Table<'a> {
+    /**
+     * table_oid is the OID of the entry object, e.g. 1.3.6.1.4.1.2021.9.1 for dskEntry.
+     */
+    pub fn new(table_oid: &str, columns: &'static [Column]) -> Table<'a> {
+        Table {
+            oid:     OID::from_parts(&[table_oid]).as_vec().to_owned(),
+            columns,
+            index:   &[],
+            rows:    BTreeMap::new(),
+        }
+    }
+
+    /**
+     * A table of sunt's own. Its index columns are left out of the tree, so they
+     * need not be set.
+     */
+    pub fn from_def(def: &TableDef) -> Table<'a> {
+        Table { index: def.index, ..Table::new(def.entry_oid, def.columns) }
+    }
+
+    /**
+     * Get the row for the given index, creating it if it does not exist yet.
+     */
+    pub fn row<I: Index>(&mut self, index: I) -> Row<'_, 'a> {
+        let mut suffix = Vec::new();
+        index.append_to(&mut suffix);
+        let width = self.columns.len();
+        Row {
+            columns: self.columns,
+            cells:   self.rows.entry(suffix).or_insert_with(|| (0..width).map(|_| None).collect()),
+        }
+    }
+
+    /**
+     * Whether there is a row for the given index already.
+     */
+    pub fn has_row<I: Index>(&self, index: &I) -> bool {
+        let mut suffix = Vec::new();
+        index.append_to(&mut suffix);
+        self.rows.contains_key(&suffix)
+    }
+
+    /**
+     * Make sure every row has a value for each mandatory column.
+     */
+    pub fn check_complete(&self) -> Result<()> {
+        for (suffix, cells) in &self.rows {
+            if let Some(column) = missing_column(self.columns, self.index, cells) {
+                bail!("Row {} lacks mandatory column {}", OID::from_vec(suffix), column.name);
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Move all cells into the value tree, using <table oid>.<column>.<index> as their OID.
+     * Rows that lack a mandatory column are left out with a warning.
+     */
+    pub fn insert_into(self, values: &mut BTreeMap<OID, Value<'a>>) {
+        for (suffix, cells) in self.rows {
+            if let Some(column) = missing_column(self.columns, self.index, &cells) {
+                eprintln!("warning: Leaving out row {} of {}, it lacks mandatory column {}",
+                          OID::from_vec(&suffix), OID::from_vec(&self.oid), column.name);
+                continue;
+            }
+            for (column, cell) in self.columns.iter().zip(cells) {
+                if self.index.contains(&column.name) {
+                    continue;
+                }
+                if let Some(value) = cell {
+                    let mut oid = self.oid.clone();
+                    oid.push(column.number);
+                    oid.extend_from_slice(&suffix);
+                    values.insert(OID::from_vec(&oid), value);
+                }
+            }
+        }
+    }
+}
+
+fn missing_column<'c>(columns: &'c [Column], index: &[&str], cells: &[Option<Value>]) -> Option<&'c Column> {
+    columns.iter().zip(cells.iter())
+        .find(|&(column, cell)| column.mandatory && cell.is_none() && !index.contains(&column.name))
+        .map(|(column, _)| column)
+}
+
+impl<'t, 'a> Row<'t, 'a> {
+    /**
+     * Set the cell of the column with the given name.
+     *
+     * Panics if the table has no such column, which is a bug in the table definition.
+     */
+    pub fn set(&mut self, name: &str, value: Value<'a>) -> &mut Self {
+        let pos = self.columns.iter()
+            .position(|column| column.name == name)
+            .unwrap_or_else(|| panic!("Table has no column named {}", name));
+        debug_assert!(
+            Syntax::of(&value) == Some(self.columns[pos].syntax),
+            "Value for {} does not match its declared syntax {:?}", name, self.columns[pos].syntax
+        );
+        self.cells[pos] = Some(value);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use table::Syntax::*;
+
+    const COLUMNS: &[Column] = &[
+        Column::new(     1, "testIndex", Integer),
+        Column::new(     2, "testName",  OctetString),
+        Column::optional(4, "testCount", Counter32),
+    ];
+
+    fn encode<I: Index>(index: I) -> Vec<u32> {
+        let mut suffix = Vec::new();
+        index.append_to(&mut suffix);
+        suffix
+    }
+
+    #[test]
+    fn indexes() {
+        assert_eq!(encode(7u32), vec![7]);
+        // Strings are prefixed with their length
+        assert_eq!(encode("eth0"), vec![4, 101, 116, 104, 48]);
+        assert_eq!(encode(String::from("")), vec![0]);
+        assert_eq!(encode(&[1u8, 2][..]), vec![2, 1, 2]);
+        // IpAddress and MacAddress are not
+        assert_eq!(encode([192u8, 168, 0, 1]), vec![192, 168, 0, 1]);
+        assert_eq!(encode([0x3cu8, 0x97, 0x0e, 0xa1, 0xb2, 0x01]), vec![60, 151, 14, 161, 178, 1]);
+        // OIDs are
+        assert_eq!(encode(OID::from_parts(&["1.3.6.1"])), vec![4, 1, 3, 6, 1]);
+        // Tuples are simply concatenated
+        assert_eq!(encode((2u32, "lo", [127u8, 0, 0, 1])), vec![2, 2, 108, 111, 127, 0, 0, 1]);
+    }
+
+    #[test]
+    fn incomplete_rows() {
+        let mut table = Table::new("1.3.6.1.4.1.99999.1.1", COLUMNS);
+        table.row(1u32)
+            .set("testIndex", Value::Integer(1))
+            .set("testName",  Value::OctetStr("one"));
+        assert!(table.check_complete().is_ok());
+
+        table.row(2u32).set("testIndex", Value::Integer(2));
+        assert_eq!(table.check_complete().unwrap_err().to_string(), "Row 2 lacks mandatory column testName");
+
+        // Left out rather than exported without it
+        let mut values = BTreeMap::new();
+        table.insert_into(&mut values);
+        assert_eq!(values.keys().map(OID::to_string).collect::<Vec<_>>(), vec![
+            "1.3.6.1.4.1.99999.1.1.1.1", "1.3.6.1.4.1.99999.1.1.2.1",
+        ]);
+    }
+
+    #[test]
+    fn oids() {
+        let mut table = Table::new("1.3.6.1.4.1.99999.1.1", COLUMNS);
+        table.row((3u32, "a"))
+            .set("testIndex", Value::Integer(3))
+            .set("testName",  Value::OctetStr("three"))
+            .set("testCount", Value::Counter32(42));
+        table.row((1u32, "b"))
+            .set("testIndex", Value::Integer(1))
+            .set("testName",  Value::OctetStr("one"));
+        assert!(table.has_row(&(1u32, "b")));
+        assert!(!table.has_row(&(1u32, "a")));
+
+        let mut values = BTreeMap::new();
+        table.insert_into(&mut values);
+        // <table oid>.<column>.<index>, leaving out unset optional cells
+        assert_eq!(values.into_iter().collect::<Vec<_>>(), vec![
+            (OID::from_parts(&["1.3.6.1.4.1.99999.1.1.1.1.1.98"]), Value::Integer(1)),
+            (OID::from_parts(&["1.3.6.1.4.1.99999.1.1.1.3.1.97"]), Value::Integer(3)),
+            (OID::from_parts(&["1.3.6.1.4.1.99999.1.1.2.1.1.98"]), Value::OctetStr("one")),
+            (OID::from_parts(&["1.3.6.1.4.1.99999.1.1.2.3.1.97"]), Value::OctetStr("three")),
+            (OID::from_parts(&["1.3.6.1.4.1.99999.1.1.4.3.1.97"]), Value::Counter32(42)),
+        ]);
+    }
+
+    #[test]
+    fn index_columns() {
+        let def = TableDef {
+            name:        "testTable",
+            entry_oid:   "1.3.6.1.4.1.99999.1.1",
+            description: "",
+            index:       &["testIndex"],
+            columns:     COLUMNS,
+        };
+        let mut table = Table::from_def(&def);
+        table.row(1u32).set("testName", Value::OctetStr("one"));
+        assert!(table.check_complete().is_ok());
+
+        // Not-accessible, so only the index carries it
+        let mut values = BTreeMap::new();
+        table.insert_into(&mut values);
+        assert_eq!(values.keys().map(OID::to_string).collect::<Vec<_>>(), vec!["1.3.6.1.4.1.99999.1.1.2.1"]);
+    }
+}
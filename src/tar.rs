@@ -0,0 +1,271 @@
+use std::fs;
+use std::io::{Read,Write};
+use std::os::unix::fs::symlink;
+use std::path::{Path,PathBuf,Component};
+use errors::*;
+
+const BLOCK: usize = 512;
+
+const REGULAR: u8 = b'0';
+const SYMLINK: u8 = b'2';
+const DIRECTORY: u8 = b'5';
+
+/// Far more than any captured file, but archives come from elsewhere and headers may lie
+const MAX_SIZE: u64 = 1 << 30;
+
+/**
+ * What an archive entry is, along with its content.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Entry {
+    File(Vec<u8>),
+    Dir,
+    Symlink(PathBuf),
+}
+
+/**
+ * Writes a ustar archive, which is all that is needed to move a capture of
+ * /proc, /sys and /dev around: files, directories and symlinks.
+ */
+pub struct Builder<W: Write> {
+    out: W,
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    // Leave room for the terminating NUL
+    let text = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..text.len()].copy_from_slice(text.as_bytes());
+}
+
+fn read_octal(field: &[u8]) -> Result<u64> {
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|chr: char| chr == '\0' || chr == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).chain_err(|| format!("Invalid number {:?} in tar header", text))
+}
+
+fn read_str(field: &[u8]) -> String {
+    let len = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).into_owned()
+}
+
+impl<W: Write> Builder<W> {
+    pub fn new(out: W) -> Builder<W> {
+        Builder { out }
+    }
+
+    /**
+     * Add an entry. path is relative to the root of the archive.
+     */
+    pub fn append(&mut self, path: &str, entry: &Entry) -> Result<()> {
+        let mut header = [0u8; BLOCK];
+
+        // Names longer than 100 bytes are split at a / into prefix and name
+        let (prefix, name) =
+            if path.len() <= 100 {
+                ("", path)
+            }
+            else {
+                match path.char_indices().rev().find(|&(pos, chr)| chr == '/' && pos <= 155 && path.len() - pos - 1 <= 100) {
+                    Some((pos, _)) => (&path[..pos], &path[pos + 1..]),
+                    None           => bail!("Path too long for a tar archive: {}", path)
+                }
+            };
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        let (mode, typeflag, size) = match *entry {
+            Entry::File(ref content)   => (0o644, REGULAR, content.len()),
+            Entry::Dir                 => (0o755, DIRECTORY, 0),
+            Entry::Symlink(ref target) => {
+                let target = target.to_str().ok_or("Symlink target is not UTF-8")?;
+                if target.len() > 100 {
+                    bail!("Symlink target too long for a tar archive: {}", target);
+                }
+                header[157..157 + target.len()].copy_from_slice(target.as_bytes());
+                (0o777, SYMLINK, 0)
+            }
+        };
+        write_octal(&mut header[100..108], mode);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], size as u64);
+        write_octal(&mut header[136..148], 0);
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // The checksum is calculated with the checksum field set to spaces
+        header[148..156].copy_from_slice(b"        ");
+        let checksum = header.iter().map(|&byte| byte as u64).sum::<u64>();
+        write_octal(&mut header[148..155], checksum);
+
+        self.out.write_all(&header).chain_err(|| "Could not write archive")?;
+        if let Entry::File(ref content) = *entry {
+            self.out.write_all(content).chain_err(|| "Could not write archive")?;
+            let padding = (BLOCK - content.len() % BLOCK) % BLOCK;
+            self.out.write_all(&vec![0u8; padding]).chain_err(|| "Could not write archive")?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Write the end-of-archive marker and return the writer.
+     */
+    pub fn finish(mut self) -> Result<W> {
+        self.out.write_all(&[0u8; 2 * BLOCK]).chain_err(|| "Could not write archive")?;
+        Ok(self.out)
+    }
+}
+
+/**
+ * Read all entries of a ustar archive. Other entry types, such as devices, are skipped.
+ */
+pub fn read_entries<R: Read>(mut input: R) -> Result<Vec<(String, Entry)>> {
+    let mut entries = Vec::new();
+    loop {
+        let mut header = [0u8; BLOCK];
+        if input.read_exact(&mut header).is_err() || header.iter().all(|&byte| byte == 0) {
+            // Archives just end after the last entry if they're not written by the book
+            return Ok(entries);
+        }
+
+        let stored = read_octal(&header[148..156])?;
+        header[148..156].copy_from_slice(b"        ");
+        if header.iter().map(|&byte| byte as u64).sum::<u64>() != stored {
+            bail!("Invalid checksum in tar header");
+        }
+
+        let name = read_str(&header[..100]);
+        let prefix = read_str(&header[345..500]);
+        let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        let size = read_octal(&header[124..136])?;
+        if size > MAX_SIZE {
+            bail!("{} is too large with {} bytes", path, size);
+        }
+
+        // Only as much is allocated as the archive really holds
+        let mut content = Vec::new();
+        input.by_ref().take(size).read_to_end(&mut content).chain_err(|| "Could not read archive")?;
+        if content.len() as u64 != size {
+            bail!("Archive ends in the middle of a file");
+        }
+        let size = size as usize;
+        let mut padding = vec![0u8; (BLOCK - size % BLOCK) % BLOCK];
+        input.read_exact(&mut padding).chain_err(|| "Archive ends in the middle of a file")?;
+
+        let entry = match header[156] {
+            REGULAR | 0 => Entry::File(content),
+            DIRECTORY   => Entry::Dir,
+            SYMLINK     => Entry::Symlink(PathBuf::from(read_str(&header[157..257]))),
+            _           => continue
+        };
+        entries.push((path, entry));
+    }
+}
+
+/**
+ * Extract an archive into dir, refusing entries that would end up outside of it,
+ * be it by .. or by a symlink extracted earlier, e.g. a -> /etc followed by a/passwd.
+ */
+pub fn extract<R: Read>(input: R, dir: &Path) -> Result<()> {
+    for (path, entry) in read_entries(input)? {
+        let relative = Path::new(&path);
+        if !relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+            bail!("Refusing to extract {}", path);
+        }
+        // The entry itself must not be a symlink either, as writing a file follows it
+        let mut current = dir.to_path_buf();
+        for component in relative.components() {
+            current.push(component);
+            if fs::symlink_metadata(&current).map(|meta| meta.file_type().is_symlink()).unwrap_or(false) {
+                bail!("Refusing to extract {} through the symlink {}", path, current.display());
+            }
+        }
+        let target = dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .chain_err(|| format!("Could not create {}", parent.display()))?;
+        }
+        match entry {
+            Entry::Dir                 => fs::create_dir_all(&target),
+            Entry::File(content)       => fs::write(&target, content),
+            Entry::Symlink(link_target) => symlink(link_target, &target),
+        }.chain_err(|| format!("Could not extract {}", target.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn round_trip() {
+        let long_name = format!("sys/devices/{}/net/eth0/statistics/rx_bytes", "pci0000:00/0000:00:1c.0".repeat(5));
+        let entries = vec![
+            (String::from("proc/"), Entry::Dir),
+            (String::from("proc/uptime"), Entry::File(b"1234.56 7890.12\n".to_vec())),
+            (String::from("proc/mounts"), Entry::Symlink(PathBuf::from("self/mounts"))),
+            (long_name, Entry::File(vec![b'x'; 513])),
+        ];
+
+        let mut builder = Builder::new(Vec::new());
+        for (path, entry) in &entries {
+            builder.append(path, entry).unwrap();
+        }
+        let archive = builder.finish().unwrap();
+        assert_eq!(archive.len() % BLOCK, 0);
+
+        let expected = entries.into_iter()
+            .map(|(path, entry)| (path.trim_end_matches('/').to_owned(), entry))
+            .collect::<Vec<_>>();
+        let read = read_entries(&archive[..]).unwrap().into_iter()
+            .map(|(path, entry)| (path.trim_end_matches('/').to_owned(), entry))
+            .collect::<Vec<_>>();
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn corrupt_archives() {
+        let mut builder = Builder::new(Vec::new());
+        builder.append("proc/uptime", &Entry::File(b"1234.56 7890.12\n".to_vec())).unwrap();
+        let archive = builder.finish().unwrap();
+
+        let mut corrupt = archive.clone();
+        corrupt[0] = b'x';
+        assert!(read_entries(&corrupt[..]).is_err());
+
+        assert!(read_entries(&archive[..BLOCK + 4]).is_err());
+
+        let mut builder = Builder::new(Vec::new());
+        builder.append("../etc/passwd", &Entry::File(Vec::new())).unwrap();
+        assert!(extract(&builder.finish().unwrap()[..], Path::new("/nonexistent")).is_err());
+
+        // A header claiming a huge file, without the file
+        let mut huge = archive.clone();
+        huge[124..136].copy_from_slice(b"77777777777\0");
+        huge[148..156].copy_from_slice(b"        ");
+        let checksum = huge[..BLOCK].iter().map(|&byte| byte as u64).sum::<u64>();
+        write_octal(&mut huge[148..155], checksum);
+        assert!(read_entries(&huge[..]).is_err());
+    }
+
+    #[test]
+    fn symlinks_out_of_dir() {
+        let dir = TempDir::new().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+
+        for &(link, target, path) in &[("a", "", "a/passwd"), ("b", "passwd", "b")] {
+            let mut builder = Builder::new(Vec::new());
+            builder.append(link, &Entry::Symlink(outside.join(target))).unwrap();
+            builder.append(path, &Entry::File(b"x".to_vec())).unwrap();
+            assert!(extract(&builder.finish().unwrap()[..], &dir.path().join("extracted")).is_err());
+        }
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+    }
+}
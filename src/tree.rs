@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::collections::Bound::{Included,Excluded,Unbounded};
+use std::time::{Instant,Duration};
+use oid::OID;
+use value::Value;
+use provider::Provider;
+use table::TableDef;
+
+struct Entry {
+    provider:  Box<dyn Provider>,
+    max_age:   Duration,
+    refreshed: Option<Instant>,
+    values:    BTreeMap<OID, Value<'static>>,
+}
+
+impl Entry {
+    fn refresh(&mut self) {
+        if let Some(refreshed) = self.refreshed {
+            if refreshed.elapsed() < self.max_age {
+                return;
+            }
+        }
+        self.values.clear();
+        self.provider.collect(&mut self.values);
+        self.refreshed = Some(Instant::now());
+    }
+}
+
+/**
+ * The MIB tree as seen by clients, stitched together from the subtrees of all providers.
+ *
+ * Providers are refreshed lazily: Only when a request needs a value from one of
+ * their subtrees and their cached values are older than their max age.
+ */
+#[derive(Default)]
+pub struct Tree {
+    entries:  Vec<Entry>,
+    // (subtree, index into entries), sorted by subtree
+    subtrees: Vec<(OID, usize)>,
+}
+
+impl Tree {
+    pub fn new() -> Tree {
+        Tree { entries: Vec::new(), subtrees: Vec::new() }
+    }
+
+    pub fn register<P: Provider + 'static>(&mut self, max_age: Duration, provider: P) {
+        let idx = self.entries.len();
+        for subtree in provider.subtrees() {
+            self.subtrees.push((subtree, idx));
+        }
+        self.subtrees.sort_by(|a, b| a.0.cmp(&b.0));
+        self.entries.push(Entry {
+            provider:  Box::new(provider),
+            max_age,
+            refreshed: None,
+            values:    BTreeMap::new(),
+        });
+    }
+
+    /**
+     * Definitions of the sunt enterprise tables of all providers.
+     */
+    pub fn tables(&self) -> Vec<&'static TableDef> {
+        self.entries.iter()
+            .flat_map(|entry| entry.provider.tables())
+            .collect()
+    }
+
+    /**
+     * Get the value of exactly the given OID.
+     */
+    pub fn get(&mut self, oid: &OID) -> Value<'static> {
+        let idx = match self.subtrees.iter().find(|&(subtree, _)| oid.is_subtree_of(subtree)) {
+            Some(&(_, idx)) => idx,
+            None            => return Value::NoSuchObject
+        };
+        let entry = &mut self.entries[idx];
+        entry.refresh();
+        match entry.values.get(oid) {
+            Some(value) => value.clone(),
+            None        => Value::NoSuchInstance
+        }
+    }
+
+    /**
+     * Get the first OID that follows the given one in lexicographical order, and its value.
+     */
+    pub fn get_next(&mut self, oid: &OID) -> Option<(OID, Value<'static>)> {
+        for &(ref subtree, idx) in &self.subtrees {
+            if oid > subtree && !oid.is_subtree_of(subtree) {
+                // This subtree lies entirely before oid, no need to look at it
+                continue;
+            }
+            let entry = &mut self.entries[idx];
+            entry.refresh();
+            let from = if oid < subtree { Included(subtree) } else { Excluded(oid) };
+            let next = entry.values
+                .range((from, Unbounded))
+                .next()
+                .filter(|&(next_oid, _)| next_oid.is_subtree_of(subtree));
+            if let Some((next_oid, value)) = next {
+                return Some((next_oid.clone(), value.clone()));
+            }
+        }
+        None
+    }
+
+    /**
+     * Get all values in the given subtree, in order.
+     */
+    pub fn walk(&mut self, subtree: &OID) -> Vec<(OID, Value<'static>)> {
+        let mut found = Vec::new();
+        let mut cursor = subtree.clone();
+        match self.get(subtree) {
+            Value::NoSuchObject | Value::NoSuchInstance => (),
+            value => found.push((subtree.clone(), value))
+        }
+        while let Some((oid, value)) = self.get_next(&cursor) {
+            if !oid.is_subtree_of(subtree) {
+                break;
+            }
+            cursor = oid.clone();
+            found.push((oid, value));
+        }
+        found
+    }
+}
@@ -1,44 +1,84 @@
-use snmp;
+use ber;
+use ber::{Writer,DecodeError,DecodeResult};
+use oid::OID;
 use std::fs::File;
 use std::path::PathBuf;
 use std::io::{BufReader,BufRead};
 
-#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value<'a> {
     Boolean(bool),
     Null,
     Integer(i64),
     OctetString(String),
     OctetStr(&'a str),
+    Octets(Vec<u8>),
+    ObjectIdentifier(OID),
 
     IpAddress([u8;4]),
     Counter32(u64),
     Unsigned32(u32),
     Timeticks(u32),
+    Opaque(Vec<u8>),
     Counter64(u64),
+
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
 }
 
 impl<'a> Value<'a> {
-    pub fn as_snmp_value(&self) -> snmp::Value {
-        match self {
-            &Value::Boolean(bool_)          => snmp::Value::Boolean(bool_),
-            &Value::Null                    => snmp::Value::Null,
-            &Value::Integer(i64_)           => snmp::Value::Integer(i64_),
-            &Value::OctetString(ref string) => snmp::Value::OctetString(string.as_bytes()),
-            &Value::OctetStr(str_)          => snmp::Value::OctetString(str_.as_bytes()),
-            &Value::IpAddress(ip)           => snmp::Value::IpAddress(ip),
-            &Value::Counter32(u64_)         => snmp::Value::Counter32((u64_ & 0xFFFFFFFF) as u32),
-            &Value::Unsigned32(u32_)        => snmp::Value::Unsigned32(u32_),
-            &Value::Timeticks(u32_)         => snmp::Value::Timeticks(u32_),
-            &Value::Counter64(u64_)         => snmp::Value::Counter64(u64_),
+    pub fn encode(&self, writer: &mut Writer) {
+        match *self {
+            Value::Boolean(bool_)            => writer.write_tlv(ber::BOOLEAN, &[if bool_ { 0xFF } else { 0x00 }]),
+            Value::Null                      => writer.write_null(ber::NULL),
+            Value::Integer(i64_)             => writer.write_integer(ber::INTEGER, i64_),
+            Value::OctetString(ref string)   => writer.write_tlv(ber::OCTET_STRING, string.as_bytes()),
+            Value::OctetStr(str_)            => writer.write_tlv(ber::OCTET_STRING, str_.as_bytes()),
+            Value::Octets(ref bytes)         => writer.write_tlv(ber::OCTET_STRING, bytes),
+            Value::ObjectIdentifier(ref oid) => writer.write_oid(oid.as_vec()),
+            Value::IpAddress(ip)             => writer.write_tlv(ber::IP_ADDRESS, &ip),
+            Value::Counter32(u64_)           => writer.write_unsigned(ber::COUNTER32, u64_ & 0xFFFFFFFF),
+            Value::Unsigned32(u32_)          => writer.write_unsigned(ber::GAUGE32, u32_ as u64),
+            Value::Timeticks(u32_)           => writer.write_unsigned(ber::TIMETICKS, u32_ as u64),
+            Value::Opaque(ref bytes)         => writer.write_tlv(ber::OPAQUE, bytes),
+            Value::Counter64(u64_)           => writer.write_unsigned(ber::COUNTER64, u64_),
+            Value::NoSuchObject              => writer.write_null(ber::NO_SUCH_OBJECT),
+            Value::NoSuchInstance            => writer.write_null(ber::NO_SUCH_INSTANCE),
+            Value::EndOfMibView              => writer.write_null(ber::END_OF_MIB_VIEW),
         }
     }
+
+    pub fn decode(tag: u8, content: &[u8]) -> DecodeResult<Value<'static>> {
+        let null = || if content.is_empty() { Ok(()) } else { Err(DecodeError::InvalidNull) };
+        Ok(match tag {
+            ber::NULL              => { null()?; Value::Null }
+            ber::INTEGER           => Value::Integer(ber::decode_i32(content)? as i64),
+            ber::OCTET_STRING      => Value::Octets(content.to_vec()),
+            ber::OBJECT_IDENTIFIER => Value::ObjectIdentifier(OID::from_vec(&ber::decode_oid(content)?)),
+            ber::IP_ADDRESS        => {
+                if content.len() != 4 {
+                    return Err(DecodeError::InvalidIpAddress);
+                }
+                Value::IpAddress([content[0], content[1], content[2], content[3]])
+            }
+            ber::COUNTER32         => Value::Counter32(ber::decode_unsigned(content, 32)?),
+            ber::GAUGE32           => Value::Unsigned32(ber::decode_unsigned(content, 32)? as u32),
+            ber::TIMETICKS         => Value::Timeticks(ber::decode_unsigned(content, 32)? as u32),
+            ber::OPAQUE            => Value::Opaque(content.to_vec()),
+            ber::COUNTER64         => Value::Counter64(ber::decode_unsigned(content, 64)?),
+            ber::NO_SUCH_OBJECT    => { null()?; Value::NoSuchObject }
+            ber::NO_SUCH_INSTANCE  => { null()?; Value::NoSuchInstance }
+            ber::END_OF_MIB_VIEW   => { null()?; Value::EndOfMibView }
+            _                      => return Err(DecodeError::UnknownTag(tag))
+        })
+    }
 }
 
 pub fn str_from_file(fpath: &PathBuf) -> Option<String> {
-    BufReader::new(File::open(fpath).unwrap())
+    BufReader::new(File::open(fpath).ok()?)
         .lines()
-        .nth(0)?
+        .next()?
         .ok()
 }
 
@@ -49,3 +89,10 @@ pub fn u32_from_file(fpath: &PathBuf) -> Option<u32> {
         .parse::<u32>()
         .ok()
 }
+
+pub fn u64_from_file(fpath: &PathBuf) -> Option<u64> {
+    str_from_file(fpath)?
+        .trim()
+        .parse::<u64>()
+        .ok()
+}
@@ -0,0 +1,495 @@
+//! Runs the collectors against captures of real-world hosts in tests/fixtures.
+
+extern crate sunt;
+extern crate tempfile;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use std::process;
+use sunt::agent::Agent;
+use sunt::capture;
+use sunt::config::Config;
+use sunt::host::Host;
+use sunt::oid::OID;
+use sunt::value::Value;
+use tempfile::TempDir;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn walk(host: Host) -> BTreeMap<String, Value<'static>> {
+    walk_with(host, &Config::default())
+}
+
+fn walk_with(host: Host, config: &Config) -> BTreeMap<String, Value<'static>> {
+    Agent::new()
+        .host(host)
+        .host_collectors(config)
+        .walk(&OID::from_vec(&vec![]))
+        .into_iter()
+        .map(|(oid, value)| (oid.to_string(), value))
+        .collect()
+}
+
+/**
+ * Get a column of a table as (index, value) pairs.
+ */
+fn column(values: &BTreeMap<String, Value<'static>>, column_oid: &str) -> Vec<(String, Value<'static>)> {
+    let prefix = format!("{}.", column_oid);
+    values.iter()
+        .filter(|&(oid, _)| oid.starts_with(&prefix))
+        .map(|(oid, value)| (oid[prefix.len()..].to_owned(), value.clone()))
+        .collect()
+}
+
+fn strings(values: &BTreeMap<String, Value<'static>>, column_oid: &str) -> Vec<String> {
+    column(values, column_oid).into_iter()
+        .map(|(_, value)| match value {
+            Value::OctetString(string) => string,
+            other => panic!("{} is not a string: {:?}", column_oid, other)
+        })
+        .collect()
+}
+
+fn integers(values: &BTreeMap<String, Value<'static>>, column_oid: &str) -> Vec<i64> {
+    column(values, column_oid).into_iter()
+        .map(|(_, value)| match value {
+            Value::Integer(int) => int,
+            other => panic!("{} is not an integer: {:?}", column_oid, other)
+        })
+        .collect()
+}
+
+#[test]
+fn mib_sys() {
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+    assert_eq!(values["1.3.6.1.2.1.1.1.0"],
+        Value::OctetString(String::from("Linux hive 4.9.0-3-amd64 #1 SMP Debian 4.9.30-2+deb9u2 (2017-06-26) x86_64")));
+    assert_eq!(values["1.3.6.1.2.1.1.5.0"], Value::OctetString(String::from("hive")));
+    assert_eq!(values["1.3.6.1.2.1.1.3.0"], Value::Timeticks(123456789));
+}
+
+#[test]
+fn mib_disks() {
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+
+    // Pseudo filesystems are skipped, and so is the bind mount of /data
+    assert_eq!(strings(&values, "1.3.6.1.4.1.2021.9.1.2"),
+        vec!["/", "/boot", "/boot/efi", "/data", "/var/lib/lxc", "/home"]);
+    assert_eq!(strings(&values, "1.3.6.1.2.1.25.2.3.1.3"), strings(&values, "1.3.6.1.4.1.2021.9.1.2"));
+    // LVs are named by their /dev/vg/lv symlinks, other device mapper devices by their /dev/mapper name
+    assert_eq!(strings(&values, "1.3.6.1.4.1.2021.9.1.3"),
+        vec!["/dev/vghive/root", "/dev/sda1", "/dev/sda2", "/dev/md0", "/dev/vghive/var-lxc", "/dev/crypted_home"]);
+    assert_eq!(integers(&values, "1.3.6.1.4.1.2021.9.1.6")[0], 5127839 * 4);
+    assert_eq!(integers(&values, "1.3.6.1.4.1.2021.9.1.9"), vec![55, 38, 1, 34, 22, 58]);
+    // vfat has no inodes, so it has no dskPercentNode
+    assert_eq!(column(&values, "1.3.6.1.4.1.2021.9.1.10").into_iter().map(|(idx, _)| idx).collect::<Vec<String>>(),
+        vec!["1", "2", "4", "5", "6"]);
+
+    // Loop devices are skipped
+    assert_eq!(strings(&values, "1.3.6.1.4.1.2021.13.15.1.1.2"),
+        vec!["sda", "sda1", "sda2", "sda3", "md0", "vghive/root", "vghive/var-lxc", "crypted_home"]);
+}
+
+#[test]
+fn mib_net() {
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+
+    // lo and the veth of the container are skipped, the rest is indexed by ifindex
+    assert_eq!(strings(&values, "1.3.6.1.2.1.2.2.1.2"), vec!["eno1", "eno2", "wlp3s0", "bond0", "bond0.20", "br0"]);
+    assert_eq!(integers(&values, "1.3.6.1.2.1.2.2.1.1"), vec![2, 3, 4, 5, 6, 7]);
+    assert_eq!(integers(&values, "1.3.6.1.2.1.2.2.1.3"), vec![6, 6, 71, 161, 135, 209]);
+    assert_eq!(integers(&values, "1.3.6.1.2.1.2.2.1.8"), vec![1, 1, 2, 1, 1, 1]);
+    assert_eq!(values["1.3.6.1.2.1.2.2.1.5.2"], Value::Unsigned32(1000000000));
+    // Bridges have no speed
+    assert_eq!(values["1.3.6.1.2.1.2.2.1.5.7"], Value::Unsigned32(0));
+    assert_eq!(values["1.3.6.1.2.1.31.1.1.1.6.5"], Value::Counter64(12962427553));
+
+    assert_eq!(values["1.3.6.1.2.1.2.2.1.6.2"], Value::Octets(vec![0x3c, 0x97, 0x0e, 0xa1, 0xb2, 0x01]));
+    // wlp3s0 is down because it has been taken down, the others are up
+    assert_eq!(integers(&values, "1.3.6.1.2.1.2.2.1.7"), vec![1, 1, 2, 1, 1, 1]);
+    // Everything has been up since before the agent started
+    assert_eq!(values["1.3.6.1.2.1.2.2.1.9.2"], Value::Timeticks(0));
+    assert_eq!(values["1.3.6.1.2.1.2.2.1.15.2"], Value::Counter32(17));
+    // Linux does not count sent broadcasts and multicasts
+    assert!(!values.contains_key("1.3.6.1.2.1.2.2.1.18.2"));
+    assert!(!values.contains_key("1.3.6.1.2.1.31.1.1.1.13.2"));
+    // Multicast packets are not counted as unicast
+    assert_eq!(values["1.3.6.1.2.1.2.2.1.11.2"], Value::Counter32(9432119 - 81234));
+    assert_eq!(values["1.3.6.1.2.1.31.1.1.1.8.2"], Value::Counter64(81234));
+    assert_eq!(strings(&values, "1.3.6.1.2.1.31.1.1.1.18"), vec!["uplink A", "uplink B", "", "uplink", "lxc vlan", ""]);
+    assert_eq!(integers(&values, "1.3.6.1.2.1.31.1.1.1.16"), vec![2, 2, 2, 2, 2, 2]);
+    assert_eq!(integers(&values, "1.3.6.1.2.1.31.1.1.1.17"), vec![1, 1, 1, 2, 2, 2]);
+
+    // br0 on bond0.20 on bond0 on eno1 and eno2, the veth in br0 is filtered
+    let stack = |column_oid: &str| column(&values, column_oid).into_iter().map(|(idx, _)| idx).collect::<Vec<String>>();
+    assert_eq!(stack("1.3.6.1.2.1.31.1.2.1.3"),
+        vec!["0.4", "0.7", "2.0", "3.0", "4.0", "5.2", "5.3", "6.5", "7.6"]);
+    assert_eq!(stack("1.3.6.1.2.1.77.1.1.1.1"),
+        vec!["0.2", "0.3", "0.4", "2.5", "3.5", "4.0", "5.6", "6.7", "7.0"]);
+
+    // Only the Ethernet NICs are in the dot3StatsTable
+    assert_eq!(integers(&values, "1.3.6.1.2.1.10.7.2.1.1"), vec![2, 3]);
+    assert_eq!(values["1.3.6.1.2.1.10.7.2.1.2.3"], Value::Counter32(1));
+    assert_eq!(values["1.3.6.1.2.1.10.7.2.1.3.3"], Value::Counter32(1));
+    assert_eq!(values["1.3.6.1.2.1.10.7.2.1.3.2"], Value::Counter32(0));
+    assert_eq!(integers(&values, "1.3.6.1.2.1.10.7.2.1.19"), vec![3, 2]);
+
+    // Link settings from ethtool, only asked for NICs, which the wireless driver does not support
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.3.1.1.2"], Value::Unsigned32(1000));
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.3.1.5.2"],
+        Value::OctetString(String::from("10baseT/Half 10baseT/Full 100baseT/Half 100baseT/Full 1000baseT/Full Autoneg TP")));
+    assert_eq!(integers(&values, "1.3.6.1.4.1.2147483647.1.3.1.2"), vec![3, 2]);
+    assert!(!values.contains_key("1.3.6.1.4.1.2147483647.1.3.1.2.4"));
+    // Driver counters are indexed by name, only the NICs have any
+    let name = |index: String| index.split('.').skip(2).map(|byte| byte.parse::<u8>().unwrap() as char).collect::<String>();
+    assert_eq!(column(&values, "1.3.6.1.4.1.2147483647.1.4.1.2").into_iter().map(|(index, _)| name(index)).collect::<Vec<_>>(), vec![
+        "rx_packets", "tx_packets", "rx_missed_errors", "tx_restart_queue", "rx_no_buffer_count",
+        "rx_packets", "tx_packets", "rx_crc_errors", "rx_missed_errors",
+    ]);
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.4.1.2.2.16.114.120.95.109.105.115.115.101.100.95.101.114.114.111.114.115"],
+        Value::Counter64(12));
+}
+
+#[test]
+fn mib_bonding() {
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.1.1.1.5"], Value::OctetString(String::from("bond0")));
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.1.1.2.5"], Value::OctetString(String::from("802.3ad")));
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.1.1.6.5"], Value::Unsigned32(2));
+    // Both slaves are up and in the active aggregator
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.1.1.7.5"], Value::Integer(2));
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.1.1.8.5"], Value::Integer(1));
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.1.1.9.5"], Value::Octets(vec![0x00, 0x1b, 0x21, 0x3a, 0x4c, 0x5d]));
+    // No active slave in 802.3ad mode
+    assert!(!values.contains_key("1.3.6.1.4.1.2147483647.1.1.1.4.5"));
+
+    assert_eq!(strings(&values, "1.3.6.1.4.1.2147483647.1.2.1.2"), vec!["eno1", "eno2"]);
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.2.1.4.5.3"], Value::Counter32(2));
+}
+
+#[test]
+fn mib_bridge() {
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+
+    assert_eq!(values["1.3.6.1.2.1.17.1.1.0"], Value::Octets(vec![0x3c, 0x97, 0x0e, 0xa1, 0xb2, 0x01]));
+    assert_eq!(values["1.3.6.1.2.1.17.1.2.0"], Value::Integer(2));
+    assert_eq!(values["1.3.6.1.2.1.17.4.2.0"], Value::Integer(300));
+    // bond0.20 and vethA1B2C3
+    assert_eq!(column(&values, "1.3.6.1.2.1.17.1.4.1.2"), vec![
+        (String::from("1"), Value::Integer(6)),
+        (String::from("2"), Value::Integer(9)),
+    ]);
+    assert_eq!(values["1.3.6.1.2.1.17.2.15.1.3.2"], Value::Integer(5));
+    assert_eq!(values["1.3.6.1.2.1.17.2.15.1.6.1"], Value::Octets(vec![0x80, 0x00, 0x3c, 0x97, 0x0e, 0xa1, 0xb2, 0x01]));
+    assert_eq!(values["1.3.6.1.2.1.17.2.15.1.9.2"], Value::Octets(vec![0x80, 0x02]));
+
+    // The LXC container behind vethA1B2C3, learned on port 2
+    assert_eq!(values["1.3.6.1.2.1.17.4.3.1.2.0.22.62.90.1.2"], Value::Integer(2));
+    assert_eq!(values["1.3.6.1.2.1.17.4.3.1.3.0.22.62.90.1.2"], Value::Integer(3));
+    // The bridge's own address
+    assert_eq!(values["1.3.6.1.2.1.17.4.3.1.3.60.151.14.161.178.1"], Value::Integer(4));
+    assert_eq!(column(&values, "1.3.6.1.2.1.17.4.3.1.2").len(), 4);
+
+    // The same, in the suntBridgeFdbTable indexed by br0's ifIndex
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.7.1.3.7.0.22.62.90.1.2"], Value::Integer(9));
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.7.1.4.7.0.22.62.90.1.2"], Value::Integer(3));
+    assert_eq!(values["1.3.6.1.4.1.2147483647.1.7.1.4.7.60.151.14.161.178.1"], Value::Integer(4));
+    assert_eq!(column(&values, "1.3.6.1.4.1.2147483647.1.7.1.2").len(), 4);
+}
+
+#[test]
+fn mib_ip() {
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+
+    // bond0, br0 (static IPv4, SLAAC, privacy and link-local IPv6), but not lo
+    assert_eq!(integers(&values, "1.3.6.1.2.1.4.34.1.3"), vec![5, 7, 7, 7, 7]);
+    assert_eq!(values["1.3.6.1.2.1.4.34.1.6.1.4.10.0.0.5"], Value::Integer(4));
+    assert_eq!(values["1.3.6.1.2.1.4.34.1.8.1.4.10.0.0.5"], Value::Timeticks(1250));
+    assert_eq!(values["1.3.6.1.2.1.4.34.1.9.1.4.10.0.0.5"], Value::Timeticks(123400000));
+    assert_eq!(values["1.3.6.1.2.1.4.34.1.5.1.4.192.168.20.5"],
+        Value::ObjectIdentifier(OID::from_parts(&["1.3.6.1.2.1.4.32.1.5.7.1.4.192.168.20.0.24"])));
+    assert_eq!(values["1.3.6.1.2.1.4.34.1.6.1.4.192.168.20.5"], Value::Integer(2));
+    let privacy = "2.16.32.1.13.184.0.32.0.0.0.0.0.0.161.178.195.212";
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.34.1.6.{}", privacy)], Value::Integer(6));
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.34.1.7.{}", privacy)], Value::Integer(2));
+    let link_local = "4.20.254.128.0.0.0.0.0.0.62.151.14.255.254.161.178.1.0.0.0.7";
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.34.1.6.{}", link_local)], Value::Integer(5));
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.34.1.7.{}", link_local)], Value::Integer(1));
+
+    // Both IPv6 global addresses share their router advertised prefix
+    let prefix = "7.2.16.32.1.13.184.0.32.0.0.0.0.0.0.0.0.0.0.64";
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.32.1.5.{}", prefix)], Value::Integer(5));
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.32.1.7.{}", prefix)], Value::Integer(1));
+    assert_eq!(column(&values, "1.3.6.1.2.1.4.32.1.5").len(), 4);
+
+    assert_eq!(column(&values, "1.3.6.1.2.1.4.20.1.2"), vec![
+        (String::from("10.0.0.5"), Value::Integer(5)),
+        (String::from("192.168.20.5"), Value::Integer(7)),
+    ]);
+    assert_eq!(values["1.3.6.1.2.1.4.20.1.3.192.168.20.5"], Value::IpAddress([255, 255, 255, 0]));
+    assert_eq!(values["1.3.6.1.2.1.4.20.1.4.192.168.20.5"], Value::Integer(1));
+
+    // The main table, without the local one and table 1000
+    assert_eq!(values["1.3.6.1.2.1.4.24.6.0"], Value::Unsigned32(12));
+    assert_eq!(values["1.3.6.1.2.1.4.24.3.0"], Value::Unsigned32(8));
+    let route = |column: u32, index: &str| values[&format!("1.3.6.1.2.1.4.24.7.1.{}.{}", column, index)].clone();
+    // default via 10.0.0.1 dev bond0 proto dhcp metric 100
+    let default = "1.4.0.0.0.0.0.2.0.0.1.4.10.0.0.1";
+    assert_eq!((route(7, default), route(8, default), route(9, default), route(12, default)),
+        (Value::Integer(5), Value::Integer(4), Value::Integer(1), Value::Integer(100)));
+    // 10.0.0.0/24 dev bond0 proto kernel
+    let connected = "1.4.10.0.0.0.24.2.0.0.0.0";
+    assert_eq!((route(7, connected), route(8, connected), route(9, connected)),
+        (Value::Integer(5), Value::Integer(3), Value::Integer(2)));
+    assert_eq!(route(8, "1.4.10.99.0.0.16.2.0.0.0.0"), Value::Integer(5));
+    assert_eq!(route(9, "1.4.172.16.0.0.12.2.0.0.1.4.192.168.20.1"), Value::Integer(14));
+    // Both next hops of the multipath route
+    assert_eq!(route(7, "1.4.198.51.100.0.24.2.0.0.1.4.10.0.0.2"), Value::Integer(5));
+    assert_eq!(route(7, "1.4.198.51.100.0.24.2.0.0.1.4.192.168.20.2"), Value::Integer(7));
+    // default via fe80::1 dev br0 proto ra, and the link-local prefix of br0
+    let default6 = "2.16.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.2.0.0.4.20.254.128.0.0.0.0.0.0.0.0.0.0.0.0.0.1.0.0.0.7";
+    assert_eq!((route(7, default6), route(9, default6)), (Value::Integer(7), Value::Integer(4)));
+    assert_eq!(route(7, "4.20.254.128.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.7.64.2.0.0.0.0"), Value::Integer(7));
+
+    assert_eq!(values["1.3.6.1.2.1.4.24.4.1.5.0.0.0.0.0.0.0.0.0.10.0.0.1"], Value::Integer(5));
+    assert_eq!(values["1.3.6.1.2.1.4.24.4.1.6.10.99.0.0.255.255.0.0.0.0.0.0.0"], Value::Integer(2));
+    assert_eq!(values["1.3.6.1.2.1.4.24.4.1.2.172.16.0.0.255.240.0.0.0.192.168.20.1"], Value::IpAddress([255, 240, 0, 0]));
+
+    // Neighbors of bond0 and br0, but not of the veth or multicast ones
+    let neighbor = |column: u32, index: &str| values[&format!("1.3.6.1.2.1.4.35.1.{}.{}", column, index)].clone();
+    assert_eq!(integers(&values, "1.3.6.1.2.1.4.35.1.7"), vec![1, 5, 2, 3, 6, 1, 2, 7]);
+    assert_eq!((neighbor(4, "5.1.4.10.0.0.1"), neighbor(5, "5.1.4.10.0.0.1"), neighbor(6, "5.1.4.10.0.0.1")),
+        (Value::Octets(vec![0x00, 0x1b, 0x21, 0x3a, 0x4f, 0x01]), Value::Timeticks(123456539), Value::Integer(3)));
+    assert_eq!(neighbor(4, "5.1.4.10.0.0.7"), Value::Octets(vec![]));
+    assert_eq!(neighbor(6, "7.1.4.192.168.20.77"), Value::Integer(4));
+    assert_eq!(neighbor(7, "7.2.16.254.128.0.0.0.0.0.0.0.0.0.0.0.0.0.1"), Value::Integer(1));
+    assert_eq!(neighbor(7, "7.2.16.32.1.13.184.0.32.0.0.0.0.0.0.0.0.0.153"), Value::Integer(7));
+
+    // ipNetToMediaTable only has IPv4 neighbors with a link layer address
+    assert_eq!(column(&values, "1.3.6.1.2.1.4.22.1.4"), vec![
+        (String::from("5.10.0.0.1"), Value::Integer(3)),
+        (String::from("5.10.0.0.9"), Value::Integer(3)),
+        (String::from("7.192.168.20.1"), Value::Integer(3)),
+        (String::from("7.192.168.20.77"), Value::Integer(4)),
+    ]);
+}
+
+#[test]
+fn mib_netstat() {
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+
+    assert_eq!(values["1.3.6.1.2.1.4.1.0"], Value::Integer(1));
+    assert_eq!(values["1.3.6.1.2.1.4.3.0"], Value::Counter32(5739814203));
+    assert_eq!(values["1.3.6.1.2.1.4.25.0"], Value::Integer(2));
+    assert_eq!(values["1.3.6.1.2.1.4.26.0"], Value::Integer(64));
+    assert_eq!(values["1.3.6.1.2.1.5.3.0"], Value::Counter32(12840));
+    assert_eq!(values["1.3.6.1.2.1.6.4.0"], Value::Integer(-1));
+    assert_eq!(values["1.3.6.1.2.1.6.9.0"], Value::Unsigned32(83));
+    assert_eq!(values["1.3.6.1.2.1.6.12.0"], Value::Counter32(904132));
+    assert_eq!(values["1.3.6.1.2.1.6.17.0"], Value::Counter64(5607729154));
+    // IPv4 and IPv6 together
+    assert_eq!(values["1.3.6.1.2.1.7.3.0"], Value::Counter32(1533 + 12));
+    assert_eq!(values["1.3.6.1.2.1.7.9.0"], Value::Counter64(102873215 + 1289004));
+
+    assert_eq!(values["1.3.6.1.2.1.4.31.1.1.6.1"], Value::Counter64(6391285213877));
+    assert_eq!(values["1.3.6.1.2.1.4.31.1.1.6.2"], Value::Counter64(102854129031));
+    assert_eq!(values["1.3.6.1.2.1.4.31.1.1.23.1"], Value::Counter32(1270355));
+    assert_eq!(values["1.3.6.1.2.1.4.31.1.1.42.1"], Value::Counter32(913522));
+    // Neither broadcasts for IPv6 nor OutTransmits before Linux 6.3
+    assert!(!values.contains_key("1.3.6.1.2.1.4.31.1.1.42.2"));
+    assert!(!values.contains_key("1.3.6.1.2.1.4.31.1.1.30.1"));
+    assert_eq!(column(&values, "1.3.6.1.2.1.5.29.1.2"), vec![
+        (String::from("1"), Value::Counter32(48213)),
+        (String::from("2"), Value::Counter32(402311)),
+    ]);
+
+    // The suntNetstatTable is indexed by group and name
+    let index = |text: &str| format!("{}.{}", text.len(), text.bytes().map(|byte| byte.to_string()).collect::<Vec<String>>().join("."));
+    let netstat = |group: &str, name: &str| values[&format!("1.3.6.1.4.1.2147483647.1.5.1.3.{}.{}", index(group), index(name))].clone();
+    assert_eq!(netstat("TcpExt", "ListenOverflows"), Value::Counter64(4127));
+    assert_eq!(netstat("Udp", "RcvbufErrors"), Value::Counter64(1533));
+    assert_eq!(netstat("Udp6", "RcvbufErrors"), Value::Counter64(12));
+    assert!(!values.contains_key(&format!("1.3.6.1.4.1.2147483647.1.5.1.3.{}.{}", index("Udp"), index("InErrors"))));
+
+    // IPv6 only, for the interfaces in the ifTable
+    assert_eq!(column(&values, "1.3.6.1.2.1.4.31.3.1.3"), vec![
+        (String::from("2.2"), Value::Counter32(0)),
+        (String::from("2.3"), Value::Counter32(0)),
+        (String::from("2.5"), Value::Counter32(270111)),
+        (String::from("2.7"), Value::Counter32(178212)),
+    ]);
+    assert_eq!(values["1.3.6.1.2.1.4.31.3.1.17.2.5"], Value::Counter32(9));
+    assert_eq!(values["1.3.6.1.2.1.4.31.3.1.33.2.7"], Value::Counter64(214975196));
+    assert!(!values.contains_key("1.3.6.1.2.1.4.31.3.1.42.2.5"));
+}
+
+#[test]
+fn mib_sockets() {
+    let config = Config::parse("
+collectors:
+  socket_processes: { enabled: true }
+", "processes.yaml").unwrap();
+    let values = walk_with(Host::replay(fixture("hive")).unwrap(), &config);
+    let any6 = "2.16.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0";
+
+    // Sockets listening on the same port are listed once, owned by the lowest process ID
+    assert_eq!(column(&values, "1.3.6.1.2.1.6.20.1.4"), vec![
+        (String::from("1.4.0.0.0.0.22"),    Value::Unsigned32(812)),
+        (String::from("1.4.0.0.0.0.80"),    Value::Unsigned32(1047)),
+        (String::from("1.4.127.0.0.53.53"), Value::Unsigned32(634)),
+        (format!("{}.22", any6),            Value::Unsigned32(812)),
+        (format!("{}.9100", any6),          Value::Unsigned32(733)),
+    ]);
+
+    assert_eq!(values["1.3.6.1.2.1.6.19.1.7.1.4.192.168.20.5.22.1.4.192.168.20.77.51234"], Value::Integer(5));
+    assert_eq!(values["1.3.6.1.2.1.6.19.1.8.1.4.192.168.20.5.22.1.4.192.168.20.77.51234"], Value::Unsigned32(2231));
+    assert_eq!(values["1.3.6.1.2.1.6.19.1.8.1.4.192.168.20.5.80.1.4.192.168.20.31.60112"], Value::Unsigned32(1048));
+    // Nobody owns connections in TIME_WAIT
+    assert_eq!(values["1.3.6.1.2.1.6.19.1.7.1.4.10.0.0.5.44790.1.4.10.0.0.1.443"], Value::Integer(11));
+    assert_eq!(values["1.3.6.1.2.1.6.19.1.8.1.4.10.0.0.5.44790.1.4.10.0.0.1.443"], Value::Unsigned32(0));
+    assert_eq!(values["1.3.6.1.2.1.6.19.1.7.2.16.32.1.13.184.0.32.0.0.62.151.14.255.254.161.178.1.22.2.16.32.1.13.184.0.32.0.0.0.0.0.0.0.0.0.119.50022"],
+        Value::Integer(5));
+    assert_eq!(column(&values, "1.3.6.1.2.1.6.19.1.7").len(), 5);
+
+    // Sockets bound to the same port are told apart by their instance
+    assert_eq!(values["1.3.6.1.2.1.7.7.1.8.1.4.0.0.0.0.5353.1.4.0.0.0.0.0.1"], Value::Unsigned32(655));
+    assert_eq!(values["1.3.6.1.2.1.7.7.1.8.1.4.0.0.0.0.5353.1.4.0.0.0.0.0.2"], Value::Unsigned32(655));
+    assert_eq!(values["1.3.6.1.2.1.7.7.1.8.1.4.10.0.0.5.41877.1.4.10.0.0.1.123.1"], Value::Unsigned32(702));
+    // Kernel sockets, like those of WireGuard, have no process
+    assert_eq!(values[&format!("1.3.6.1.2.1.7.7.1.8.{}.51820.{}.0.1", any6, any6)], Value::Unsigned32(0));
+    assert_eq!(column(&values, "1.3.6.1.2.1.7.7.1.8").len(), 9);
+
+    assert_eq!(column(&values, "1.3.6.1.4.1.2147483647.1.6.1.2"), vec![
+        (String::from("1047"), Value::OctetString(String::from("nginx"))),
+        (String::from("1048"), Value::OctetString(String::from("nginx"))),
+        (String::from("2231"), Value::OctetString(String::from("sshd"))),
+        (String::from("634"),  Value::OctetString(String::from("systemd-resolve"))),
+        (String::from("655"),  Value::OctetString(String::from("avahi-daemon"))),
+        (String::from("681"),  Value::OctetString(String::from("dhclient"))),
+        (String::from("702"),  Value::OctetString(String::from("chronyd"))),
+        (String::from("733"),  Value::OctetString(String::from("prometheus-node"))),
+        (String::from("812"),  Value::OctetString(String::from("sshd"))),
+    ]);
+    // Process names are only exported if enabled, and processes are not even looked into otherwise
+    let values = walk(Host::replay(fixture("hive")).unwrap());
+    assert!(!values.keys().any(|oid| oid.starts_with("1.3.6.1.4.1.2147483647.1.6.")));
+    assert_eq!(values["1.3.6.1.2.1.6.19.1.8.1.4.192.168.20.5.22.1.4.192.168.20.77.51234"], Value::Unsigned32(0));
+}
+
+/**
+ * The hive fixture without its netlink dump, so collectors have to use /proc and /sys.
+ */
+fn hive_without_netlink() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join(".sunt")).unwrap();
+    for name in &["proc", "sys", "dev"] {
+        symlink(fixture("hive").join(name), dir.path().join(name)).unwrap();
+    }
+    for name in &["statvfs", "uname", "ethtool"] {
+        fs::copy(fixture("hive").join(".sunt").join(name), dir.path().join(".sunt").join(name)).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn netlink_and_sysfs_agree() {
+    let interfaces = |values: BTreeMap<String, Value<'static>>| values.into_iter()
+        .filter(|(oid, _)| ["1.3.6.1.2.1.2.", "1.3.6.1.2.1.10.", "1.3.6.1.2.1.31.", "1.3.6.1.2.1.77."].iter().any(|prefix| oid.starts_with(prefix)))
+        .collect::<BTreeMap<String, Value<'static>>>();
+    let config = Config::parse("
+filters:
+  interfaces:
+    - include: {}
+", "all.yaml").unwrap();
+
+    let dir = hive_without_netlink();
+    let netlink = interfaces(walk_with(Host::replay(fixture("hive")).unwrap(), &config));
+    let sysfs = interfaces(walk_with(Host::replay(dir.path()).unwrap(), &config));
+
+    assert_eq!(strings(&netlink, "1.3.6.1.2.1.2.2.1.2").len(), 9);
+    assert_eq!(netlink, sysfs);
+}
+
+#[test]
+fn routes_from_proc() {
+    let dir = hive_without_netlink();
+    let values = walk(Host::new(dir.path()));
+
+    // Only the first next hop of the multipath route
+    assert_eq!(values["1.3.6.1.2.1.4.24.6.0"], Value::Unsigned32(11));
+    assert_eq!(values["1.3.6.1.2.1.4.24.4.1.5.198.51.100.0.255.255.255.0.0.10.0.0.2"], Value::Integer(5));
+    assert_eq!(values["1.3.6.1.2.1.4.24.7.1.12.1.4.0.0.0.0.0.2.0.0.1.4.10.0.0.1"], Value::Integer(100));
+    let default6 = "2.16.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.2.0.0.4.20.254.128.0.0.0.0.0.0.0.0.0.0.0.0.0.1.0.0.0.7";
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.24.7.1.7.{}", default6)], Value::Integer(7));
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.24.7.1.9.{}", default6)], Value::Integer(4));
+    assert_eq!(values[&format!("1.3.6.1.2.1.4.24.7.1.12.{}", default6)], Value::Integer(1024));
+    assert_eq!(values["1.3.6.1.2.1.4.24.7.1.7.2.16.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.1.128.2.0.0.0.0"], Value::Integer(1));
+
+    // /proc/net/arp only tells complete, incomplete and permanent entries apart
+    assert_eq!(integers(&values, "1.3.6.1.2.1.4.35.1.7"), vec![6, 7, 6, 6, 6]);
+    assert_eq!(values["1.3.6.1.2.1.4.35.1.6.7.1.4.192.168.20.77"], Value::Integer(4));
+    assert!(!values.contains_key("1.3.6.1.2.1.4.35.1.5.5.1.4.10.0.0.1"));
+}
+
+#[test]
+fn filters() {
+    let config = Config::parse("
+filters:
+  filesystems:
+    - exclude: { mountpoint: /boot/efi }
+    - include: { fstype: tmpfs, options: noexec }
+  disks:
+    - exclude: { type: partition }
+  interfaces:
+    - include: { name: { regex: '^veth' } }
+    - exclude: { class: [vlan, bonding] }
+", "filters.yaml").unwrap();
+    let values = walk_with(Host::replay(fixture("hive")).unwrap(), &config);
+
+    assert_eq!(strings(&values, "1.3.6.1.4.1.2021.9.1.2"), vec!["/", "/run", "/boot", "/data", "/var/lib/lxc", "/home"]);
+    assert_eq!(strings(&values, "1.3.6.1.4.1.2021.13.15.1.1.2"), vec!["sda", "md0", "vghive/root", "vghive/var-lxc", "crypted_home"]);
+    assert_eq!(strings(&values, "1.3.6.1.2.1.2.2.1.2"), vec!["eno1", "eno2", "wlp3s0", "br0", "vethA1B2C3"]);
+}
+
+#[test]
+fn interface_classes() {
+    let config = Config::parse("
+filters:
+  interfaces:
+    - include: { class: [loopback, veth, wireguard] }
+", "filters.yaml").unwrap();
+    let values = walk_with(Host::replay(fixture("hive")).unwrap(), &config);
+
+    let types = strings(&values, "1.3.6.1.2.1.2.2.1.2").into_iter()
+        .zip(integers(&values, "1.3.6.1.2.1.2.2.1.3"))
+        .collect::<BTreeMap<String, i64>>();
+    assert_eq!(types["lo"], 24);
+    assert_eq!(types["vethA1B2C3"], 6);
+    assert_eq!(types["wg0"], 131);
+    assert_eq!(types.len(), 9);
+}
+
+#[test]
+fn capture_and_replay() {
+    let host = Host::replay(fixture("hive")).unwrap().record();
+    let original = walk(host.clone());
+
+    let dir = TempDir::new().unwrap();
+    let archive = dir.path().join("capture.tar");
+    capture::capture(&host, &archive).unwrap();
+    let replay_dirs = || fs::read_dir(env::temp_dir()).unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(&format!("sunt-replay-{}-", process::id())))
+        .count();
+    let before = replay_dirs();
+    let replayed = walk(capture::replay(&archive).unwrap());
+
+    assert_eq!(replayed, original);
+    // The archive was extracted somewhere that is gone again
+    assert_eq!(replay_dirs(), before);
+}